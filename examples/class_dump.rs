@@ -0,0 +1,55 @@
+//! Dump the Objective-C classes loaded in this process
+//!
+//! ```text
+//! class_dump [OUTPUT] [IMAGE_FILTER...]
+//! ```
+//!
+//! Writes to `/tmp/all_objc_classes.txt` by default, the path the build
+//! script reads. With filters, only images whose path contains one of them
+//! are dumped, e.g. `class_dump out.txt UIKitCore Foundation`. Frameworks
+//! that aren't linked can be loaded first with `DYLD_INSERT_LIBRARIES`.
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+
+use ios_sys::class_dump::{self, DumpedClass};
+use ios_sys::introspect;
+
+const DEFAULT_OUTPUT: &str = "/tmp/all_objc_classes.txt";
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let output = args.next().unwrap_or_else(|| DEFAULT_OUTPUT.to_string());
+    let filters: Vec<String> = args.collect();
+
+    let classes: Vec<DumpedClass> = if filters.is_empty() {
+        class_dump::dump_classes()
+    } else {
+        let mut classes: Vec<_> = introspect::image_names()
+            .filter(|image| filters.iter().any(|filter| image.contains(filter.as_str())))
+            .filter_map(|image| CString::new(image).ok())
+            .flat_map(|image| class_dump::dump_image(&image))
+            .collect();
+        classes.sort_by(|a, b| a.name.cmp(&b.name));
+        classes
+    };
+
+    match write(&output, &classes) {
+        Ok(()) => {
+            println!("Dumped {} classes to {}", classes.len(), output);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("Failed to write {}: {}", output, err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn write(path: &str, classes: &[DumpedClass]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    class_dump::write_class_dump(&mut out, classes)?;
+    out.flush()
+}
@@ -0,0 +1,185 @@
+//! Tests of the ad-hoc signature writer (src/codesign.rs) against the Mach-O fixtures
+
+use super::codesign::{SignError, Signature, sign_adhoc};
+use super::macho::LC_CODE_SIGNATURE;
+use super::macho::parse::MachO;
+use super::macho::tests::{BASE, Fixture, HEADER_SIZE};
+
+// 8K of code, then a __LINKEDIT whose end isn't 16-byte aligned
+const CODE_SIZE: usize = 0x2000;
+const LINKEDIT: usize = HEADER_SIZE + CODE_SIZE;
+const LINKEDIT_SIZE: usize = 0x123;
+const CODE_LIMIT: usize = (LINKEDIT + LINKEDIT_SIZE).next_multiple_of(16);
+
+const GET_TASK_ALLOW: &[u8] = b"<plist version=\"1.0\"><dict><key>get-task-allow</key><true/></dict></plist>";
+
+/// An unsigned dylib with some code and a `__LINKEDIT` at the end
+fn unsigned() -> Vec<u8> {
+    let mut fixture = Fixture::new(CODE_SIZE);
+    fixture.section("__text", HEADER_SIZE, CODE_SIZE);
+    fixture.segment("__LINKEDIT", BASE + 0x4000, LINKEDIT as u64, LINKEDIT_SIZE as u64);
+    for (i, byte) in fixture.data.iter_mut().enumerate().skip(HEADER_SIZE) {
+        *byte = i as u8;
+    }
+    let mut image = fixture.build();
+    image.resize(LINKEDIT + LINKEDIT_SIZE, 0xaa);
+    image
+}
+
+#[test]
+fn test_signature_appended_to_linkedit() {
+    let image = unsigned();
+    let signed = sign_adhoc(&image, "com.example.tweak", None).unwrap();
+
+    // LC_CODE_SIGNATURE points past the old end, and __LINKEDIT grows over it
+    let macho = MachO::parse(&signed).unwrap();
+    let blob = macho.code_signature.unwrap();
+    assert_eq!(blob.offset as usize, CODE_LIMIT);
+    assert_eq!(blob.offset as usize + blob.size as usize, signed.len());
+    let linkedit = macho.segment("__LINKEDIT").unwrap();
+    assert_eq!(linkedit.fileoff + linkedit.filesize, signed.len() as u64);
+    assert_eq!(signed[HEADER_SIZE..LINKEDIT + LINKEDIT_SIZE], image[HEADER_SIZE..]);
+}
+
+#[test]
+fn test_code_directory() {
+    let signed = sign_adhoc(&unsigned(), "com.example.tweak", None).unwrap();
+
+    let signature = Signature::of_image(&signed).unwrap();
+    let directory = &signature.code_directory;
+    assert_eq!(directory.identifier, "com.example.tweak");
+    assert_eq!(directory.flags, 0x2); // CS_ADHOC
+    assert_eq!((directory.page_size, directory.code_limit), (0x1000, CODE_LIMIT as u64));
+    assert_eq!(directory.code_hashes.len(), CODE_LIMIT.div_ceil(0x1000));
+    assert_eq!(directory.special_hashes.len(), 2);
+    assert_eq!((directory.exec_seg_base, directory.exec_seg_limit), (0, LINKEDIT as u64));
+    assert_eq!(signature.requirements.as_deref(), Some(&[0xfa, 0xde, 0x0c, 0x01, 0, 0, 0, 12, 0, 0, 0, 0][..]));
+    assert!(signature.matches(&signed));
+}
+
+#[test]
+fn test_tampered_page_does_not_match() {
+    let signed = sign_adhoc(&unsigned(), "com.example.tweak", None).unwrap();
+    let signature = Signature::of_image(&signed).unwrap();
+
+    let mut tampered = signed.clone();
+    tampered[HEADER_SIZE + 0x1800] ^= 1;
+    assert!(!signature.matches(&tampered));
+}
+
+#[test]
+fn test_entitlements_as_plist_and_der() {
+    let signed = sign_adhoc(&unsigned(), "com.example.tweak", Some(GET_TASK_ALLOW)).unwrap();
+
+    let signature = Signature::of_image(&signed).unwrap();
+    assert_eq!(signature.code_directory.special_hashes.len(), 7);
+    assert_eq!(signature.entitlements.as_deref(), Some(GET_TASK_ALLOW));
+    let mut der = vec![0x70, 0x1a, 0x02, 0x01, 0x01, 0xb0, 0x15, 0x30, 0x13, 0x0c, 0x0e];
+    der.extend_from_slice(b"get-task-allow");
+    der.extend_from_slice(&[0x01, 0x01, 0xff]);
+    assert_eq!(signature.der_entitlements, Some(der));
+    assert!(signature.matches(&signed));
+}
+
+#[test]
+fn test_der_entitlements_sorted_by_key() {
+    let entitlements = br#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <!-- nested values -->
+    <key>b</key>
+    <array><string>x&amp;y</string><integer>128</integer></array>
+    <key>a</key>
+    <false/>
+</dict>
+</plist>
+"#;
+    let signed = sign_adhoc(&unsigned(), "x", Some(entitlements)).unwrap();
+
+    let signature = Signature::of_image(&signed).unwrap();
+    let der = [
+        0x70, 0x1d, 0x02, 0x01, 0x01, 0xb0, 0x18, // version 1, then the dictionary
+        0x30, 0x06, 0x0c, 0x01, b'a', 0x01, 0x01, 0x00, // a = false
+        0x30, 0x0e, 0x0c, 0x01, b'b', 0x30, 0x09, // b = [
+        0x0c, 0x03, b'x', b'&', b'y', 0x02, 0x02, 0x00, 0x80, // "x&y", 128]
+    ];
+    assert_eq!(signature.der_entitlements.as_deref(), Some(&der[..]));
+    assert!(signature.matches(&signed));
+}
+
+#[test]
+fn test_entitlements_without_der_encoding() {
+    let data = b"<plist version=\"1.0\"><dict><key>k</key><data>AA==</data></dict></plist>";
+    assert_eq!(sign_adhoc(&unsigned(), "x", Some(data)), Err(SignError::BadEntitlements));
+}
+
+#[test]
+fn test_resigning_replaces_signature() {
+    let signed = sign_adhoc(&unsigned(), "com.example.tweak", Some(GET_TASK_ALLOW)).unwrap();
+    let resigned = sign_adhoc(&signed, "com.example.tweak", None).unwrap();
+
+    // The existing LC_CODE_SIGNATURE is reused rather than a second one added
+    let macho = MachO::parse(&resigned).unwrap();
+    assert_eq!(macho.load_commands().filter(|command| command.cmd == LC_CODE_SIGNATURE).count(), 1);
+    assert_eq!(macho.code_signature.unwrap().offset as usize, CODE_LIMIT);
+    let signature = Signature::of_image(&resigned).unwrap();
+    assert_eq!((signature.entitlements.as_deref(), signature.code_directory.special_hashes.len()), (None, 2));
+    assert_eq!(signature.der_entitlements, None);
+    assert!(signature.matches(&resigned));
+}
+
+#[test]
+fn test_unsignable_images() {
+    assert_eq!(sign_adhoc(b"not a mach-o", "x", None), Err(SignError::NotMachO));
+    assert_eq!(sign_adhoc(&Fixture::new(0x100).build(), "x", None), Err(SignError::NoLinkedit));
+    let mut trailing = unsigned();
+    trailing.extend_from_slice(&[0; 4]);
+    assert_eq!(sign_adhoc(&trailing, "x", None), Err(SignError::LinkeditNotLast));
+}
+
+#[test]
+fn test_no_room_for_load_command() {
+    // Two segment commands and one section end the commands at 0x100
+    let mut fixture = Fixture::new(0x100);
+    fixture.section("__text", 0x108, 8);
+    fixture.segment("__LINKEDIT", BASE + 0x4000, (HEADER_SIZE + 0x100) as u64, 0);
+    assert_eq!(sign_adhoc(&fixture.build(), "x", None), Err(SignError::NoRoomForLoadCommand));
+}
+
+#[test]
+fn test_old_signature_before_linkedit() {
+    let signed = sign_adhoc(&unsigned(), "x", None).unwrap();
+    let command = MachO::parse(&signed)
+        .unwrap()
+        .load_commands()
+        .find(|command| command.cmd == LC_CODE_SIGNATURE)
+        .unwrap()
+        .offset;
+
+    // dataoff in __TEXT, and in the load commands
+    for dataoff in [HEADER_SIZE as u32, 0] {
+        let mut image = signed.clone();
+        image[command + 8..command + 12].copy_from_slice(&dataoff.to_le_bytes());
+        assert_eq!(sign_adhoc(&image, "x", None), Err(SignError::LinkeditNotLast));
+    }
+}
+
+#[test]
+fn test_nsects_past_command() {
+    // Only the section that's in __TEXT's command is read
+    let mut image = unsigned();
+    image[32 + 64..32 + 68].copy_from_slice(&u32::MAX.to_le_bytes());
+    let signed = sign_adhoc(&image, "x", None).unwrap();
+    assert!(Signature::of_image(&signed).unwrap().matches(&signed));
+}
+
+#[test]
+fn test_truncated_entitlements_blob() {
+    // A SuperBlob whose entitlements blob is shorter than its header
+    let mut short = Vec::new();
+    for field in [0xfade_0cc0, 28, 1, 5, 20, 0xfade_7171, 6] {
+        short.extend_from_slice(&u32::to_be_bytes(field));
+    }
+    assert_eq!(Signature::parse(&short), None);
+}
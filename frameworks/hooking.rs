@@ -0,0 +1,284 @@
+//! Bindings for the hooking libraries tweaks link against
+//!
+//! CydiaSubstrate, libhooker and libblackjack ship no headers in the iOS SDKs;
+//! Theos carries TBD stubs for them in `$THEOS/vendor/lib`. The exported
+//! symbols come from those stubs and the signatures from the table below,
+//! since a TBD only lists names. ElleKit installs itself as all three
+//! libraries, so the same bindings work on ElleKit jailbreaks.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::tbd::parse_tbd_file;
+
+/// A hooking library and the symbols we know the signatures of
+struct HookingLibrary {
+    /// Name in the generated comments and warnings
+    name: &'static str,
+    /// Generated file in OUT_DIR, and the feature that enables it
+    module: &'static str,
+    /// TBD stub paths, relative to a Theos library directory
+    tbds: &'static [&'static str],
+    /// (symbol, Rust declaration)
+    functions: &'static [(&'static str, &'static str)],
+}
+
+const SUBSTRATE: HookingLibrary = HookingLibrary {
+    name: "CydiaSubstrate",
+    module: "substrate",
+    tbds: &["CydiaSubstrate.framework/CydiaSubstrate.tbd", "libsubstrate.tbd"],
+    functions: &[
+        (
+            "_MSHookFunction",
+            "pub fn MSHookFunction(symbol: *mut c_void, replace: *mut c_void, result: *mut *mut c_void);",
+        ),
+        (
+            "_MSHookMessageEx",
+            "pub fn MSHookMessageEx(class: Class, selector: SEL, replace: IMP, result: *mut IMP);",
+        ),
+        ("_MSHookMemory", "pub fn MSHookMemory(target: *mut c_void, data: *const c_void, size: usize);"),
+        ("_MSGetImageByName", "pub fn MSGetImageByName(file: *const c_char) -> MSImageRef;"),
+        ("_MSFindSymbol", "pub fn MSFindSymbol(image: MSImageRef, name: *const c_char) -> *mut c_void;"),
+        ("_MSCloseImage", "pub fn MSCloseImage(image: MSImageRef);"),
+    ],
+};
+
+const LIBHOOKER: HookingLibrary = HookingLibrary {
+    name: "libhooker",
+    module: "libhooker",
+    tbds: &["libhooker.tbd"],
+    functions: &[
+        ("_LHStrError", "pub fn LHStrError(err: LIBHOOKER_ERR) -> *const c_char;"),
+        (
+            "_LHHookFunctions",
+            "pub fn LHHookFunctions(hooks: *const LHFunctionHook, count: c_int) -> c_int;",
+        ),
+        (
+            "_LHPatchMemory",
+            "pub fn LHPatchMemory(patches: *const LHMemoryPatch, count: c_int) -> c_int;",
+        ),
+        ("_LHOpenImage", "pub fn LHOpenImage(path: *const c_char) -> *mut c_void;"),
+        ("_LHCloseImage", "pub fn LHCloseImage(image: *mut c_void);"),
+        (
+            "_LHFindSymbols",
+            "pub fn LHFindSymbols(image: *mut c_void, names: *const *const c_char, symbols: *mut *mut c_void, count: usize) -> bool;",
+        ),
+    ],
+};
+
+const LIBBLACKJACK: HookingLibrary = HookingLibrary {
+    name: "libblackjack",
+    module: "libhooker",
+    tbds: &["libblackjack.tbd"],
+    functions: &[(
+        "_LBHookMessage",
+        "pub fn LBHookMessage(class: Class, selector: SEL, replacement: *mut c_void, old: *mut *mut c_void) -> LIBHOOKER_ERR;",
+    )],
+};
+
+const LIBRARIES: &[HookingLibrary] = &[SUBSTRATE, LIBHOOKER, LIBBLACKJACK];
+
+/// Types the declarations refer to, per generated module
+fn module_prelude(module: &str) -> &'static str {
+    match module {
+        "substrate" => {
+            "/// An image loaded in the process, from `MSGetImageByName`\n\
+             pub type MSImageRef = *const c_void;\n\n"
+        }
+        "libhooker" => {
+            "/// Error codes returned by libhooker and libblackjack\n\
+             pub type LIBHOOKER_ERR = c_int;\n\
+             pub const LIBHOOKER_OK: LIBHOOKER_ERR = 0;\n\
+             pub const LIBHOOKER_ERR_SELECTOR_NOT_FOUND: LIBHOOKER_ERR = 1;\n\
+             pub const LIBHOOKER_ERR_SHORT_FUNC: LIBHOOKER_ERR = 2;\n\
+             pub const LIBHOOKER_ERR_BAD_INSN_AT_START: LIBHOOKER_ERR = 3;\n\
+             pub const LIBHOOKER_ERR_VM: LIBHOOKER_ERR = 4;\n\
+             pub const LIBHOOKER_ERR_NO_SYMBOL: LIBHOOKER_ERR = 5;\n\n\
+             pub const LHOptionsNone: c_int = 0;\n\
+             pub const LHOptionsSetJumpReg: c_int = 1;\n\n\
+             #[repr(C)]\n\
+             #[derive(Debug, Copy, Clone)]\n\
+             pub struct LHFunctionHookOptions {\n\
+             \x20   pub options: c_int,\n\
+             \x20   pub jmp_reg: c_int,\n\
+             }\n\n\
+             #[repr(C)]\n\
+             #[derive(Debug, Copy, Clone)]\n\
+             pub struct LHFunctionHook {\n\
+             \x20   pub function: *mut c_void,\n\
+             \x20   pub replacement: *mut c_void,\n\
+             \x20   pub oldptr: *mut c_void,\n\
+             \x20   pub options: *mut LHFunctionHookOptions,\n\
+             }\n\n\
+             #[repr(C)]\n\
+             #[derive(Debug, Copy, Clone)]\n\
+             pub struct LHMemoryPatch {\n\
+             \x20   pub destination: *mut c_void,\n\
+             \x20   pub data: *const c_void,\n\
+             \x20   pub size: usize,\n\
+             \x20   pub options: *mut c_void,\n\
+             }\n\n"
+        }
+        _ => "",
+    }
+}
+
+/// The Theos installation: `$THEOS`, or `~/theos` where its installer puts it
+pub fn find_theos() -> Option<PathBuf> {
+    let theos = env::var_os("THEOS")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join("theos")))?;
+    theos.exists().then_some(theos)
+}
+
+/// The first TBD stub of `library` under `theos`
+fn find_tbd(library: &HookingLibrary, theos: &Path) -> Option<PathBuf> {
+    ["vendor/lib", "lib"]
+        .iter()
+        .flat_map(|dir| library.tbds.iter().map(move |tbd| theos.join(dir).join(tbd)))
+        .find(|path| path.exists())
+}
+
+/// Generate `substrate.rs` and `libhooker.rs` in `out_path`
+///
+/// Without the TBD stubs every known function is declared, so the crate
+/// still builds in header-only mode.
+pub fn generate_hooking_bindings(theos: Option<&Path>, out_path: &Path) {
+    let mut modules: Vec<(&str, String)> = Vec::new();
+
+    for library in LIBRARIES {
+        let tbd = theos.and_then(|theos| find_tbd(library, theos));
+        let symbols = tbd.as_deref().and_then(parse_tbd_file).map(|info| info.symbols);
+        if tbd.is_none() {
+            println!(
+                "cargo:warning=ios-sys: No {} TBD found (set THEOS), declaring all known functions",
+                library.name
+            );
+        }
+
+        let output = match modules.iter_mut().find(|(module, _)| *module == library.module) {
+            Some((_, output)) => output,
+            None => {
+                let mut output = String::new();
+                output.push_str(&format!(
+                    "// Generated hooking library bindings ({})\n\n\
+                     use core::ffi::{{c_char, c_int, c_void}};\n\
+                     use crate::objc::{{Class, SEL, IMP}};\n\n",
+                    library.module
+                ));
+                output.push_str(module_prelude(library.module));
+                modules.push((library.module, output));
+                &mut modules.last_mut().unwrap().1
+            }
+        };
+        output.push_str(&library_bindings(library, tbd.as_deref(), symbols.as_deref()));
+    }
+
+    for (module, output) in modules {
+        fs::write(out_path.join(format!("{}.rs", module)), output)
+            .unwrap_or_else(|_| panic!("Failed to write {} bindings", module));
+    }
+}
+
+/// The extern block of one library
+///
+/// With `symbols` from a TBD, only the functions it exports are declared, and
+/// exports without a known signature are listed as comments.
+fn library_bindings(library: &HookingLibrary, tbd: Option<&Path>, symbols: Option<&[String]>) -> String {
+    let mut output = match tbd {
+        Some(tbd) => format!("// {} (from {})\n", library.name, tbd.display()),
+        None => format!("// {} (no TBD found, all known functions)\n", library.name),
+    };
+
+    output.push_str("unsafe extern \"C\" {\n");
+    for (symbol, declaration) in library.functions {
+        if symbols.is_none_or(|symbols| symbols.iter().any(|s| s == symbol)) {
+            output.push_str(&format!("    {}\n", declaration));
+        }
+    }
+    for symbol in symbols.unwrap_or_default() {
+        if !library.functions.iter().any(|(known, _)| known == symbol) {
+            let clean_symbol = symbol.strip_prefix('_').unwrap_or(symbol);
+            output.push_str(&format!(
+                "    // pub fn {}(...);  // Signature unknown - define manually if needed\n",
+                clean_symbol
+            ));
+        }
+    }
+    output.push_str("}\n\n");
+    output
+}
+
+/// Link the stubs of the enabled hooking libraries
+///
+/// The stubs live outside the SDK, so they are passed to the linker by path
+/// on every host.
+#[allow(dead_code)]
+pub fn link_hooking_libraries(theos: Option<&Path>, feature_enabled: &dyn Fn(&str) -> bool) {
+    for library in LIBRARIES.iter().filter(|library| feature_enabled(library.module)) {
+        match theos.and_then(|theos| find_tbd(library, theos)) {
+            Some(tbd) => println!("cargo:rustc-link-arg={}", tbd.display()),
+            None => println!(
+                "cargo:warning=ios-sys: Cannot link {}: no TBD stub found under $THEOS",
+                library.name
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_bindings_from_tbd() {
+        let symbols = vec![
+            "_MSHookFunction".to_string(),
+            "_MSHookMessageEx".to_string(),
+            "_MSDebug".to_string(),
+        ];
+        let output = library_bindings(&SUBSTRATE, Some(Path::new("CydiaSubstrate.tbd")), Some(&symbols));
+
+        assert!(output.contains("pub fn MSHookFunction(symbol: *mut c_void"));
+        assert!(output.contains("pub fn MSHookMessageEx(class: Class"));
+        assert!(!output.contains("pub fn MSFindSymbol"));
+        assert!(output.contains("// pub fn MSDebug(...);"));
+    }
+
+    #[test]
+    fn test_library_bindings_without_tbd() {
+        let output = library_bindings(&LIBHOOKER, None, None);
+        for (_, declaration) in LIBHOOKER.functions {
+            assert!(output.contains(declaration));
+        }
+        assert!(output.contains("no TBD found"));
+    }
+
+    #[test]
+    fn test_generate_from_theos() {
+        let theos = env::temp_dir().join("ios_sys_test_theos");
+        let lib = theos.join("vendor/lib");
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(
+            lib.join("libhooker.tbd"),
+            "--- !tapi-tbd-v3\narchs: [arm64]\ninstall-name: /usr/lib/libhooker.dylib\n\
+             exports:\n  - archs: [arm64]\n    symbols: [_LHHookFunctions, _LHStrError]\n",
+        )
+        .unwrap();
+
+        let out = theos.join("out");
+        fs::create_dir_all(&out).unwrap();
+        generate_hooking_bindings(Some(&theos), &out);
+
+        let libhooker = fs::read_to_string(out.join("libhooker.rs")).unwrap();
+        assert!(libhooker.contains("pub struct LHFunctionHook"));
+        assert!(libhooker.contains("pub fn LHHookFunctions"));
+        assert!(!libhooker.contains("pub fn LHPatchMemory"));
+        // libblackjack has no stub here, so it falls back to everything
+        assert!(libhooker.contains("pub fn LBHookMessage"));
+        assert!(fs::read_to_string(out.join("substrate.rs")).unwrap().contains("pub type MSImageRef"));
+
+        fs::remove_dir_all(theos).ok();
+    }
+}
@@ -0,0 +1,709 @@
+//! Mach-O reader for the build script
+//!
+//! The parsing is the crate's `macho` module, included by path; this adds the
+//! vm address view the Objective-C metadata reader needs to read arm64 images
+//! on the build host, where the pointers in data segments are either plain or
+//! encoded as chained fixups (what ld64 writes for iOS 15 and later).
+
+#[allow(dead_code)]
+#[path = "../src/macho.rs"]
+pub(crate) mod parse;
+
+// The local `MachO` shadows the parser's, which it wraps
+pub use parse::*;
+
+/// A pointer stored in an image, with its fixup encoding removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pointer {
+    /// An (unslid) address in the image, or 0 for NULL
+    Address(u64),
+    /// A reference to an imported symbol, by import ordinal
+    Bind(u32),
+}
+
+impl From<ChainedPointer> for Pointer {
+    fn from(pointer: ChainedPointer) -> Self {
+        match pointer {
+            ChainedPointer::Rebase(address) => Pointer::Address(address),
+            ChainedPointer::Bind { ordinal, .. } => Pointer::Bind(ordinal),
+        }
+    }
+}
+
+/// The vm address space of an image, as laid out in a file
+///
+/// Implemented by [`MachO`] for standalone images; a dyld shared cache maps
+/// the images it contains differently.
+pub trait ImageMemory {
+    /// The bytes from vm address `addr` to the end of its mapping
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]>;
+
+    /// The pointer stored at vm address `addr`
+    fn pointer_at(&self, addr: u64) -> Option<Pointer>;
+
+    /// The name of the symbol bound by import ordinal `ordinal`
+    fn import_name(&self, _ordinal: u32) -> Option<&str> {
+        None
+    }
+
+    /// The address relative method list selectors are offsets from
+    ///
+    /// Only set in the shared cache, where relative method lists flagged as
+    /// having direct selectors point at selector strings instead of at
+    /// selector references.
+    fn relative_selector_base(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// A parsed 64-bit Mach-O image
+#[derive(Debug)]
+pub struct MachO<'a> {
+    image: parse::MachO<'a>,
+    pub segments: Vec<Segment>,
+    /// The `LC_ID_DYLIB` install name, for dylibs and frameworks
+    pub install_name: Option<String>,
+    /// `DYLD_CHAINED_PTR_*` format of the data pointers, `None` if they are plain
+    pub pointer_format: Option<u16>,
+    /// Imported symbol names, by chained fixup import ordinal
+    pub imports: Vec<String>,
+}
+
+impl<'a> MachO<'a> {
+    /// Parse a thin 64-bit image; use [`arm64_slice`] first for fat binaries
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let image = parse::MachO::parse(data)?;
+        let fixups = image.parse_chained_fixups();
+        Some(MachO {
+            segments: image.segments.clone(),
+            install_name: image.install_name.clone(),
+            pointer_format: fixups.as_ref().and_then(ChainedFixups::pointer_format),
+            imports: fixups
+                .map(|fixups| fixups.imports.into_iter().map(|import| import.name).collect())
+                .unwrap_or_default(),
+            image,
+        })
+    }
+
+    /// The first section named `name`, in whichever segment holds it
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.image.section(name)
+    }
+
+    /// The address the image is based at: the vm address of `__TEXT`
+    pub fn base_address(&self) -> u64 {
+        self.image.base_address()
+    }
+
+    /// `size` bytes at file offset `offset` of the image, read through `memory`
+    ///
+    /// Load commands give `__LINKEDIT` data as file offsets. In a shared cache
+    /// they are offsets into the cache file instead, so they are translated to
+    /// vm addresses through the segment holding them.
+    pub fn file_bytes<'m>(&self, memory: &'m dyn ImageMemory, offset: u32, size: u32) -> Option<&'m [u8]> {
+        let offset = offset as u64;
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| offset >= segment.fileoff && offset - segment.fileoff < segment.filesize)?;
+        memory
+            .bytes_at(segment.vmaddr + (offset - segment.fileoff))?
+            .get(..size as usize)
+    }
+
+    /// The entries of the symbol table
+    pub fn symbols(&self, memory: &dyn ImageMemory) -> Vec<Symbol> {
+        let Some(symtab) = self.image.symtab else {
+            return Vec::new();
+        };
+        let nlists = self.file_bytes(memory, symtab.symoff, symtab.nsyms * 16);
+        let strings = self.file_bytes(memory, symtab.stroff, symtab.strsize);
+        match (nlists, strings) {
+            (Some(nlists), Some(strings)) => parse_nlists(nlists, strings),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The exported symbols and their offsets from the base address
+    ///
+    /// From the exports trie, or from the symbol table for images without one.
+    /// Re-exports have no offset in this image and are left out.
+    pub fn exports(&self, memory: &dyn ImageMemory) -> Vec<(String, u64)> {
+        if let Some(LinkeditData { offset, size }) = self.image.exports_trie {
+            return self
+                .file_bytes(memory, offset, size)
+                .map(parse::parse_export_trie)
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|export| export.offset().map(|offset| (export.name, offset)))
+                .collect();
+        }
+        let base = self.base_address();
+        self.symbols(memory)
+            .into_iter()
+            .filter(|symbol| symbol.is_exported() && !symbol.name.is_empty())
+            .map(|symbol| (symbol.name, symbol.value.wrapping_sub(base)))
+            .collect()
+    }
+}
+
+impl ImageMemory for MachO<'_> {
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        // Zero-fill at the end of a segment isn't in the file
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| addr >= segment.vmaddr && addr - segment.vmaddr < segment.filesize)?;
+        let start = (segment.fileoff + (addr - segment.vmaddr)) as usize;
+        let end = (segment.fileoff + segment.filesize) as usize;
+        self.image.data().get(start..end)
+    }
+
+    fn pointer_at(&self, addr: u64) -> Option<Pointer> {
+        let raw = read_u64(self.bytes_at(addr)?, 0)?;
+        Some(match self.pointer_format {
+            Some(format) => decode_chained_pointer(raw, format, self.base_address()).into(),
+            None => Pointer::Address(raw),
+        })
+    }
+
+    fn import_name(&self, ordinal: u32) -> Option<&str> {
+        self.imports.get(ordinal as usize).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a thin arm64 image with one segment covering the whole file
+    ///
+    /// The load commands fill the first `HEADER_SIZE` bytes; tests put their
+    /// data after that and address it at `base + offset`.
+    pub(crate) struct Fixture {
+        pub data: Vec<u8>,
+        base: u64,
+        file_offset: u64,
+        sections: Vec<(&'static str, usize, usize, u32, u32)>,
+        segments: Vec<(&'static str, u64, u64, u64)>,
+        install_name: Option<&'static str>,
+        commands: Vec<Vec<u8>>,
+    }
+
+    pub(crate) const BASE: u64 = 0x1_0000_0000;
+    pub(crate) const HEADER_SIZE: usize = 0x400;
+
+    impl Fixture {
+        pub(crate) fn new(size: usize) -> Self {
+            Self::with_base(BASE, size)
+        }
+
+        /// An image loaded at `base`, e.g. inside a shared cache
+        pub(crate) fn with_base(base: u64, size: usize) -> Self {
+            Fixture {
+                data: vec![0; HEADER_SIZE + size],
+                base,
+                file_offset: 0,
+                sections: Vec::new(),
+                segments: Vec::new(),
+                install_name: None,
+                commands: Vec::new(),
+            }
+        }
+
+        /// Place the image at `offset` in a bigger file
+        ///
+        /// The file offsets in a shared cache image are offsets in the cache.
+        pub(crate) fn file_offset(&mut self, offset: u64) {
+            self.file_offset = offset;
+        }
+
+        pub(crate) fn install_name(&mut self, name: &'static str) {
+            self.install_name = Some(name);
+        }
+
+        /// Declare section `name` at file offset `offset`
+        pub(crate) fn section(&mut self, name: &'static str, offset: usize, size: usize) {
+            self.sections.push((name, offset, size, 0, 0));
+        }
+
+        /// Declare a symbol pointer section of `count` pointers, whose entries
+        /// in the indirect symbol table start at `first_indirect`
+        pub(crate) fn pointer_section(
+            &mut self,
+            name: &'static str,
+            offset: usize,
+            count: usize,
+            section_type: u32,
+            first_indirect: u32,
+        ) {
+            self.sections.push((name, offset, count * 8, section_type, first_indirect));
+        }
+
+        /// Declare another segment, without sections, after `__TEXT`
+        pub(crate) fn segment(&mut self, name: &'static str, vmaddr: u64, fileoff: u64, size: u64) {
+            self.segments.push((name, vmaddr, fileoff, size));
+        }
+
+        /// Add a load command made of 32-bit fields after `cmd` and `cmdsize`
+        pub(crate) fn load_command(&mut self, cmd: u32, fields: &[u32]) {
+            let mut command = Vec::new();
+            command.extend_from_slice(&cmd.to_le_bytes());
+            command.extend_from_slice(&((8 + fields.len() * 4) as u32).to_le_bytes());
+            for field in fields {
+                command.extend_from_slice(&field.to_le_bytes());
+            }
+            self.commands.push(command);
+        }
+
+        pub(crate) fn put_u32(&mut self, offset: usize, value: u32) {
+            self.data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        }
+
+        pub(crate) fn put_u64(&mut self, offset: usize, value: u64) {
+            self.data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+        }
+
+        pub(crate) fn put_str(&mut self, offset: usize, value: &str) {
+            self.data[offset..offset + value.len()].copy_from_slice(value.as_bytes());
+            self.data[offset + value.len()] = 0;
+        }
+
+        /// Write the header and load commands, returning the file
+        pub(crate) fn build(mut self) -> Vec<u8> {
+            let mut commands = Vec::new();
+
+            let segment_size = 72 + 80 * self.sections.len();
+            let mut segment = vec![0u8; segment_size];
+            segment[0..4].copy_from_slice(&LC_SEGMENT_64.to_le_bytes());
+            segment[4..8].copy_from_slice(&(segment_size as u32).to_le_bytes());
+            segment[8..14].copy_from_slice(b"__TEXT");
+            segment[24..32].copy_from_slice(&self.base.to_le_bytes());
+            segment[32..40].copy_from_slice(&(self.data.len() as u64).to_le_bytes());
+            segment[40..48].copy_from_slice(&self.file_offset.to_le_bytes());
+            segment[48..56].copy_from_slice(&(self.data.len() as u64).to_le_bytes());
+            segment[64..68].copy_from_slice(&(self.sections.len() as u32).to_le_bytes());
+            for (i, (name, offset, size, flags, reserved1)) in self.sections.iter().enumerate() {
+                let section = &mut segment[72 + i * 80..72 + (i + 1) * 80];
+                section[..name.len()].copy_from_slice(name.as_bytes());
+                section[16..22].copy_from_slice(b"__TEXT");
+                section[32..40].copy_from_slice(&(self.base + *offset as u64).to_le_bytes());
+                section[40..48].copy_from_slice(&(*size as u64).to_le_bytes());
+                section[48..52].copy_from_slice(&(*offset as u32).to_le_bytes());
+                section[64..68].copy_from_slice(&flags.to_le_bytes());
+                section[68..72].copy_from_slice(&reserved1.to_le_bytes());
+            }
+            commands.push(segment);
+
+            for (name, vmaddr, fileoff, size) in &self.segments {
+                let mut segment = vec![0u8; 72];
+                segment[0..4].copy_from_slice(&LC_SEGMENT_64.to_le_bytes());
+                segment[4..8].copy_from_slice(&72u32.to_le_bytes());
+                segment[8..8 + name.len()].copy_from_slice(name.as_bytes());
+                segment[24..32].copy_from_slice(&vmaddr.to_le_bytes());
+                segment[32..40].copy_from_slice(&size.to_le_bytes());
+                segment[40..48].copy_from_slice(&fileoff.to_le_bytes());
+                segment[48..56].copy_from_slice(&size.to_le_bytes());
+                commands.push(segment);
+            }
+
+            if let Some(name) = self.install_name {
+                let size = (24 + name.len() + 1).next_multiple_of(8);
+                let mut command = vec![0u8; size];
+                command[0..4].copy_from_slice(&LC_ID_DYLIB.to_le_bytes());
+                command[4..8].copy_from_slice(&(size as u32).to_le_bytes());
+                command[8..12].copy_from_slice(&24u32.to_le_bytes());
+                command[24..24 + name.len()].copy_from_slice(name.as_bytes());
+                commands.push(command);
+            }
+            commands.append(&mut self.commands);
+
+            let mut header = Vec::new();
+            header.extend_from_slice(&MH_MAGIC_64.to_le_bytes());
+            header.extend_from_slice(&CPU_TYPE_ARM64.to_le_bytes());
+            header.extend_from_slice(&0u32.to_le_bytes());
+            header.extend_from_slice(&6u32.to_le_bytes()); // MH_DYLIB
+            header.extend_from_slice(&(commands.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(commands.iter().map(Vec::len).sum::<usize>() as u32).to_le_bytes());
+            header.extend_from_slice(&[0; 8]);
+            header.extend(commands.concat());
+            assert!(header.len() <= HEADER_SIZE, "fixture load commands overflow the header");
+
+            self.data[..header.len()].copy_from_slice(&header);
+            self.data
+        }
+    }
+
+    #[test]
+    fn test_parse_segments_and_sections() {
+        let mut fixture = Fixture::new(0x100);
+        fixture.install_name("/System/Library/Frameworks/Foundation.framework/Foundation");
+        fixture.section("__objc_classlist", HEADER_SIZE, 16);
+        fixture.put_u64(HEADER_SIZE, 0x1122_3344);
+        let data = fixture.build();
+
+        let macho = MachO::parse(&data).unwrap();
+        assert_eq!(macho.install_name.as_deref(), Some("/System/Library/Frameworks/Foundation.framework/Foundation"));
+        assert_eq!(macho.base_address(), BASE);
+
+        let section = macho.section("__objc_classlist").unwrap();
+        assert_eq!(section.addr, BASE + HEADER_SIZE as u64);
+        assert_eq!(section.size, 16);
+        assert_eq!(macho.pointer_at(section.addr), Some(Pointer::Address(0x1122_3344)));
+        assert_eq!(macho.bytes_at(BASE + data.len() as u64), None);
+    }
+
+    #[test]
+    fn test_symbols_and_exports() {
+        const SYMBOLS: usize = HEADER_SIZE;
+        const STRINGS: usize = HEADER_SIZE + 0x40;
+        const TRIE: usize = HEADER_SIZE + 0x80;
+
+        let mut fixture = Fixture::new(0x100);
+        fixture.load_command(LC_SYMTAB, &[SYMBOLS as u32, 2, STRINGS as u32, 0x20]);
+
+        // An exported function and a local one
+        fixture.put_str(STRINGS + 1, "_SBExported");
+        fixture.put_str(STRINGS + 13, "_local");
+        fixture.put_u32(SYMBOLS, 1);
+        fixture.data[SYMBOLS + 4] = N_SECT | N_EXT;
+        fixture.data[SYMBOLS + 5] = 1;
+        fixture.put_u64(SYMBOLS + 8, BASE + 0x1234);
+        fixture.put_u32(SYMBOLS + 16, 13);
+        fixture.data[SYMBOLS + 20] = N_SECT;
+        fixture.put_u64(SYMBOLS + 24, BASE + 0x5678);
+
+        let data = fixture.build();
+        let macho = MachO::parse(&data).unwrap();
+        let symbols = macho.symbols(&macho);
+        assert_eq!(symbols.len(), 2);
+        assert_eq!(symbols[1].name, "_local");
+        assert!(symbols[0].is_exported() && !symbols[1].is_exported());
+        // Without a trie, exports come from the symbol table
+        assert_eq!(macho.exports(&macho), [("_SBExported".to_string(), 0x1234)]);
+
+        // "_" -> { "foo" (0x10), "bar" (re-export), "baz" (0x3000, with a multi-byte offset) }
+        #[rustfmt::skip]
+        let trie = [
+            0, 1, b'_', 0, 5,
+            0, 3, b'f', b'o', b'o', 0, 24, b'b', b'a', b'r', 0, 28, b'b', b'a', b'z', 0, 36,
+            0, 0,
+            2, 0, 0x10, 0,
+            5, 0x08, 1, b'_', b'x', 0, 0, 0,
+            3, 0, 0x80, 0x60, 0,
+        ];
+        let mut fixture = Fixture::new(0x100);
+        fixture.load_command(LC_DYLD_EXPORTS_TRIE, &[TRIE as u32, trie.len() as u32]);
+        fixture.data[TRIE..TRIE + trie.len()].copy_from_slice(&trie);
+        let data = fixture.build();
+        let macho = MachO::parse(&data).unwrap();
+        assert_eq!(
+            macho.exports(&macho),
+            [("_baz".to_string(), 0x3000), ("_foo".to_string(), 0x10)]
+        );
+
+        // The full entries keep the re-export
+        let exports = parse::parse_export_trie(&trie);
+        assert_eq!(exports.len(), 3);
+        assert_eq!(exports[0].name, "_bar");
+        assert_eq!(
+            exports[0].target,
+            parse::ExportTarget::Reexport { ordinal: 1, name: Some("_x".to_string()) }
+        );
+        assert_eq!(exports[0].offset(), None);
+    }
+
+    /// A load command's 32-bit fields followed by a string, as dylib commands store names
+    fn with_string(fields: &[u32], string: &str) -> Vec<u32> {
+        let mut bytes: Vec<u8> = fields.iter().flat_map(|field| field.to_le_bytes()).collect();
+        bytes.extend_from_slice(string.as_bytes());
+        bytes.resize((bytes.len() + 1).next_multiple_of(8), 0);
+        bytes.chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    #[test]
+    fn test_load_commands_and_indirect_symbols() {
+        const INDIRECT: usize = HEADER_SIZE + 0x80;
+
+        let mut fixture = Fixture::new(0x100);
+        fixture.install_name("/usr/lib/libtweak.dylib");
+        fixture.load_command(LC_LOAD_DYLIB, &with_string(&[24, 2, 0x1_0000, 0x1_0000], "/usr/lib/libobjc.A.dylib"));
+        fixture.load_command(LC_UUID, &[0x0403_0201, 0x0807_0605, 0x0c0b_0a09, 0x100f_0e0d]);
+        let mut dysymtab = [0u32; 18];
+        dysymtab[..6].copy_from_slice(&[0, 1, 1, 2, 3, 2]);
+        dysymtab[12..14].copy_from_slice(&[INDIRECT as u32, 3]);
+        fixture.load_command(LC_DYSYMTAB, &dysymtab);
+        fixture.pointer_section("__got", HEADER_SIZE, 1, S_NON_LAZY_SYMBOL_POINTERS, 0);
+        fixture.pointer_section("__la_symbol_ptr", HEADER_SIZE + 8, 2, S_LAZY_SYMBOL_POINTERS, 1);
+        for (i, entry) in [3u32, parse::INDIRECT_SYMBOL_LOCAL, 4].into_iter().enumerate() {
+            fixture.put_u32(INDIRECT + i * 4, entry);
+        }
+
+        let data = fixture.build();
+        let image = parse::MachO::parse(&data).unwrap();
+        assert_eq!(image.header.filetype, MH_DYLIB);
+        assert_eq!(
+            image.load_commands().map(|command| command.cmd).collect::<Vec<_>>(),
+            [LC_SEGMENT_64, LC_ID_DYLIB, LC_LOAD_DYLIB, LC_UUID, LC_DYSYMTAB]
+        );
+        assert_eq!(image.install_name.as_deref(), Some("/usr/lib/libtweak.dylib"));
+        assert_eq!(image.dylibs, ["/usr/lib/libobjc.A.dylib"]);
+        assert_eq!(image.uuid, Some([1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]));
+
+        let dysymtab = image.dysymtab.unwrap();
+        assert_eq!((dysymtab.iextdefsym, dysymtab.nextdefsym), (1, 2));
+        assert_eq!((dysymtab.iundefsym, dysymtab.nundefsym), (3, 2));
+        assert_eq!(image.indirect_symbols(), [3, parse::INDIRECT_SYMBOL_LOCAL, 4]);
+
+        let lazy = image.section("__la_symbol_ptr").unwrap();
+        assert_eq!(lazy.section_type(), S_LAZY_SYMBOL_POINTERS);
+        assert_eq!((lazy.size, lazy.reserved1), (16, 1));
+        assert_eq!(image.section("__got").unwrap().section_type(), S_NON_LAZY_SYMBOL_POINTERS);
+    }
+
+    #[test]
+    fn test_symbol_pointer_bindings() {
+        const SYMBOLS: usize = HEADER_SIZE + 0x40;
+        const STRINGS: usize = HEADER_SIZE + 0x80;
+        const INDIRECT: usize = HEADER_SIZE + 0xc0;
+
+        let mut fixture = Fixture::new(0x100);
+        fixture.load_command(LC_SYMTAB, &[SYMBOLS as u32, 3, STRINGS as u32, 0x30]);
+        let mut dysymtab = [0u32; 18];
+        dysymtab[12..14].copy_from_slice(&[INDIRECT as u32, 4]);
+        fixture.load_command(LC_DYSYMTAB, &dysymtab);
+
+        // A local function, then two imports
+        for (i, (name_offset, name)) in [(1, "_helper"), (9, "_objc_msgSend"), (23, "_free")].into_iter().enumerate() {
+            fixture.put_str(STRINGS + name_offset, name);
+            fixture.put_u32(SYMBOLS + i * 16, name_offset as u32);
+            fixture.data[SYMBOLS + i * 16 + 4] = if i == 0 { N_SECT } else { N_EXT };
+        }
+        fixture.put_u64(SYMBOLS + 8, BASE + 0x10);
+        // whose name is past the string table, which mustn't shift the imports' indices
+        fixture.put_u32(SYMBOLS, 0xffff);
+
+        // __got: _objc_msgSend, then a slot the linker filled in locally;
+        // __la_symbol_ptr: _free, _objc_msgSend
+        fixture.pointer_section("__got", HEADER_SIZE, 2, S_NON_LAZY_SYMBOL_POINTERS, 0);
+        fixture.pointer_section("__la_symbol_ptr", HEADER_SIZE + 0x10, 2, S_LAZY_SYMBOL_POINTERS, 2);
+        for (i, entry) in [1u32, parse::INDIRECT_SYMBOL_LOCAL, 2, 1].into_iter().enumerate() {
+            fixture.put_u32(INDIRECT + i * 4, entry);
+        }
+
+        let data = fixture.build();
+        let image = parse::MachO::parse(&data).unwrap();
+        let binding = |name: &str, offset: usize| Binding { name: name.to_string(), offset: offset as u64 };
+        assert_eq!(
+            image.symbol_pointers(),
+            [
+                binding("_objc_msgSend", HEADER_SIZE),
+                binding("_free", HEADER_SIZE + 0x10),
+                binding("_objc_msgSend", HEADER_SIZE + 0x18),
+            ]
+        );
+        assert_eq!(image.chained_binds(), []);
+        assert_eq!(image.bindings(), image.symbol_pointers());
+
+        // Sections past the end of a shorter indirect table bind nothing
+        let sections = image.segments.iter().flat_map(|segment| &segment.sections);
+        let bindings = parse::symbol_pointer_bindings(sections, BASE, &[1, 2], &image.symbols());
+        assert_eq!(bindings, [binding("_objc_msgSend", HEADER_SIZE), binding("_free", HEADER_SIZE + 8)]);
+    }
+
+    #[test]
+    fn test_unreadable_symbol_names_keep_indices() {
+        // A name past the string table, one that isn't UTF-8, then an import
+        let strings = b"\0\xff_bad\0_free\0";
+        let mut nlists = Vec::new();
+        for (strx, n_type) in [(0x100u32, N_SECT), (1, N_SECT), (7, N_EXT)] {
+            nlists.extend_from_slice(&strx.to_le_bytes());
+            nlists.extend_from_slice(&[n_type, 0, 0, 0]);
+            nlists.extend_from_slice(&0u64.to_le_bytes());
+        }
+
+        let symbols = parse::parse_nlists(&nlists, strings);
+        let names: Vec<_> = symbols.iter().map(|symbol| symbol.name.as_str()).collect();
+        assert_eq!(names, ["", "\u{fffd}_bad", "_free"]);
+
+        // The indirect symbol table still finds _free at index 2; the nameless symbol binds nothing
+        let section = parse::Section {
+            name: "__got".to_string(),
+            segment_name: "__DATA_CONST".to_string(),
+            addr: BASE + 0x4000,
+            size: 16,
+            offset: 0x4000,
+            flags: S_NON_LAZY_SYMBOL_POINTERS,
+            reserved1: 0,
+            reserved2: 0,
+        };
+        assert_eq!(
+            parse::symbol_pointer_bindings([&section], BASE, &[0, 2], &symbols),
+            [Binding { name: "_free".to_string(), offset: 0x4008 }]
+        );
+    }
+
+    #[test]
+    fn test_loaded_layout() {
+        // __LINKEDIT follows __TEXT in the file, but is mapped 0x4000 past the base
+        const LINKEDIT: u64 = (HEADER_SIZE + 0x100) as u64;
+        const LINKEDIT_VM: usize = 0x4000;
+
+        let mut fixture = Fixture::new(0x100);
+        fixture.segment("__LINKEDIT", BASE + LINKEDIT_VM as u64, LINKEDIT, 0x40);
+        fixture.load_command(LC_SYMTAB, &[LINKEDIT as u32, 1, LINKEDIT as u32 + 0x10, 0x30]);
+        let mut file = fixture.build();
+        let mut linkedit = vec![0u8; 0x40];
+        linkedit[0..4].copy_from_slice(&1u32.to_le_bytes());
+        linkedit[4] = N_SECT | N_EXT;
+        linkedit[8..16].copy_from_slice(&(BASE + 0x10).to_le_bytes());
+        linkedit[0x11..0x1c].copy_from_slice(b"_SBExported");
+        file.extend_from_slice(&linkedit);
+
+        let mut memory = vec![0u8; LINKEDIT_VM + 0x40];
+        memory[..LINKEDIT as usize].copy_from_slice(&file[..LINKEDIT as usize]);
+        memory[LINKEDIT_VM..].copy_from_slice(&linkedit);
+
+        let from_file = parse::MachO::parse(&file).unwrap();
+        let loaded = parse::MachO::parse_loaded(&memory).unwrap();
+        assert_eq!(loaded.layout, Layout::Loaded);
+        assert_eq!(from_file.symbols(), loaded.symbols());
+        assert_eq!(loaded.symbols()[0].name, "_SBExported");
+        let segment = loaded.segment("__LINKEDIT").unwrap();
+        assert_eq!(loaded.segment_bytes(segment), Some(&linkedit[..]));
+        assert_eq!(from_file.segment_bytes(segment), Some(&linkedit[..]));
+    }
+
+    #[test]
+    fn test_walk_chained_fixups() {
+        const FIXUPS: usize = HEADER_SIZE + 0x80;
+
+        // dyld_chained_fixups_header, starts_in_image, starts_in_segment for
+        // __TEXT with one page starting at HEADER_SIZE, one import, its name
+        let mut fixups = Vec::new();
+        for field in [0u32, 32, 64, 68, 1, DYLD_CHAINED_IMPORT, 0, 0, 1, 8] {
+            fixups.extend_from_slice(&field.to_le_bytes());
+        }
+        fixups.extend_from_slice(&24u32.to_le_bytes());
+        fixups.extend_from_slice(&0x4000u16.to_le_bytes());
+        fixups.extend_from_slice(&DYLD_CHAINED_PTR_64.to_le_bytes());
+        fixups.extend_from_slice(&[0; 12]);
+        fixups.extend_from_slice(&1u16.to_le_bytes());
+        fixups.extend_from_slice(&(HEADER_SIZE as u16).to_le_bytes());
+        fixups.extend_from_slice(&((1u32 << 9) | 1).to_le_bytes());
+        fixups.extend_from_slice(b"\0_objc_msgSend\0");
+
+        let mut fixture = Fixture::new(0x100);
+        fixture.load_command(LC_DYLD_CHAINED_FIXUPS, &[FIXUPS as u32, fixups.len() as u32]);
+        fixture.data[FIXUPS..FIXUPS + fixups.len()].copy_from_slice(&fixups);
+        // A rebase whose next pointer is 2 strides (8 bytes) on, then a bind ending the chain
+        fixture.put_u64(HEADER_SIZE, (2 << 51) | (BASE + 0x1234));
+        fixture.put_u64(HEADER_SIZE + 8, (1 << 63) | (5 << 24));
+        let data = fixture.build();
+
+        let image = parse::MachO::parse(&data).unwrap();
+        let chained = image.parse_chained_fixups().unwrap();
+        assert_eq!(chained.pointer_format(), Some(DYLD_CHAINED_PTR_64));
+        assert_eq!(chained.imports[0].name, "_objc_msgSend");
+        assert_eq!(
+            image.fixups(),
+            [
+                parse::Fixup {
+                    offset: HEADER_SIZE as u64,
+                    pointer: ChainedPointer::Rebase(BASE + 0x1234),
+                },
+                parse::Fixup {
+                    offset: HEADER_SIZE as u64 + 8,
+                    pointer: ChainedPointer::Bind { ordinal: 0, addend: 5 },
+                },
+            ]
+        );
+
+        // Only the bind is bound to a symbol
+        assert_eq!(
+            image.chained_binds(),
+            [Binding { name: "_objc_msgSend".to_string(), offset: HEADER_SIZE as u64 + 8 }]
+        );
+
+        // The build-side view decodes the same pointers
+        let macho = MachO::parse(&data).unwrap();
+        assert_eq!(macho.pointer_at(BASE + HEADER_SIZE as u64 + 8), Some(Pointer::Bind(0)));
+        assert_eq!(macho.import_name(0), Some("_objc_msgSend"));
+    }
+
+    #[test]
+    fn test_arm64_slice() {
+        let thin = Fixture::new(0).build();
+        assert_eq!(arm64_slice(&thin).map(<[u8]>::len), Some(thin.len()));
+
+        // fat_header plus an x86_64 and an arm64 fat_arch
+        let mut fat = Vec::new();
+        fat.extend_from_slice(&FAT_MAGIC.to_be_bytes());
+        fat.extend_from_slice(&2u32.to_be_bytes());
+        for (cputype, offset) in [(CPU_TYPE_X86_64, 0x1000u32), (CPU_TYPE_ARM64, 0x2000)] {
+            for value in [cputype, 0, offset, thin.len() as u32, 12] {
+                fat.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        fat.resize(0x2000, 0);
+        fat.extend_from_slice(&thin);
+
+        assert_eq!(arm64_slice(&fat), Some(&thin[..]));
+        let arches = fat_arches(&fat).unwrap();
+        assert_eq!(arches.iter().map(|arch| arch.cputype).collect::<Vec<_>>(), [CPU_TYPE_X86_64, CPU_TYPE_ARM64]);
+        assert_eq!(arches[1].offset, 0x2000);
+        assert_eq!(parse::slice(&fat, CPU_TYPE_X86_64).map(<[u8]>::len), Some(thin.len()));
+        assert_eq!(parse::slice(&thin, CPU_TYPE_X86_64), None);
+        assert_eq!(fat_arches(&thin), None);
+        assert_eq!(arm64_slice(b"not a mach-o"), None);
+    }
+
+    #[test]
+    fn test_decode_chained_pointers() {
+        let base = 0x1_0000_0000;
+        let decode = |raw, format| Pointer::from(decode_chained_pointer(raw, format, base));
+
+        // DYLD_CHAINED_PTR_64: vm address target, bind ordinal in the low 24 bits
+        let next = 5 << 51;
+        assert_eq!(decode(next | 0x1_0000_8000, DYLD_CHAINED_PTR_64), Pointer::Address(0x1_0000_8000));
+        assert_eq!(decode((0x80 << 36) | 0x8000, DYLD_CHAINED_PTR_64), Pointer::Address(0x8000_0000_0000_8000));
+        assert_eq!(decode((1 << 63) | 7, DYLD_CHAINED_PTR_64), Pointer::Bind(7));
+        assert_eq!(decode(0x8000, DYLD_CHAINED_PTR_64_OFFSET), Pointer::Address(base + 0x8000));
+
+        // arm64e: auth rebases are offsets from the base, plain rebases vm addresses
+        assert_eq!(decode((1 << 63) | next | 0x8000, DYLD_CHAINED_PTR_ARM64E), Pointer::Address(base + 0x8000));
+        assert_eq!(decode(next | 0x1_0000_8000, DYLD_CHAINED_PTR_ARM64E), Pointer::Address(0x1_0000_8000));
+        assert_eq!(decode(next | 0x8000, DYLD_CHAINED_PTR_ARM64E_USERLAND), Pointer::Address(base + 0x8000));
+        assert_eq!(decode((1 << 62) | 0x1_2345, DYLD_CHAINED_PTR_ARM64E_USERLAND24), Pointer::Bind(0x1_2345));
+        assert_eq!(decode((1 << 62) | 0x1_2345, DYLD_CHAINED_PTR_ARM64E), Pointer::Bind(0x2345));
+
+        // Bind addends: 8 unsigned bits, or 19 signed bits on arm64e
+        assert_eq!(
+            decode_chained_pointer((1 << 63) | (0x10 << 24) | 3, DYLD_CHAINED_PTR_64, base),
+            ChainedPointer::Bind { ordinal: 3, addend: 0x10 }
+        );
+        assert_eq!(
+            decode_chained_pointer((1 << 62) | (0x7_fff8 << 32) | 3, DYLD_CHAINED_PTR_ARM64E, base),
+            ChainedPointer::Bind { ordinal: 3, addend: -8 }
+        );
+    }
+
+    #[test]
+    fn test_parse_chained_imports() {
+        // Header, then two DYLD_CHAINED_IMPORT entries, then the symbol strings
+        let mut fixups = vec![0u8; 28];
+        fixups[8..12].copy_from_slice(&28u32.to_le_bytes());
+        fixups[12..16].copy_from_slice(&36u32.to_le_bytes());
+        fixups[16..20].copy_from_slice(&2u32.to_le_bytes());
+        fixups[20..24].copy_from_slice(&DYLD_CHAINED_IMPORT.to_le_bytes());
+        for name_offset in [1u32, 24] {
+            fixups.extend_from_slice(&((name_offset << 9) | 1).to_le_bytes());
+        }
+        fixups.extend_from_slice(b"\0_OBJC_CLASS_$_NSObject\0_objc_msgSend\0");
+
+        let imports = ChainedFixups::parse(&fixups).unwrap().imports;
+        let names: Vec<_> = imports.iter().map(|import| import.name.as_str()).collect();
+        assert_eq!(names, ["_OBJC_CLASS_$_NSObject", "_objc_msgSend"]);
+        assert_eq!(imports[0].library_ordinal, 1);
+    }
+}
@@ -8,6 +8,30 @@
 mod tbd;
 mod type_encoding;
 mod objc_codegen;
+mod hooking;
+mod macho;
+mod objc_metadata;
+mod shared_cache;
+
+// The runtime class dump writer, for round-trip tests of the parser
+#[cfg(test)]
+#[path = "../src/class_dump/format.rs"]
+mod class_dump_format;
+
+// The property attribute parser, which the runtime's strings can't exercise here
+#[cfg(test)]
+#[path = "../src/introspect/property.rs"]
+mod introspect_property;
+#[cfg(test)]
+mod property_tests;
+
+// The ad-hoc signature writer, tested against the Mach-O fixtures
+#[cfg(all(test, feature = "codesign"))]
+#[allow(dead_code)]
+#[path = "../src/codesign.rs"]
+mod codesign;
+#[cfg(all(test, feature = "codesign"))]
+mod codesign_tests;
 
 use std::env;
 use std::fs;
@@ -20,6 +44,14 @@ const MAX_INTERNAL_SYMBOL_LENGTH: usize = 50;
 // Constants for class dump paths
 const FULL_CLASS_DUMP_PATH: &str = "/tmp/all_objc_classes.txt";
 const FOUNDATION_CLASS_DUMP_PATH: &str = "/tmp/foundation_classes.txt";
+// Optional nullability annotations for dumps taken from the runtime (which has none)
+const NULLABILITY_OVERRIDES_PATH: &str = "/tmp/objc_nullability.txt";
+// Mach-O images (':'-separated) to read Objective-C classes from instead of a dump
+const OBJC_IMAGES_ENV: &str = "IOS_SYS_OBJC_IMAGES";
+// dyld shared cache to read private frameworks and Objective-C classes from
+const SHARED_CACHE_ENV: &str = "IOS_SYS_SHARED_CACHE";
+// Libraries without SDK stubs (':'-separated): Mach-O dylibs, or INSTALL_NAME=SYMBOL_LIST
+const TBD_STUBS_ENV: &str = "IOS_SYS_TBD_STUBS";
 
 // ============================================================================
 // Bindings Generation
@@ -213,6 +245,10 @@ fn generate_private_framework_modules(frameworks: &[String], out_path: &Path) {
 pub fn main() {
     println!("cargo:rerun-if-changed=frameworks.rs");
     println!("cargo:rerun-if-changed=wrapper.h");
+    println!("cargo:rerun-if-env-changed=THEOS");
+    println!("cargo:rerun-if-env-changed={}", OBJC_IMAGES_ENV);
+    println!("cargo:rerun-if-env-changed={}", SHARED_CACHE_ENV);
+    println!("cargo:rerun-if-env-changed={}", TBD_STUBS_ENV);
 
     let _target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
     let _target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
@@ -405,6 +441,18 @@ fn link_frameworks(sdk_path: &Path) {
         // Link all feature-gated frameworks
         link_feature_frameworks_linux(&frameworks_dir);
     }
+
+    // Hooking libraries come from Theos rather than the SDK
+    let feature_enabled = |module_name: &str| {
+        env::var(format!("CARGO_FEATURE_{}", module_name.to_uppercase())).is_ok()
+    };
+    hooking::link_hooking_libraries(hooking::find_theos().as_deref(), &feature_enabled);
+
+    // Stubs written for libraries outside the SDK
+    if let (Some(stubs), Some(out_dir)) = (env::var_os(TBD_STUBS_ENV), env::var_os("OUT_DIR")) {
+        let stubs: Vec<PathBuf> = env::split_paths(&stubs).collect();
+        tbd::link_tbd_stubs(&stubs, Path::new(&out_dir));
+    }
 }
 
 #[cfg(all(feature = "runtime", target_os = "macos"))]
@@ -629,6 +677,29 @@ fn get_enabled_frameworks() -> Vec<String> {
     frameworks
 }
 
+/// Lets bindgen evaluate macros that cast to a typedef, like `VM_PROT_READ`
+///
+/// cexpr can't parse casts, so `((vm_prot_t) 0x01)` would generate nothing;
+/// this drops the `(name_t)` before the value.
+#[derive(Debug)]
+struct StripTypedefCasts;
+
+impl bindgen::callbacks::ParseCallbacks for StripTypedefCasts {
+    fn modify_macro(&self, _name: &str, tokens: &mut Vec<bindgen::callbacks::Token>) {
+        use bindgen::callbacks::TokenKind;
+
+        let is_cast = |window: &[bindgen::callbacks::Token]| {
+            window[0].kind == TokenKind::Punctuation && &*window[0].raw == b"("
+                && window[1].kind == TokenKind::Identifier && window[1].raw.ends_with(b"_t")
+                && window[2].kind == TokenKind::Punctuation && &*window[2].raw == b")"
+        };
+        // The first token is the macro's name
+        while let Some(start) = tokens.windows(3).skip(1).position(is_cast) {
+            tokens.drain(start + 1..start + 4);
+        }
+    }
+}
+
 /// Generate bindings using bindgen from SDK headers
 fn generate_bindings(sdk_path: &Path) {
     let sysroot = format!("-isysroot{}", sdk_path.display());
@@ -699,6 +770,19 @@ unsafe extern "C" {
     /// objc_msgSend for floating-point return on stret (x86)
     pub fn objc_msgSend_fp2ret();
 }
+
+// Reference counting entry points (exported by libobjc, declared in the ARC headers)
+unsafe extern "C" {
+    pub fn objc_retain(obj: id) -> id;
+    pub fn objc_release(obj: id);
+    pub fn objc_autorelease(obj: id) -> id;
+}
+
+// Autorelease pool entry points (what @autoreleasepool compiles to)
+unsafe extern "C" {
+    pub fn objc_autoreleasePoolPush() -> *mut ::core::ffi::c_void;
+    pub fn objc_autoreleasePoolPop(context: *mut ::core::ffi::c_void);
+}
 "#;
 
     let mut objc_file = fs::OpenOptions::new()
@@ -710,6 +794,10 @@ unsafe extern "C" {
     objc_file.write_all(objc_additions.as_bytes())
         .expect("Failed to append to objc.rs");
 
+    // Generate CydiaSubstrate/libhooker bindings from the Theos TBD stubs (ALWAYS)
+    println!("cargo:warning=Generating hooking library bindings...");
+    hooking::generate_hooking_bindings(hooking::find_theos().as_deref(), &out_path);
+
     // Generate Mach kernel bindings (ALWAYS)
     println!("cargo:warning=Generating Mach kernel bindings...");
     let mach_bindings = bindgen::Builder::default()
@@ -725,6 +813,8 @@ unsafe extern "C" {
 #include <mach/boolean.h>
 #include <mach/vm_prot.h>
 #include <mach/vm_inherit.h>
+#include <mach/vm_region.h>
+#include <mach/vm_page_size.h>
             "#,
         )
         .clang_args(&common_args)
@@ -750,6 +840,8 @@ unsafe extern "C" {
         .allowlist_var("MACH_.*")
         .allowlist_var("CPU_.*")
         .allowlist_var("MH_.*")
+        .allowlist_var("mach_task_self_")
+        .allowlist_var("vm_page_size")
         .derive_default(true)
         .derive_debug(true)
         .derive_copy(true)
@@ -758,6 +850,7 @@ unsafe extern "C" {
         .use_core()
         .ctypes_prefix("::core::ffi")
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+        .parse_callbacks(Box::new(StripTypedefCasts))
         .generate()
         .expect("Unable to generate mach bindings");
 
@@ -795,16 +888,76 @@ unsafe extern "C" {
     // Generate Foundation bindings (ALWAYS)
     println!("cargo:warning=Generating Foundation bindings from runtime dump...");
 
+    let shared_cache = env::var_os(SHARED_CACHE_ENV).and_then(|path| {
+        let path = PathBuf::from(path);
+        println!("cargo:rerun-if-changed={}", path.display());
+        match shared_cache::SharedCache::open(&path) {
+            Ok(cache) => {
+                println!("cargo:warning=Using shared cache {} ({} images)", path.display(), cache.images().len());
+                Some(cache)
+            }
+            Err(e) => {
+                println!("cargo:warning=Failed to open shared cache: {}", e);
+                None
+            }
+        }
+    });
+
+    // Classes from other frameworks go into per-framework modules, gated by their feature
+    let feature_enabled = |module_name: &str| {
+        env::var(format!("CARGO_FEATURE_{}", module_name.to_uppercase())).is_ok()
+    };
+    let nullability_path = Path::new(NULLABILITY_OVERRIDES_PATH);
+    let nullability_path = nullability_path.exists().then_some(nullability_path);
+
     // Check if we have a class dump file (prefer comprehensive dump)
     let class_dump_path = if Path::new(FULL_CLASS_DUMP_PATH).exists() {
         Path::new(FULL_CLASS_DUMP_PATH)
     } else {
         Path::new(FOUNDATION_CLASS_DUMP_PATH)
     };
-    if class_dump_path.exists() {
+    if let Some(images) = env::var_os(OBJC_IMAGES_ENV) {
+        // Images on the build host, e.g. extracted from an IPSW
+        let mut classes = Vec::new();
+        for image in env::split_paths(&images) {
+            println!("cargo:rerun-if-changed={}", image.display());
+            match objc_metadata::classes_in_file(&image) {
+                Ok(image_classes) => classes.extend(image_classes),
+                Err(e) => println!("cargo:warning=Failed to read classes from {}: {}", image.display(), e),
+            }
+        }
+        println!("cargo:warning=Read {} Objective-C classes from Mach-O images", classes.len());
+
+        if let Err(e) = objc_codegen::generate_from_classes(classes, nullability_path, &out_path, &feature_enabled) {
+            println!("cargo:warning=Failed to generate from Mach-O images: {}", e);
+            println!("cargo:warning=Falling back to minimal Foundation bindings");
+            generate_minimal_foundation_bindings(&out_path);
+        }
+    } else if let Some(cache) = &shared_cache {
+        // Only the images whose classes would be generated
+        let classes: Vec<_> = cache
+            .images()
+            .iter()
+            .filter(|image| {
+                objc_codegen::framework_for_image(&image.path).is_some_and(|framework| {
+                    framework == "Foundation" || feature_enabled(&objc_codegen::framework_module_name(&framework))
+                })
+            })
+            .flat_map(|image| cache.classes(image))
+            .collect();
+        println!("cargo:warning=Read {} Objective-C classes from the shared cache", classes.len());
+
+        if let Err(e) = objc_codegen::generate_from_classes(classes, nullability_path, &out_path, &feature_enabled) {
+            println!("cargo:warning=Failed to generate from the shared cache: {}", e);
+            println!("cargo:warning=Falling back to minimal Foundation bindings");
+            generate_minimal_foundation_bindings(&out_path);
+        }
+    } else if class_dump_path.exists() {
         if let Err(e) = objc_codegen::generate_from_dump_file(
             class_dump_path,
-            &out_path.join("foundation.rs")
+            nullability_path,
+            &out_path,
+            &feature_enabled,
         ) {
             println!("cargo:warning=Failed to generate from class dump: {}", e);
             println!("cargo:warning=Falling back to minimal Foundation bindings");
@@ -813,7 +966,7 @@ unsafe extern "C" {
     } else {
         println!("cargo:warning=No class dump found at {} or {}",
                  FULL_CLASS_DUMP_PATH, FOUNDATION_CLASS_DUMP_PATH);
-        println!("cargo:warning=Run class_dump tool on device to generate full bindings");
+        println!("cargo:warning=Run the class_dump example on device to generate full bindings");
         generate_minimal_foundation_bindings(&out_path);
     }
 
@@ -833,12 +986,24 @@ unsafe extern "C" {
     );
 
     // Also scan for PRIVATE frameworks with TBD files
-    let private_frameworks = get_private_frameworks_with_tbd(sdk_path);
+    let mut private_frameworks = get_private_frameworks_with_tbd(sdk_path);
     println!(
         "cargo:warning=Found {} private frameworks with TBD files",
         private_frameworks.len()
     );
 
+    // and add the ones only the shared cache has
+    if let Some(cache) = &shared_cache {
+        let tbd_count = private_frameworks.len();
+        private_frameworks.extend(cache.private_frameworks());
+        private_frameworks.sort();
+        private_frameworks.dedup();
+        println!(
+            "cargo:warning=Found {} more private frameworks in the shared cache",
+            private_frameworks.len() - tbd_count
+        );
+    }
+
     // Generate bindings for ALL frameworks (feature-gated)
     for framework in &all_frameworks {
         // Skip frameworks we already generated
@@ -885,7 +1050,18 @@ unsafe extern "C" {
                 .join("System/Library/PrivateFrameworks")
                 .join(format!("{}.framework/{}.tbd", framework, framework));
 
-            if let Some(tbd_info) = parse_tbd_file(&tbd_path) {
+            // The cache has the real exports, which TBD stubs often miss some of
+            let install_name = format!("/System/Library/PrivateFrameworks/{0}.framework/{0}", framework);
+            let cached = shared_cache.as_ref().and_then(|cache| cache.tbd_info(&install_name));
+            let tbd_info = match (parse_tbd_file(&tbd_path), cached) {
+                (Some(mut tbd_info), Some(cached)) => {
+                    tbd_info.merge(cached);
+                    Some(tbd_info)
+                }
+                (tbd_info, cached) => tbd_info.or(cached),
+            };
+
+            if let Some(tbd_info) = tbd_info {
                 println!(
                     "cargo:warning=  Found {} classes, {} symbols",
                     tbd_info.objc_classes.len(),
@@ -955,7 +1131,8 @@ pub type NSUInteger = usize;
 pub type CGFloat = f64;
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, crate::encode::Encode)]
+#[encoding(name = "_NSRange")]
 pub struct NSRange {
     pub location: NSUInteger,
     pub length: NSUInteger,
@@ -969,6 +1146,13 @@ unsafe extern "C" {
 }
 "#;
     fs::write(out_path.join("foundation.rs"), stub).expect("Failed to write Foundation stub");
+
+    // No class dump means no per-framework class modules either
+    fs::write(
+        out_path.join("objc_classes.rs"),
+        objc_codegen::generate_class_modules(&[]),
+    )
+    .expect("Failed to write objc_classes.rs");
 }
 
 fn generate_framework_bindings(
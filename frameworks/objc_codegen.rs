@@ -2,7 +2,7 @@
 //!
 //! Parses class_dump output and generates Rust bindings automatically
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Write as FmtWrite;
 use std::fs;
 use std::path::Path;
@@ -13,21 +13,129 @@ use super::type_encoding::{ObjCType, parse_method_encoding};
 pub struct ObjCClass {
     pub name: String,
     pub superclass: Option<String>,
+    /// Path of the image the class was loaded from (`Image:` line in the dump)
+    pub image: Option<String>,
     pub methods: Vec<ObjCMethod>,
+    /// Class methods (`+` lines), not turned into wrappers yet
+    pub class_methods: Vec<ObjCMethod>,
+    pub properties: Vec<ObjCProperty>,
+    pub ivars: Vec<ObjCIvar>,
 }
 
 #[derive(Debug, Clone)]
 pub struct ObjCMethod {
     pub name: String,
     pub type_encoding: String,
+    /// Annotations for the return value followed by each argument, empty if
+    /// the dump carries no nullability information
+    pub nullability: Vec<Option<ObjectAnnotation>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjCProperty {
+    pub name: String,
+    /// Runtime attribute string, e.g. `T@"NSString",C,N,V_title`
+    pub attributes: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjCIvar {
+    pub name: String,
+    pub type_encoding: String,
+    pub offset: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Nullability {
+    Nullable,
+    Nonnull,
+}
+
+/// Nullability and class of an object return value or argument
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectAnnotation {
+    pub nullability: Nullability,
+    /// Class of the object, `None` for `id`
+    pub class: Option<String>,
+}
+
+/// Parse a nullability annotation list like `nullable NSObject; nonnull NSString; _`
+///
+/// The first entry is the return value, the rest are the arguments. `_`
+/// marks a value without annotation (non-object types, `void`).
+fn parse_nullability_annotations(annotations: &str) -> Vec<Option<ObjectAnnotation>> {
+    annotations
+        .split(';')
+        .map(|annotation| {
+            let mut words = annotation.split_whitespace();
+            let nullability = match words.next()? {
+                "nullable" => Nullability::Nullable,
+                "nonnull" => Nullability::Nonnull,
+                _ => return None,
+            };
+            let class = words.next().filter(|class| *class != "id").map(str::to_string);
+            Some(ObjectAnnotation { nullability, class })
+        })
+        .collect()
+}
+
+/// Parse a nullability override file
+///
+/// Each line names a method and its annotations, e.g.
+/// `-[NSDictionary objectForKey:] (nullable NSObject; nonnull NSString)`.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn parse_nullability_overrides(content: &str) -> HashMap<(String, String), Vec<Option<ObjectAnnotation>>> {
+    let mut overrides = HashMap::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some(rest) = line.strip_prefix("-[") else { continue };
+        let Some((method, annotations)) = rest.split_once("] (") else { continue };
+        let Some((class, selector)) = method.split_once(' ') else { continue };
+        let annotations = annotations.strip_suffix(')').unwrap_or(annotations);
+
+        overrides.insert(
+            (class.to_string(), selector.trim().to_string()),
+            parse_nullability_annotations(annotations),
+        );
+    }
+
+    overrides
+}
+
+/// Replace the nullability of every method listed in `overrides`
+pub fn apply_nullability_overrides(
+    classes: &mut [ObjCClass],
+    overrides: &HashMap<(String, String), Vec<Option<ObjectAnnotation>>>,
+) {
+    for class in classes {
+        for method in &mut class.methods {
+            if let Some(nullability) = overrides.get(&(class.name.clone(), method.name.clone())) {
+                method.nullability = nullability.clone();
+            }
+        }
+    }
+}
+
+/// Section of an `@interface` block the parser is in
+#[derive(Clone, Copy, PartialEq)]
+enum DumpSection {
+    None,
+    Ivars,
+    Properties,
+    ClassMethods,
+    Methods,
 }
 
 /// Parse class_dump output into structured data
 pub fn parse_class_dump(dump_content: &str) -> Vec<ObjCClass> {
     let mut classes = Vec::new();
     let mut current_class: Option<ObjCClass> = None;
-    let mut in_methods = false;
-    let mut in_properties = false;
+    let mut section = DumpSection::None;
 
     for line in dump_content.lines() {
         let line = line.trim();
@@ -48,51 +156,70 @@ pub fn parse_class_dump(dump_content: &str) -> Vec<ObjCClass> {
             current_class = Some(ObjCClass {
                 name,
                 superclass: None,
+                image: None,
                 methods: Vec::new(),
+                class_methods: Vec::new(),
+                properties: Vec::new(),
+                ivars: Vec::new(),
             });
-            in_methods = false;
-            in_properties = false;
+            section = DumpSection::None;
+            continue;
         }
-        // Superclass
-        else if line.starts_with("Superclass: ") {
-            if let Some(ref mut class) = current_class {
-                class.superclass = Some(line.strip_prefix("Superclass: ").unwrap().to_string());
-            }
+
+        let Some(class) = current_class.as_mut() else { continue };
+
+        if let Some(superclass) = line.strip_prefix("Superclass: ") {
+            class.superclass = Some(superclass.to_string());
+        }
+        // Image (framework or dylib the class lives in)
+        else if let Some(image) = line.strip_prefix("Image: ") {
+            class.image = Some(image.to_string());
         }
-        // Methods section
         else if line.starts_with("Methods (") {
-            in_methods = true;
-            in_properties = false;
+            section = DumpSection::Methods;
+        }
+        else if line.starts_with("Class Methods (") {
+            section = DumpSection::ClassMethods;
         }
-        // Properties section
         else if line.starts_with("Properties (") {
-            in_methods = false;
-            in_properties = true;
+            section = DumpSection::Properties;
+        }
+        else if line.starts_with("Ivars (") {
+            section = DumpSection::Ivars;
         }
         // End of interface
         else if line == "@end" {
-            in_methods = false;
-            in_properties = false;
-        }
-        // Parse method
-        else if in_methods && line.starts_with("- ") {
-            if let Some(ref mut class) = current_class {
-                // Format: "    - methodName:param: [type_encoding]"
-                let parts: Vec<&str> = line.splitn(2, " [").collect();
-                if parts.len() == 2 {
-                    let method_name = parts[0].strip_prefix("- ").unwrap_or("").trim();
-                    let type_encoding = parts[1].strip_suffix(']').unwrap_or("").trim();
-
-                    class.methods.push(ObjCMethod {
-                        name: method_name.to_string(),
-                        type_encoding: type_encoding.to_string(),
-                    });
-                }
+            section = DumpSection::None;
+        }
+        else if section == DumpSection::Methods && line.starts_with("- ") {
+            if let Some(method) = parse_dump_method(line) {
+                class.methods.push(method);
+            }
+        }
+        else if section == DumpSection::ClassMethods && line.starts_with("+ ") {
+            if let Some(method) = parse_dump_method(line) {
+                class.class_methods.push(method);
+            }
+        }
+        // Format: "    @property name [attributes]"
+        else if section == DumpSection::Properties && line.starts_with("@property ") {
+            if let Some((name, attributes)) = line["@property ".len()..].split_once(" [") {
+                class.properties.push(ObjCProperty {
+                    name: name.trim().to_string(),
+                    attributes: attributes.strip_suffix(']').unwrap_or(attributes).to_string(),
+                });
             }
         }
-        // Parse property (currently ignored)
-        else if in_properties && line.starts_with("@property ") {
-            // Properties are not currently used
+        // Format: "    name [type_encoding] offset"
+        else if section == DumpSection::Ivars
+            && let Some((name, rest)) = line.split_once(" [")
+            && let Some((type_encoding, offset)) = rest.rsplit_once("] ")
+        {
+            class.ivars.push(ObjCIvar {
+                name: name.to_string(),
+                type_encoding: type_encoding.to_string(),
+                offset: offset.trim().parse().unwrap_or(0),
+            });
         }
     }
 
@@ -104,16 +231,87 @@ pub fn parse_class_dump(dump_content: &str) -> Vec<ObjCClass> {
     classes
 }
 
+/// Parse a `- selector [type_encoding]` or `+ selector [type_encoding]` line
+///
+/// Header-based dumps append nullability: `[type_encoding] (nullable NSString; _)`.
+fn parse_dump_method(line: &str) -> Option<ObjCMethod> {
+    let (method_name, rest) = line[2..].split_once(" [")?;
+    let (type_encoding, nullability) = match rest.rsplit_once("] (") {
+        Some((encoding, annotations)) if annotations.ends_with(')') => (
+            encoding.trim(),
+            parse_nullability_annotations(annotations.strip_suffix(')').unwrap()),
+        ),
+        _ => (rest.strip_suffix(']').unwrap_or("").trim(), Vec::new()),
+    };
+
+    Some(ObjCMethod {
+        name: method_name.trim().to_string(),
+        type_encoding: type_encoding.to_string(),
+        nullability,
+    })
+}
+
+/// Framework name for a class image path
+///
+/// `/System/Library/Frameworks/UIKit.framework/UIKit` and
+/// `/System/Library/PrivateFrameworks/UIKitCore.framework/UIKitCore` map to
+/// `UIKit` and `UIKitCore`. Root classes like NSObject live in libobjc but
+/// belong with the Foundation wrappers. Other images (apps, plain dylibs)
+/// have no framework module.
+pub fn framework_for_image(image: &str) -> Option<String> {
+    if image.starts_with("/usr/lib/libobjc.") {
+        return Some("Foundation".to_string());
+    }
+
+    // Use the innermost framework for nested ones (Foo.framework/Frameworks/Bar.framework/Bar)
+    let end = image.rfind(".framework/")?;
+    let start = image[..end].rfind('/').map_or(0, |pos| pos + 1);
+    let name = &image[start..end];
+
+    if name.is_empty() { None } else { Some(name.to_string()) }
+}
+
+/// Module (and feature) name for a framework, e.g. `UIKitCore` -> `uikitcore`
+pub fn framework_module_name(framework: &str) -> String {
+    framework.to_lowercase().replace("_", "").replace("-", "").replace(" ", "")
+}
+
+/// Group classes by the framework they belong to
+///
+/// Classes without an `Image:` line (dumps from older class_dump versions)
+/// are assumed to be Foundation classes. Classes from images that are not
+/// frameworks are dropped.
+pub fn group_classes_by_framework(classes: Vec<ObjCClass>) -> BTreeMap<String, Vec<ObjCClass>> {
+    let mut frameworks: BTreeMap<String, Vec<ObjCClass>> = BTreeMap::new();
+
+    for class in classes {
+        let framework = match &class.image {
+            Some(image) => match framework_for_image(image) {
+                Some(framework) => framework,
+                None => continue,
+            },
+            None => "Foundation".to_string(),
+        };
+        frameworks.entry(framework).or_default().push(class);
+    }
+
+    frameworks
+}
+
 /// Generate Rust code from ObjC classes
+///
+/// This is the Foundation module: it carries the basic Foundation types and
+/// C functions that the other framework modules import.
 pub fn generate_rust_bindings(classes: &[ObjCClass]) -> String {
     let mut output = String::new();
 
     writeln!(output, "// Auto-generated Objective-C bindings from runtime introspection").unwrap();
     writeln!(output, "// DO NOT EDIT - regenerate with class_dump\n").unwrap();
     writeln!(output, "use crate::objc::{{").unwrap();
-    writeln!(output, "    id, Class, SEL, objc_getClass, sel_registerName,").unwrap();
+    writeln!(output, "    id, Class, SEL,").unwrap();
     writeln!(output, "    objc_ivar, objc_method, objc_method_description, objc_property_t,").unwrap();
     writeln!(output, "}};").unwrap();
+    writeln!(output, "use crate::cache::{{CachedClass, CachedSel}};").unwrap();
     writeln!(output, "use std::ffi::CString;").unwrap();
     writeln!(output, "use core::ffi::c_void;\n").unwrap();
 
@@ -137,7 +335,8 @@ pub fn generate_rust_bindings(classes: &[ObjCClass]) -> String {
     writeln!(output, "pub type NSTimeInterval = f64;\n").unwrap();
 
     writeln!(output, "#[repr(C)]").unwrap();
-    writeln!(output, "#[derive(Debug, Copy, Clone, PartialEq)]").unwrap();
+    writeln!(output, "#[derive(Debug, Copy, Clone, PartialEq, crate::encode::Encode)]").unwrap();
+    writeln!(output, "#[encoding(name = \"_NSRange\")]").unwrap();
     writeln!(output, "pub struct NSRange {{").unwrap();
     writeln!(output, "    pub location: NSUInteger,").unwrap();
     writeln!(output, "    pub length: NSUInteger,").unwrap();
@@ -172,8 +371,64 @@ pub fn generate_rust_bindings(classes: &[ObjCClass]) -> String {
     writeln!(output, "    pub extra: [u64; 5],").unwrap();
     writeln!(output, "}}\n").unwrap();
 
+    let wrappers: HashSet<&str> = classes.iter().map(|c| c.name.as_str()).collect();
+
     for class in classes {
-        generate_class_bindings(&mut output, class);
+        generate_class_bindings(&mut output, class, &wrappers);
+    }
+
+    output
+}
+
+/// Generate Rust code for the ObjC classes of a single non-Foundation framework
+///
+/// `foundation_classes` are the classes in `crate::foundation`, which the
+/// generated module imports so methods can take and return their wrappers.
+pub fn generate_framework_class_bindings(
+    framework: &str,
+    classes: &[ObjCClass],
+    foundation_classes: &[ObjCClass],
+) -> String {
+    let mut output = String::new();
+
+    writeln!(output, "// Auto-generated Objective-C bindings for {} from runtime introspection",
+             framework).unwrap();
+    writeln!(output, "// DO NOT EDIT - regenerate with class_dump\n").unwrap();
+    writeln!(output, "use crate::objc::{{id, Class, SEL}};").unwrap();
+    writeln!(output, "use crate::cache::{{CachedClass, CachedSel}};").unwrap();
+    writeln!(output, "use crate::foundation::*;").unwrap();
+    writeln!(output, "use core::ffi::c_void;").unwrap();
+
+    // Only Foundation and this module are always available; other framework
+    // modules may be disabled, so their classes stay raw `id`
+    let wrappers: HashSet<&str> = classes
+        .iter()
+        .chain(foundation_classes)
+        .map(|c| c.name.as_str())
+        .collect();
+
+    for class in classes {
+        generate_class_bindings(&mut output, class, &wrappers);
+    }
+
+    output
+}
+
+/// Generate the objc_classes.rs file with one feature-gated module per framework
+pub fn generate_class_modules(modules: &[(String, String)]) -> String {
+    let mut output = String::new();
+
+    writeln!(output, "// Objective-C class modules (auto-generated from class dump)").unwrap();
+    writeln!(output, "// Do not edit manually - changes will be overwritten\n").unwrap();
+
+    for (framework, module_name) in modules {
+        writeln!(output, "#[cfg(feature = \"{}\")]", module_name).unwrap();
+        writeln!(output, "pub mod {} {{", module_name).unwrap();
+        writeln!(output, "    //! {} Objective-C classes (generated from class dump)", framework).unwrap();
+        writeln!(output, "    #![allow(clippy::all)]").unwrap();
+        writeln!(output, "    #![allow(warnings)]").unwrap();
+        writeln!(output, "    include!(concat!(env!(\"OUT_DIR\"), \"/{}_classes.rs\"));", module_name).unwrap();
+        writeln!(output, "}}\n").unwrap();
     }
 
     output
@@ -190,63 +445,251 @@ fn sanitize_class_name(name: &str) -> String {
         .replace("@", "At")
 }
 
-/// Sanitize an Objective-C selector to be a valid Rust method name
-fn sanitize_selector(selector: &str) -> String {
-    // Remove colons and convert to snake_case style
-    let mut result = selector
-        .replace(":", "_")
-        .replace("-", "_")
-        .replace("+", "plus_")
-        .replace("$", "dollar_")
-        .replace(".", "_") // Replace dots (e.g., .cxx_destruct)
-        .trim_matches('_')
-        .to_string();
+/// Split a camelCase selector keyword into lowercase words
+///
+/// Acronyms stay together and digits stick to the word before them:
+/// `stringWithUTF8String` -> `string`, `with`, `utf8`, `string`.
+fn camel_case_words(keyword: &str) -> Vec<String> {
+    let chars: Vec<char> = keyword.chars().collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_alphanumeric() {
+            // Any other character (`_`, `.`, `-`, ...) separates words
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
 
-    // If it starts with a digit, prefix with underscore
-    if result.chars().next().map_or(false, |c| c.is_ascii_digit()) {
-        result = format!("_{}", result);
+        if c.is_ascii_uppercase() && !current.is_empty() {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_ascii_lowercase());
+
+            // fooBar, foo8Bar, and the end of an acronym in URLForKey
+            if prev.is_ascii_lowercase() || prev.is_ascii_digit() || (prev.is_ascii_uppercase() && next_is_lower) {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+
+        current.push(c.to_ascii_lowercase());
     }
 
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+/// Escape names that aren't valid Rust identifiers
+fn escape_identifier(name: String) -> String {
+    // If it starts with a digit, prefix with underscore
+    let name = if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    };
+
     // If it's a Rust keyword, append underscore
-    match result.as_str() {
+    match name.as_str() {
         "self" | "Self" | "super" | "crate" | "type" | "move" | "box" | "impl" | "fn" | "let"
         | "mut" | "ref" | "static" | "const" | "unsafe" | "async" | "await" | "dyn"
         | "abstract" | "final" | "override" | "macro" | "typeof" | "yield" | "return" | "break"
         | "continue" | "loop" | "while" | "for" | "if" | "else" | "match" | "pub" | "use"
         | "extern" | "mod" | "trait" | "struct" | "enum" | "union" | "where" | "as" | "in"
-        | "become" => {
-            format!("{}_", result)
+        | "become" | "try" | "gen" => {
+            format!("{}_", name)
         }
-        _ => result,
+        _ => name,
     }
 }
 
-/// Generate argument names and msgSend selector string for a method
-fn generate_selector_call(selector: &str, arg_count: usize) -> (Vec<String>, String) {
-    let parts: Vec<&str> = selector.split(':').filter(|s| !s.is_empty()).collect();
+/// Sanitize an Objective-C selector to be a valid Rust method name
+///
+/// Selector keywords are converted to snake_case and joined:
+/// `initWithFrame:style:` -> `init_with_frame_style`. A leading underscore
+/// (private API) is kept, so `_setFrame:` and `setFrame:` stay distinct.
+fn sanitize_selector(selector: &str) -> String {
+    let words: Vec<String> = selector
+        .replace("+", "_plus_")
+        .replace("$", "_dollar_")
+        .split(':')
+        .flat_map(camel_case_words)
+        .collect();
+
+    let mut result = words.join("_");
+    if selector.starts_with('_') {
+        result.insert(0, '_');
+    }
+
+    escape_identifier(result)
+}
+
+/// Prepositions that introduce the argument in a selector keyword (`objectForKey:`)
+const SELECTOR_PREPOSITIONS: &[&str] = &[
+    "with", "without", "for", "of", "from", "to", "at", "in", "into", "on", "by", "using",
+    "as", "after", "before", "within", "via",
+];
+
+/// Verbs that are dropped from the first keyword when naming its argument (`setHidden:`)
+const SELECTOR_VERBS: &[&str] = &[
+    "set", "add", "remove", "insert", "is", "has", "get", "perform", "register", "unregister",
+    "append", "replace", "contains", "handle", "update",
+];
+
+/// Name the arguments of a method after its selector keywords
+///
+/// `initWithFrame:style:` gives `frame`, `style`; `setObject:forKey:` gives
+/// `object`, `key`. Arguments without a usable keyword fall back to `argN`.
+fn argument_names(selector: &str, arg_count: usize) -> Vec<String> {
+    let keywords: Vec<&str> = selector.split(':').collect();
+    let mut names: Vec<String> = Vec::with_capacity(arg_count);
+
+    for i in 0..arg_count {
+        let words = keywords.get(i).map(|k| camel_case_words(k)).unwrap_or_default();
+
+        // Words after the last preposition that still has something after it
+        let mut start = words[..words.len().saturating_sub(1)]
+            .iter()
+            .rposition(|w| SELECTOR_PREPOSITIONS.contains(&w.as_str()))
+            .map_or(0, |pos| pos + 1);
+
+        if start == 0 && i == 0 && words.len() > 1 && SELECTOR_VERBS.contains(&words[0].as_str()) {
+            start = 1;
+        }
+
+        let name = if words.is_empty() {
+            format!("arg{}", i)
+        } else {
+            escape_identifier(words[start..].join("_"))
+        };
 
-    let mut arg_names = Vec::new();
-    let mut msg_send_selector = String::new();
+        // Repeated keywords (foo:foo:) are numbered by position, which is stable
+        if names.contains(&name) || name == "sel" || name == "msg_send" {
+            names.push(format!("{}_{}", name, i));
+        } else {
+            names.push(name);
+        }
+    }
 
-    for (i, part) in parts.iter().enumerate() {
-        msg_send_selector.push_str(part);
-        msg_send_selector.push(':');
+    names
+}
 
-        if i < arg_count {
-            arg_names.push(format!("arg{}", i));
+/// Assign Rust names to a class's methods
+///
+/// Duplicate selectors are dropped. Distinct selectors that map to the same
+/// Rust name are ranked by selector, and all but the first get the rank as
+/// suffix, so the result depends only on the set of selectors and not on the
+/// order they appear in the dump. Names in `reserved` are taken by
+/// hand-written methods and get an underscore appended.
+fn unique_method_names<'a>(methods: &'a [ObjCMethod], reserved: &[&str]) -> Vec<(&'a ObjCMethod, String)> {
+    let mut seen = HashSet::new();
+    let methods: Vec<&ObjCMethod> = methods.iter().filter(|m| seen.insert(m.name.as_str())).collect();
+
+    let mut by_name: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for method in &methods {
+        let mut name = sanitize_selector(&method.name);
+        if reserved.contains(&name.as_str()) {
+            name.push('_');
         }
+        by_name.entry(name).or_default().push(method.name.as_str());
     }
 
-    // Handle selectors without colons
-    if parts.is_empty() {
-        msg_send_selector = selector.to_string();
+    let mut names: HashMap<&str, String> = HashMap::new();
+    for (name, mut selectors) in by_name {
+        selectors.sort_unstable();
+        for (rank, selector) in selectors.into_iter().enumerate() {
+            let unique = if rank == 0 { name.clone() } else { format!("{}_{}", name, rank) };
+            names.insert(selector, unique);
+        }
     }
 
-    (arg_names, msg_send_selector)
+    methods
+        .into_iter()
+        .map(|method| (method, names[method.name.as_str()].clone()))
+        .collect()
+}
+
+/// Wrapper type for an annotated object value, if its class has a wrapper in scope
+fn annotated_wrapper(
+    ty: &ObjCType,
+    annotation: Option<&ObjectAnnotation>,
+    wrappers: &HashSet<&str>,
+) -> Option<(Nullability, String)> {
+    let annotation = annotation?;
+    let class = annotation.class.as_deref()?;
+
+    if *ty != ObjCType::Id || !wrappers.contains(class) {
+        return None;
+    }
+
+    Some((annotation.nullability, sanitize_class_name(class)))
+}
+
+/// Wrapper for an object return value
+///
+/// The annotated class's wrapper if it is in scope, `AnyObject` otherwise.
+/// Returns without annotation are treated as nullable.
+fn return_object(
+    ty: &ObjCType,
+    annotation: Option<&ObjectAnnotation>,
+    wrappers: &HashSet<&str>,
+) -> Option<(Nullability, String)> {
+    if *ty != ObjCType::Id {
+        return None;
+    }
+
+    let nullability = annotation.map_or(Nullability::Nullable, |a| a.nullability);
+    let wrapper = annotation
+        .and_then(|a| a.class.as_deref())
+        .filter(|class| wrappers.contains(class))
+        .map_or_else(|| "crate::rc::AnyObject".to_string(), sanitize_class_name);
+
+    Some((nullability, wrapper))
+}
+
+/// Cocoa method family, which decides the ownership of returned objects
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MethodFamily {
+    /// Returns +0
+    Other,
+    /// `alloc`, `new`, `copy`, `mutableCopy`: returns +1
+    Owned,
+    /// `init`: consumes the receiver and returns +1
+    Init,
+}
+
+fn method_family(selector: &str) -> MethodFamily {
+    // The family word must end at a camelCase boundary: `copyWithZone:` but not `copyright`
+    let name = selector.trim_start_matches('_');
+    let starts_with_word = |word: &str| {
+        name.strip_prefix(word)
+            .is_some_and(|rest| !rest.starts_with(|c: char| c.is_ascii_lowercase()))
+    };
+
+    if starts_with_word("init") {
+        MethodFamily::Init
+    } else if ["alloc", "new", "copy", "mutableCopy"].into_iter().any(starts_with_word) {
+        MethodFamily::Owned
+    } else {
+        MethodFamily::Other
+    }
 }
 
 /// Generate a single method binding from a method signature with custom name
-fn generate_method_binding_named(output: &mut String, method: &ObjCMethod, method_name: &str) {
+///
+/// Object arguments with nullability annotations use the class wrappers:
+/// nullable ones as `Option<&Wrapper>`, nonnull ones as `&Wrapper`. Object
+/// return values are `Option<Retained<Wrapper>>`, or `Retained<Wrapper>`
+/// when annotated nonnull.
+fn generate_method_binding_named(
+    output: &mut String,
+    method: &ObjCMethod,
+    method_name: &str,
+    wrappers: &HashSet<&str>,
+) {
     // Parse the method type encoding
     let sig = match parse_method_encoding(&method.type_encoding) {
         Some(s) => s,
@@ -267,11 +710,20 @@ fn generate_method_binding_named(output: &mut String, method: &ObjCMethod, metho
     let method_args = &sig.arg_types[2..];
     let arg_count = method_args.len();
 
-    // Generate selector call and argument names
-    let (_selector_arg_names, selector_str) = generate_selector_call(&method.name, arg_count);
+    // Name the arguments after the selector keywords
+    let arg_names = argument_names(&method.name, arg_count);
 
     // Generate return type
     let return_type = sig.return_type.to_rust_type();
+    let has_error = has_error_out_parameter(&method.name, method_args);
+    let return_wrapper = return_object(&sig.return_type, method.nullability.first().and_then(Option::as_ref), wrappers)
+        // Methods with an NSError out-parameter return nil on failure, whatever the annotation says
+        .map(|(nullability, wrapper)| if has_error { (Nullability::Nullable, wrapper) } else { (nullability, wrapper) });
+    let arg_wrappers: Vec<_> = method_args
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| annotated_wrapper(ty, method.nullability.get(i + 1).and_then(Option::as_ref), wrappers))
+        .collect();
 
     // Build method signature
     writeln!(output).unwrap();
@@ -280,18 +732,31 @@ fn generate_method_binding_named(output: &mut String, method: &ObjCMethod, metho
     writeln!(output, "    #[inline]").unwrap();
     write!(output, "    pub unsafe fn {}(&self", method_name).unwrap();
 
-    // Add arguments to signature - use simple arg0, arg1, arg2 naming
-    for (i, arg_type) in method_args.iter().enumerate() {
-        let arg_name = format!("arg{}", i);
-        let rust_type = arg_type.to_rust_type();
+    // Add arguments to signature
+    let mut params = Vec::with_capacity(arg_count);
+    for ((arg_name, arg_type), wrapper) in arg_names.iter().zip(method_args).zip(&arg_wrappers) {
+        let rust_type = match wrapper {
+            Some((Nullability::Nullable, wrapper)) => format!("Option<&{}>", wrapper),
+            Some((Nullability::Nonnull, wrapper)) => format!("&{}", wrapper),
+            None => arg_type.to_rust_arg_type(),
+        };
         write!(output, ", {}: {}", arg_name, rust_type).unwrap();
+        params.push((arg_name.as_str(), rust_type));
     }
 
-    writeln!(output, ") -> {} {{", return_type).unwrap();
+    match &return_wrapper {
+        Some((Nullability::Nullable, wrapper)) => {
+            writeln!(output, ") -> Option<crate::rc::Retained<{}>> {{", wrapper).unwrap()
+        }
+        Some((Nullability::Nonnull, wrapper)) => {
+            writeln!(output, ") -> crate::rc::Retained<{}> {{", wrapper).unwrap()
+        }
+        None => writeln!(output, ") -> {} {{", return_type).unwrap(),
+    }
 
-    // Generate the method body using objc_msgSend
-    writeln!(output, "        let sel = sel_registerName(b\"{}\\0\".as_ptr() as *const i8);",
-             selector_str).unwrap();
+    // Generate the method body using objc_msgSend, with the selector registered once per process
+    writeln!(output, "        static SELECTOR: CachedSel = CachedSel::new(c\"{}\");", method.name).unwrap();
+    writeln!(output, "        let sel = SELECTOR.get();").unwrap();
 
     // Generate the appropriate msgSend call based on return type
     let msg_send_fn = match &sig.return_type {
@@ -300,30 +765,155 @@ fn generate_method_binding_named(output: &mut String, method: &ObjCMethod, metho
         ObjCType::LongLong | ObjCType::UnsignedLongLong if cfg!(target_arch = "x86_64") => {
             "objc_msgSend"
         }
-        ObjCType::Struct(_) => "objc_msgSend_stret",
         _ => "objc_msgSend",
     };
 
     // Build the function type signature for msgSend
     write!(output, "        type MsgSend = unsafe extern \"C\" fn(id, SEL").unwrap();
     for arg_type in method_args {
-        write!(output, ", {}", arg_type.to_rust_type()).unwrap();
+        write!(output, ", {}", arg_type.to_rust_arg_type()).unwrap();
     }
     writeln!(output, ") -> {};", return_type).unwrap();
 
     // Cast and call
-    writeln!(output, "        let msg_send: MsgSend = std::mem::transmute(crate::objc::{} as *const ());",
-             msg_send_fn).unwrap();
-    write!(output, "        msg_send(self.0, sel").unwrap();
-    for i in 0..arg_count {
-        write!(output, ", arg{}", i).unwrap();
+    if matches!(sig.return_type, ObjCType::Struct(..) | ObjCType::Union(..)) {
+        // As in msg_send_entry: arm64 returns aggregates in registers or through x8 with plain
+        // objc_msgSend, and only x86_64 needs objc_msgSend_stret, for those over 16 bytes
+        writeln!(output, "        #[cfg(target_arch = \"x86_64\")]").unwrap();
+        writeln!(output, "        let entry = if core::mem::size_of::<{}>() > 16 {{", return_type).unwrap();
+        writeln!(output, "            crate::objc::objc_msgSend_stret as *const ()").unwrap();
+        writeln!(output, "        }} else {{").unwrap();
+        writeln!(output, "            crate::objc::objc_msgSend as *const ()").unwrap();
+        writeln!(output, "        }};").unwrap();
+        writeln!(output, "        #[cfg(not(target_arch = \"x86_64\"))]").unwrap();
+        writeln!(output, "        let entry = crate::objc::objc_msgSend as *const ();").unwrap();
+        writeln!(output, "        let msg_send: MsgSend = std::mem::transmute(entry);").unwrap();
+    } else {
+        writeln!(output, "        let msg_send: MsgSend = std::mem::transmute(crate::objc::{} as *const ());",
+                 msg_send_fn).unwrap();
+    }
+    let mut call = String::from("msg_send(self.0, sel");
+    for (arg_name, wrapper) in arg_names.iter().zip(&arg_wrappers) {
+        match wrapper {
+            Some((Nullability::Nullable, _)) => {
+                write!(call, ", {}.map_or(core::ptr::null_mut(), |obj| obj.0)", arg_name).unwrap()
+            }
+            Some((Nullability::Nonnull, _)) => write!(call, ", {}.0", arg_name).unwrap(),
+            None => write!(call, ", {}", arg_name).unwrap(),
+        }
+    }
+    call.push(')');
+
+    match &return_wrapper {
+        Some((nullability, _)) => {
+            let family = method_family(&method.name);
+            if family == MethodFamily::Init {
+                // init consumes the receiver, so give it a reference of its own
+                writeln!(output, "        crate::objc::objc_retain(self.0);").unwrap();
+            }
+            let take = if family == MethodFamily::Other { "retain" } else { "from_raw" };
+
+            writeln!(output, "        let result = {};", call).unwrap();
+            match nullability {
+                Nullability::Nullable => {
+                    writeln!(output, "        crate::rc::Retained::{}(result)", take).unwrap()
+                }
+                Nullability::Nonnull => writeln!(
+                    output,
+                    "        crate::rc::Retained::{}(result).expect(\"nonnull method `{}` returned nil\")",
+                    take, method.name
+                )
+                .unwrap(),
+            }
+        }
+        None => writeln!(output, "        {}", call).unwrap(),
     }
-    writeln!(output, ")").unwrap();
 
     writeln!(output, "    }}").unwrap();
+
+    // Methods reporting failure through a trailing NSError** also get a try_ variant
+    if has_error {
+        generate_error_variant(output, method, method_name, &params, &sig.return_type, &return_wrapper);
+    }
 }
 
-fn generate_class_bindings(output: &mut String, class: &ObjCClass) {
+/// Whether the last selector keyword is `error:` and the last argument is `NSError **` (`^@`)
+fn has_error_out_parameter(selector: &str, method_args: &[ObjCType]) -> bool {
+    let last_keyword = selector.strip_suffix(':').and_then(|s| s.rsplit(':').next());
+
+    last_keyword == Some("error")
+        && method_args.last() == Some(&ObjCType::Pointer(Box::new(ObjCType::Id)))
+}
+
+/// Generate the `try_*` variant of an NSError out-parameter method
+///
+/// It supplies the out-pointer itself and returns `Result<T, NSErrorWrapper>`.
+/// Following Cocoa conventions, failure is signalled by the return value
+/// (`NO` or nil) where there is one; otherwise by the error being set.
+fn generate_error_variant(
+    output: &mut String,
+    method: &ObjCMethod,
+    method_name: &str,
+    params: &[(&str, String)],
+    return_type: &ObjCType,
+    return_wrapper: &Option<(Nullability, String)>,
+) {
+    let (value_params, error_param) = params.split_at(params.len() - 1);
+    // The out-parameter's name is unique among the arguments, so reuse it for the local
+    let error = error_param[0].0;
+    let try_name = format!("try_{}", method_name.strip_suffix("_error").unwrap_or(method_name));
+
+    let ok_type = match (return_type, return_wrapper) {
+        (_, Some((_, wrapper))) => format!("crate::rc::Retained<{}>", wrapper),
+        (ObjCType::Bool | ObjCType::Char | ObjCType::Void, None) => "()".to_string(),
+        _ => return_type.to_rust_type(),
+    };
+
+    writeln!(output).unwrap();
+    writeln!(output, "    /// Objective-C method `{}`, returning the `NSError` as `Err`", method.name).unwrap();
+    writeln!(output, "    #[inline]").unwrap();
+    write!(output, "    pub unsafe fn {}(&self", try_name).unwrap();
+    for (name, rust_type) in value_params {
+        write!(output, ", {}: {}", name, rust_type).unwrap();
+    }
+    writeln!(output, ") -> Result<{}, crate::nserror::NSErrorWrapper> {{", ok_type).unwrap();
+
+    writeln!(output, "        let mut {}: id = core::ptr::null_mut();", error).unwrap();
+    let mut call = format!("self.{}(", method_name);
+    for (name, _) in value_params {
+        write!(call, "{}, ", name).unwrap();
+    }
+    write!(call, "&mut {})", error).unwrap();
+
+    let err = format!("Err(crate::nserror::NSErrorWrapper::from_raw({}))", error);
+    match (return_type, return_wrapper) {
+        // Object returns of error methods are always nullable
+        (_, Some(_)) => {
+            writeln!(output, "        match {} {{", call).unwrap();
+            writeln!(output, "            Some(result) => Ok(result),").unwrap();
+            writeln!(output, "            None => {},", err).unwrap();
+            writeln!(output, "        }}").unwrap();
+        }
+        (ObjCType::Bool, None) => {
+            writeln!(output, "        if {} {{ Ok(()) }} else {{ {} }}", call, err).unwrap();
+        }
+        (ObjCType::Char, None) => {
+            writeln!(output, "        if {} != 0 {{ Ok(()) }} else {{ {} }}", call, err).unwrap();
+        }
+        (ObjCType::Void, None) => {
+            writeln!(output, "        {};", call).unwrap();
+            writeln!(output, "        if {}.is_null() {{ Ok(()) }} else {{ {} }}", error, err).unwrap();
+        }
+        _ => {
+            writeln!(output, "        let result = {};", call).unwrap();
+            writeln!(output, "        if {}.is_null() {{ Ok(result) }} else {{ {} }}", error, err).unwrap();
+        }
+    }
+
+    writeln!(output, "    }}").unwrap();
+}
+
+fn generate_class_bindings(output: &mut String, class: &ObjCClass, wrappers: &HashSet<&str>) {
     let rust_name = sanitize_class_name(&class.name);
 
     // Skip if the name is still invalid
@@ -340,35 +930,39 @@ fn generate_class_bindings(output: &mut String, class: &ObjCClass) {
     writeln!(output, "#[repr(transparent)]").unwrap();
     writeln!(output, "pub struct {}(pub id);\n", rust_name).unwrap();
 
+    // Let Retained manage the wrapper
+    writeln!(output, "unsafe impl crate::rc::Message for {} {{", rust_name).unwrap();
+    writeln!(output, "    #[inline]").unwrap();
+    writeln!(output, "    fn as_id(&self) -> id {{ self.0 }}").unwrap();
+    writeln!(output, "    #[inline]").unwrap();
+    writeln!(output, "    unsafe fn from_id(obj: id) -> Self {{ Self(obj) }}").unwrap();
+    writeln!(output, "}}\n").unwrap();
+
+    // Passed to msg_send! by value, as the object pointer it wraps
+    writeln!(output, "unsafe impl crate::encode::Encode for {} {{", rust_name).unwrap();
+    writeln!(output, "    const ENCODING: crate::encode::Encoding = crate::encode::Encoding::Object;").unwrap();
+    writeln!(output, "}}\n").unwrap();
+
     // Generate implementation
     writeln!(output, "impl {} {{", rust_name).unwrap();
 
     // Class method to get the ObjC class
     writeln!(output, "    /// Get the Objective-C Class object").unwrap();
     writeln!(output, "    pub unsafe fn class() -> Class {{").unwrap();
-    writeln!(output, "        let name = CString::new(\"{}\").unwrap();", class.name).unwrap();
-    writeln!(output, "        objc_getClass(name.as_ptr() as *const i8)").unwrap();
+    writeln!(output, "        static CLASS: CachedClass = CachedClass::new(c\"{}\");", class.name).unwrap();
+    writeln!(output, "        CLASS.get()").unwrap();
     writeln!(output, "    }}").unwrap();
 
     // Generate ALL methods from the class dump
-    // Track method names to handle duplicates
-    let mut method_names: HashMap<String, usize> = HashMap::new();
-
-    for method in &class.methods {
-        let method_name = sanitize_selector(&method.name);
-
-        // Check for duplicates and append suffix if needed
-        let count = method_names.entry(method_name.clone()).or_insert(0);
-        let current_count = *count;
-        *count += 1;
-
-        let unique_method_name = if current_count > 0 {
-            format!("{}_{}", method_name, current_count)
-        } else {
-            method_name
-        };
+    // `class` and the NSString convenience methods are generated by hand
+    let reserved: &[&str] = if class.name == "NSString" {
+        &["class", "from_str", "utf8_string"]
+    } else {
+        &["class"]
+    };
 
-        generate_method_binding_named(output, method, &unique_method_name);
+    for (method, method_name) in unique_method_names(&class.methods, reserved) {
+        generate_method_binding_named(output, method, &method_name, wrappers);
     }
 
     // Keep NSString convenience methods if present
@@ -381,21 +975,23 @@ fn generate_class_bindings(output: &mut String, class: &ObjCClass) {
 
 fn generate_nsstring_convenience_methods(output: &mut String) {
     writeln!(output, "    /// Create an NSString from a Rust str").unwrap();
-    writeln!(output, "    pub unsafe fn from_str(s: &str) -> Option<Self> {{").unwrap();
+    writeln!(output, "    pub unsafe fn from_str(s: &str) -> Option<crate::rc::Retained<Self>> {{").unwrap();
     writeln!(output, "        let class = Self::class();").unwrap();
-    writeln!(output, "        let sel = sel_registerName(b\"stringWithUTF8String:\\0\".as_ptr() as *const i8);").unwrap();
+    writeln!(output, "        static SELECTOR: CachedSel = CachedSel::new(c\"stringWithUTF8String:\");").unwrap();
+    writeln!(output, "        let sel = SELECTOR.get();").unwrap();
     writeln!(output, "        let c_str = CString::new(s).ok()?;").unwrap();
     writeln!(output, "        ").unwrap();
     writeln!(output, "        type MsgSend = unsafe extern \"C\" fn(Class, SEL, *const i8) -> id;").unwrap();
     writeln!(output, "        let msg_send: MsgSend = std::mem::transmute(crate::objc::objc_msgSend as *const ());").unwrap();
     writeln!(output, "        ").unwrap();
     writeln!(output, "        let result = msg_send(class, sel, c_str.as_ptr());").unwrap();
-    writeln!(output, "        if result.is_null() {{ None }} else {{ Some(Self(result)) }}").unwrap();
+    writeln!(output, "        crate::rc::Retained::retain(result)").unwrap();
     writeln!(output, "    }}\n").unwrap();
 
     writeln!(output, "    /// Get UTF-8 C string").unwrap();
     writeln!(output, "    pub unsafe fn utf8_string(&self) -> Option<*const i8> {{").unwrap();
-    writeln!(output, "        let sel = sel_registerName(b\"UTF8String\\0\".as_ptr() as *const i8);").unwrap();
+    writeln!(output, "        static SELECTOR: CachedSel = CachedSel::new(c\"UTF8String\");").unwrap();
+    writeln!(output, "        let sel = SELECTOR.get();").unwrap();
     writeln!(output, "        type MsgSend = unsafe extern \"C\" fn(id, SEL) -> *const i8;").unwrap();
     writeln!(output, "        let msg_send: MsgSend = std::mem::transmute(crate::objc::objc_msgSend as *const ());").unwrap();
     writeln!(output, "        let result = msg_send(self.0, sel);").unwrap();
@@ -404,7 +1000,17 @@ fn generate_nsstring_convenience_methods(output: &mut String) {
 }
 
 /// Main entry point - parses dump file and generates Rust code
-pub fn generate_from_dump_file(dump_path: &Path, output_path: &Path) -> std::io::Result<()> {
+///
+/// Writes `foundation.rs` with the Foundation classes, plus one
+/// `<module>_classes.rs` per other framework whose feature is enabled and an
+/// `objc_classes.rs` declaring those modules. Annotations from the
+/// `nullability_path` override file replace those in the dump.
+pub fn generate_from_dump_file(
+    dump_path: &Path,
+    nullability_path: Option<&Path>,
+    out_path: &Path,
+    feature_enabled: &dyn Fn(&str) -> bool,
+) -> std::io::Result<()> {
     let dump_content = fs::read_to_string(dump_path)?;
     let classes = parse_class_dump(&dump_content);
 
@@ -413,8 +1019,63 @@ pub fn generate_from_dump_file(dump_path: &Path, output_path: &Path) -> std::io:
         classes.len()
     );
 
-    let rust_code = generate_rust_bindings(&classes);
-    fs::write(output_path, rust_code)?;
+    generate_from_classes(classes, nullability_path, out_path, feature_enabled)
+}
+
+/// Generate the class modules from classes read from a dump or from images
+///
+/// Same outputs as [`generate_from_dump_file`].
+pub fn generate_from_classes(
+    mut classes: Vec<ObjCClass>,
+    nullability_path: Option<&Path>,
+    out_path: &Path,
+    feature_enabled: &dyn Fn(&str) -> bool,
+) -> std::io::Result<()> {
+    if let Some(nullability_path) = nullability_path {
+        let overrides = parse_nullability_overrides(&fs::read_to_string(nullability_path)?);
+        println!(
+            "cargo:warning=Applying {} nullability overrides",
+            overrides.len()
+        );
+        apply_nullability_overrides(&mut classes, &overrides);
+    }
+
+    let mut frameworks = group_classes_by_framework(classes);
+
+    let foundation_classes = frameworks.remove("Foundation").unwrap_or_default();
+    fs::write(out_path.join("foundation.rs"), generate_rust_bindings(&foundation_classes))?;
+
+    let mut modules = Vec::new();
+    let mut skipped = 0;
+
+    for (framework, classes) in &frameworks {
+        let module_name = framework_module_name(framework);
+
+        if !feature_enabled(&module_name) {
+            skipped += classes.len();
+            continue;
+        }
+
+        println!(
+            "cargo:warning=Generating {} Objective-C classes for {}",
+            classes.len(),
+            framework
+        );
+        fs::write(
+            out_path.join(format!("{}_classes.rs", module_name)),
+            generate_framework_class_bindings(framework, classes, &foundation_classes),
+        )?;
+        modules.push((framework.clone(), module_name));
+    }
+
+    if skipped > 0 {
+        println!(
+            "cargo:warning=Skipped {} classes from frameworks without an enabled feature",
+            skipped
+        );
+    }
+
+    fs::write(out_path.join("objc_classes.rs"), generate_class_modules(&modules))?;
 
     Ok(())
 }
@@ -426,14 +1087,23 @@ mod tests {
     #[test]
     fn test_sanitize_selector_basic() {
         assert_eq!(sanitize_selector("init"), "init");
-        assert_eq!(sanitize_selector("initWithString:"), "initWithString");
+        assert_eq!(sanitize_selector("initWithString:"), "init_with_string");
         assert_eq!(sanitize_selector("init:with:"), "init_with");
+        assert_eq!(sanitize_selector("initWithFrame:style:"), "init_with_frame_style");
+    }
+
+    #[test]
+    fn test_sanitize_selector_acronyms() {
+        assert_eq!(sanitize_selector("stringWithUTF8String:"), "string_with_utf8_string");
+        assert_eq!(sanitize_selector("URLForKey:"), "url_for_key");
+        assert_eq!(sanitize_selector("setHTTPBody:"), "set_http_body");
+        assert_eq!(sanitize_selector("_setFrame:"), "_set_frame");
     }
 
     #[test]
     fn test_sanitize_selector_special_chars() {
         assert_eq!(sanitize_selector(".cxx_destruct"), "cxx_destruct");
-        assert_eq!(sanitize_selector("operator+"), "operatorplus");
+        assert_eq!(sanitize_selector("operator+"), "operator_plus");
         assert_eq!(sanitize_selector("test-method"), "test_method");
     }
 
@@ -444,10 +1114,355 @@ mod tests {
         assert_eq!(sanitize_selector("async"), "async_");
     }
 
+    #[test]
+    fn test_argument_names() {
+        assert_eq!(argument_names("initWithFrame:style:", 2), ["frame", "style"]);
+        assert_eq!(argument_names("setObject:forKey:", 2), ["object", "key"]);
+        assert_eq!(argument_names("performSelector:withObject:afterDelay:", 3), ["selector", "object", "delay"]);
+        assert_eq!(argument_names("setHidden:", 1), ["hidden"]);
+        assert_eq!(argument_names("isKindOfClass:", 1), ["class"]);
+        assert_eq!(argument_names("tableView:cellForRowAtIndexPath:", 2), ["table_view", "index_path"]);
+        assert_eq!(argument_names("foo:foo:", 2), ["foo", "foo_1"]);
+        assert_eq!(argument_names("foo::", 2), ["foo", "arg1"]);
+        assert_eq!(argument_names("setType:", 1), ["type_"]);
+    }
+
+    #[test]
+    fn test_unique_method_names_ignore_dump_order() {
+        let method = |name: &str| ObjCMethod {
+            name: name.to_string(),
+            type_encoding: "v16@0:8".to_string(),
+            nullability: Vec::new(),
+        };
+        let names = |methods: &[ObjCMethod]| -> Vec<(String, String)> {
+            let mut names: Vec<_> = unique_method_names(methods, &["class"])
+                .into_iter()
+                .map(|(m, n)| (m.name.clone(), n))
+                .collect();
+            names.sort();
+            names
+        };
+
+        let forward = [method("frame"), method("frame:"), method("class"), method("frame")];
+        let backward = [method("frame"), method("class"), method("frame:")];
+
+        let expected = vec![
+            ("class".to_string(), "class_".to_string()),
+            ("frame".to_string(), "frame".to_string()),
+            ("frame:".to_string(), "frame_1".to_string()),
+        ];
+        assert_eq!(names(&forward), expected);
+        assert_eq!(names(&backward), expected);
+    }
+
+    #[test]
+    fn test_parse_nullability() {
+        let dump = "\
+@interface NSDictionary
+  Methods (2):
+    - objectForKey: [@24@0:8@16] (nullable id; nonnull NSString)
+    - count [Q16@0:8]
+@end
+";
+        let classes = parse_class_dump(dump);
+        let methods = &classes[0].methods;
+
+        assert_eq!(methods[0].type_encoding, "@24@0:8@16");
+        assert_eq!(
+            methods[0].nullability,
+            [
+                Some(ObjectAnnotation { nullability: Nullability::Nullable, class: None }),
+                Some(ObjectAnnotation {
+                    nullability: Nullability::Nonnull,
+                    class: Some("NSString".to_string()),
+                }),
+            ]
+        );
+        assert_eq!(methods[1].type_encoding, "Q16@0:8");
+        assert!(methods[1].nullability.is_empty());
+    }
+
+    #[test]
+    fn test_nullability_overrides() {
+        let mut classes = parse_class_dump("@interface NSBundle\n  Methods (1):\n    - bundleIdentifier [@16@0:8]\n@end\n");
+        let overrides = parse_nullability_overrides(
+            "# NSBundle\n-[NSBundle bundleIdentifier] (nullable NSString)\n-[NSBundle unknown] (_)\n",
+        );
+        apply_nullability_overrides(&mut classes, &overrides);
+
+        assert_eq!(
+            classes[0].methods[0].nullability,
+            [Some(ObjectAnnotation {
+                nullability: Nullability::Nullable,
+                class: Some("NSString".to_string()),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_nullable_wrapper_signatures() {
+        let dump = "\
+@interface NSString
+@end
+@interface NSDictionary
+  Methods (2):
+    - objectForKey: [@24@0:8@16] (nullable NSString; nonnull NSString)
+    - setValue:forKey: [v32@0:8@16@24] (_; nullable NSString; nonnull UIView)
+@end
+";
+        let output = generate_rust_bindings(&parse_class_dump(dump));
+
+        assert!(output.contains(
+            "pub unsafe fn object_for_key(&self, key: &NSString) -> Option<crate::rc::Retained<NSString>> {"
+        ));
+        assert!(output.contains("crate::rc::Retained::retain(result)"));
+        // UIView has no wrapper in this module, so it stays a raw id
+        assert!(output.contains("pub unsafe fn set_value_for_key(&self, value: Option<&NSString>, key: id) -> () {"));
+        assert!(output.contains("value.map_or(core::ptr::null_mut(), |obj| obj.0)"));
+    }
+
+    #[test]
+    fn test_error_out_parameter_variant() {
+        let dump = "\
+@interface NSString
+  Methods (3):
+    - writeToFile:atomically:encoding:error: [B44@0:8@16B24Q28^@36]
+    - initWithContentsOfFile:encoding:error: [@36@0:8@16Q24^@28] (nullable NSString; nonnull NSString; _; _)
+    - handleError: [v24@0:8@16]
+@end
+";
+        let output = generate_rust_bindings(&parse_class_dump(dump));
+
+        assert!(output.contains(
+            "pub unsafe fn try_write_to_file_atomically_encoding(&self, file: id, atomically: bool, encoding: u64) \
+             -> Result<(), crate::nserror::NSErrorWrapper> {"
+        ));
+        assert!(output.contains(
+            "if self.write_to_file_atomically_encoding_error(file, atomically, encoding, &mut error) { Ok(()) }"
+        ));
+        assert!(output.contains(
+            "pub unsafe fn try_init_with_contents_of_file_encoding(&self, file: &NSString, encoding: u64) \
+             -> Result<crate::rc::Retained<NSString>, crate::nserror::NSErrorWrapper> {"
+        ));
+        assert!(!output.contains("try_handle_error"));
+    }
+
+    #[test]
+    fn test_array_arguments_are_pointers() {
+        let dump = "\
+@interface NSValue
+  Methods (1):
+    - getBytes:length: [v32@0:8[16C]16Q24]
+@end
+";
+        let output = generate_rust_bindings(&parse_class_dump(dump));
+
+        assert!(output.contains("pub unsafe fn get_bytes_length(&self, bytes: *mut u8, length: u64) -> () {"));
+        assert!(output.contains("type MsgSend = unsafe extern \"C\" fn(id, SEL, *mut u8, u64) -> ();"));
+    }
+
+    #[test]
+    fn test_struct_returns_use_objc_msg_send_on_arm64() {
+        let dump = "\
+@interface NSValue
+  Methods (1):
+    - rangeValue [{_NSRange=QQ}16@0:8]
+@end
+";
+        let output = generate_rust_bindings(&parse_class_dump(dump));
+
+        assert!(output.contains("pub unsafe fn range_value(&self) -> NSRange {"));
+        assert!(output.contains(
+            "        #[cfg(not(target_arch = \"x86_64\"))]\n        let entry = crate::objc::objc_msgSend as *const ();"
+        ));
+        assert!(output.contains("let entry = if core::mem::size_of::<NSRange>() > 16 {"));
+    }
+
+    #[test]
+    fn test_method_family() {
+        assert_eq!(method_family("alloc"), MethodFamily::Owned);
+        assert_eq!(method_family("newObject"), MethodFamily::Owned);
+        assert_eq!(method_family("copyWithZone:"), MethodFamily::Owned);
+        assert_eq!(method_family("mutableCopy"), MethodFamily::Owned);
+        assert_eq!(method_family("_copyDescription"), MethodFamily::Owned);
+        assert_eq!(method_family("initWithFrame:"), MethodFamily::Init);
+        assert_eq!(method_family("copyright"), MethodFamily::Other);
+        assert_eq!(method_family("newsItems"), MethodFamily::Other);
+        assert_eq!(method_family("initialize"), MethodFamily::Other);
+        assert_eq!(method_family("description"), MethodFamily::Other);
+    }
+
+    #[test]
+    fn test_retained_returns() {
+        let dump = "\
+@interface NSObject
+  Methods (4):
+    - init [@16@0:8] (nonnull NSObject)
+    - copy [@16@0:8]
+    - description [@16@0:8]
+    - hash [Q16@0:8]
+@end
+";
+        let output = generate_rust_bindings(&parse_class_dump(dump));
+
+        assert!(output.contains("unsafe impl crate::rc::Message for NSObject {"));
+        assert!(output.contains("unsafe impl crate::encode::Encode for NSObject {"));
+        assert!(output.contains("pub unsafe fn init(&self) -> crate::rc::Retained<NSObject> {"));
+        assert!(output.contains("crate::objc::objc_retain(self.0);"));
+        assert!(output.contains("crate::rc::Retained::from_raw(result).expect(\"nonnull method `init` returned nil\")"));
+        assert!(output.contains("pub unsafe fn copy(&self) -> Option<crate::rc::Retained<crate::rc::AnyObject>> {"));
+        assert!(output.contains("pub unsafe fn description(&self) -> Option<crate::rc::Retained<crate::rc::AnyObject>> {"));
+        assert!(output.contains("pub unsafe fn hash(&self) -> u64 {"));
+    }
+
     #[test]
     fn test_sanitize_class_name() {
         assert_eq!(sanitize_class_name("NSString"), "NSString");
         assert_eq!(sanitize_class_name("NS.Something"), "NS_Something");
         assert_eq!(sanitize_class_name("Test-Class"), "Test_Class");
     }
+
+    #[test]
+    fn test_framework_for_image() {
+        assert_eq!(
+            framework_for_image("/System/Library/Frameworks/Foundation.framework/Foundation"),
+            Some("Foundation".to_string())
+        );
+        assert_eq!(
+            framework_for_image("/System/Library/PrivateFrameworks/UIKitCore.framework/UIKitCore"),
+            Some("UIKitCore".to_string())
+        );
+        assert_eq!(
+            framework_for_image("/System/Library/Frameworks/A.framework/Frameworks/B.framework/B"),
+            Some("B".to_string())
+        );
+        assert_eq!(framework_for_image("/usr/lib/libobjc.A.dylib"), Some("Foundation".to_string()));
+        assert_eq!(framework_for_image("/System/Library/CoreServices/SpringBoard.app/SpringBoard"), None);
+    }
+
+    #[test]
+    fn test_group_classes_by_framework() {
+        let dump = "\
+@interface NSString
+  Superclass: NSObject
+  Image: /System/Library/Frameworks/Foundation.framework/Foundation
+@end
+@interface UIView
+  Superclass: UIResponder
+  Image: /System/Library/PrivateFrameworks/UIKitCore.framework/UIKitCore
+@end
+@interface SBIconView
+  Image: /System/Library/CoreServices/SpringBoard.app/SpringBoard
+@end
+@interface NSLegacy
+@end
+";
+        let frameworks = group_classes_by_framework(parse_class_dump(dump));
+
+        assert_eq!(frameworks.len(), 2);
+        let foundation: Vec<&str> = frameworks["Foundation"].iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(foundation, ["NSString", "NSLegacy"]);
+        assert_eq!(frameworks["UIKitCore"][0].name, "UIView");
+        assert_eq!(framework_module_name("UIKitCore"), "uikitcore");
+    }
+
+    #[test]
+    fn test_parse_class_dump_sections() {
+        let dump = "\
+@interface UIView
+  Superclass: UIResponder
+  Ivars (2):
+    _layer [@\"CALayer\"] 40
+    _viewFlags [{?=\"dummy\"b1}] -8
+  Properties (1):
+    @property frame [T{CGRect={CGPoint=dd}{CGSize=dd}},N]
+  Class Methods (1):
+    + layerClass [#16@0:8]
+  Methods (1):
+    - layer [@16@0:8]
+@end
+";
+        let class = &parse_class_dump(dump)[0];
+
+        assert_eq!(class.ivars.len(), 2);
+        assert_eq!(class.ivars[0].type_encoding, "@\"CALayer\"");
+        assert_eq!(class.ivars[0].offset, 40);
+        assert_eq!(class.ivars[1].type_encoding, "{?=\"dummy\"b1}");
+        assert_eq!(class.ivars[1].offset, -8);
+        assert_eq!(class.properties[0].name, "frame");
+        assert_eq!(class.properties[0].attributes, "T{CGRect={CGPoint=dd}{CGSize=dd}},N");
+        assert_eq!(class.class_methods[0].name, "layerClass");
+        assert_eq!(class.class_methods[0].type_encoding, "#16@0:8");
+        assert_eq!(class.methods.len(), 1);
+        assert_eq!(class.methods[0].name, "layer");
+    }
+
+    #[test]
+    fn test_class_dump_round_trip() {
+        use super::super::class_dump_format::{DumpedClass, DumpedIvar, DumpedMethod, DumpedProperty};
+
+        let method = |name: &str, type_encoding: &str| DumpedMethod {
+            name: name.to_string(),
+            type_encoding: type_encoding.to_string(),
+        };
+        let dumped = [
+            DumpedClass {
+                name: "SBIconView".to_string(),
+                superclass: Some("UIView".to_string()),
+                image: Some("/System/Library/PrivateFrameworks/SpringBoardHome.framework/SpringBoardHome".to_string()),
+                ivars: vec![DumpedIvar {
+                    name: "_icon".to_string(),
+                    type_encoding: "@\"SBIcon\"".to_string(),
+                    offset: 608,
+                }],
+                properties: vec![DumpedProperty {
+                    name: "highlighted".to_string(),
+                    attributes: "TB,N,GisHighlighted".to_string(),
+                }],
+                class_methods: vec![method("defaultIconImageSize", "{CGSize=dd}16@0:8")],
+                methods: vec![
+                    method("setHighlighted:", "v20@0:8B16"),
+                    method("initWithConfigurationOptions:listLayoutProvider:", "@32@0:8Q16@24"),
+                ],
+            },
+            // A root class with nothing but its name
+            DumpedClass {
+                name: "NSProxy".to_string(),
+                ..Default::default()
+            },
+        ];
+        let text: String = dumped.iter().map(|class| class.to_string()).collect();
+
+        let parsed = parse_class_dump(&text);
+        assert_eq!(parsed.len(), dumped.len());
+        for (parsed, dumped) in parsed.iter().zip(&dumped) {
+            assert_eq!(parsed.name, dumped.name);
+            assert_eq!(parsed.superclass, dumped.superclass);
+            assert_eq!(parsed.image, dumped.image);
+            let ivars: Vec<_> = parsed
+                .ivars
+                .iter()
+                .map(|ivar| DumpedIvar {
+                    name: ivar.name.clone(),
+                    type_encoding: ivar.type_encoding.clone(),
+                    offset: ivar.offset,
+                })
+                .collect();
+            assert_eq!(ivars, dumped.ivars);
+            let properties: Vec<_> = parsed
+                .properties
+                .iter()
+                .map(|property| DumpedProperty {
+                    name: property.name.clone(),
+                    attributes: property.attributes.clone(),
+                })
+                .collect();
+            assert_eq!(properties, dumped.properties);
+            let methods = |methods: &[ObjCMethod]| -> Vec<DumpedMethod> {
+                methods.iter().map(|m| method(&m.name, &m.type_encoding)).collect()
+            };
+            assert_eq!(methods(&parsed.class_methods), dumped.class_methods);
+            assert_eq!(methods(&parsed.methods), dumped.methods);
+        }
+    }
 }
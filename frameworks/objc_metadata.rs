@@ -0,0 +1,442 @@
+//! Objective-C metadata in Mach-O images
+//!
+//! Reads the classes an image defines from its `__objc_classlist` section,
+//! following each `class_t` to its `class_ro_t` and from there to the method,
+//! ivar and property lists, the same structures the runtime registers at
+//! load time. The result is the `ObjCClass`es a class dump would contain, so
+//! bindings can be generated from SDK or shared-cache images on any host.
+//!
+//! Categories aren't read: their methods only show up in a runtime dump.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::macho::{ImageMemory, MachO, Pointer, arm64_slice, c_str, read_u32};
+use super::objc_codegen::{ObjCClass, ObjCIvar, ObjCMethod, ObjCProperty};
+
+/// Bits of `class_t::bits` holding the `class_ro_t` pointer
+const FAST_DATA_MASK: u64 = 0x0000_7fff_ffff_fff8;
+
+/// `method_list_t` flag for lists of relative offsets (`method_t::small`)
+const SMALL_METHOD_LIST_FLAG: u32 = 0x8000_0000;
+/// `method_list_t` flag for relative lists whose selectors are offsets from
+/// the shared cache's selector base, not to selector references
+const DIRECT_SELECTORS_FLAG: u32 = 0x4000_0000;
+/// Bits of `entsizeAndFlags` holding the entry size
+const ENTSIZE_MASK: u32 = 0x0000_fffc;
+
+// class_t fields
+const CLASS_ISA: u64 = 0;
+const CLASS_SUPERCLASS: u64 = 8;
+const CLASS_BITS: u64 = 32;
+
+// class_ro_t fields
+const RO_NAME: u64 = 24;
+const RO_BASE_METHODS: u64 = 32;
+const RO_IVARS: u64 = 48;
+const RO_BASE_PROPERTIES: u64 = 64;
+
+/// Prefix of the symbol of an imported class
+const CLASS_SYMBOL_PREFIX: &str = "_OBJC_CLASS_$_";
+
+/// The classes in the Mach-O file at `path`, or in its arm64 slice
+///
+/// `Image:` is the install name, or the path for an executable.
+pub fn classes_in_file(path: &Path) -> io::Result<Vec<ObjCClass>> {
+    let data = fs::read(path)?;
+    let macho = arm64_slice(&data)
+        .and_then(MachO::parse)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an arm64 Mach-O file"))?;
+    let image = macho
+        .install_name
+        .clone()
+        .unwrap_or_else(|| path.display().to_string());
+    Ok(classes_in_image(&macho, &macho, Some(&image)))
+}
+
+/// The classes listed in the `__objc_classlist` of `macho`
+///
+/// `memory` is where the addresses in the image are read from: `macho`
+/// itself for a standalone file, or the shared cache it was extracted from.
+pub fn classes_in_image(macho: &MachO, memory: &dyn ImageMemory, image: Option<&str>) -> Vec<ObjCClass> {
+    let Some(classlist) = macho.section("__objc_classlist") else {
+        return Vec::new();
+    };
+
+    (0..classlist.size / 8)
+        .filter_map(|i| address(memory, classlist.addr + i * 8))
+        .filter_map(|class| read_class(memory, class, image))
+        .collect()
+}
+
+/// The non-NULL address stored at `addr`
+fn address(memory: &dyn ImageMemory, addr: u64) -> Option<u64> {
+    match memory.pointer_at(addr)? {
+        Pointer::Address(0) | Pointer::Bind(_) => None,
+        Pointer::Address(target) => Some(target),
+    }
+}
+
+/// The C string a pointer at `addr` points to
+fn string_at(memory: &dyn ImageMemory, addr: u64) -> Option<String> {
+    let target = address(memory, addr)?;
+    c_str(memory.bytes_at(target)?).map(str::to_string)
+}
+
+fn read_u32_at(memory: &dyn ImageMemory, addr: u64) -> Option<u32> {
+    read_u32(memory.bytes_at(addr)?, 0)
+}
+
+fn read_i32_at(memory: &dyn ImageMemory, addr: u64) -> Option<i64> {
+    read_u32_at(memory, addr).map(|value| value as i32 as i64)
+}
+
+/// The `class_ro_t` of the class at `class`
+fn class_ro(memory: &dyn ImageMemory, class: u64) -> Option<u64> {
+    // Swift sets flags in the low bits
+    address(memory, class + CLASS_BITS).map(|bits| bits & FAST_DATA_MASK)
+}
+
+fn class_name(memory: &dyn ImageMemory, class: u64) -> Option<String> {
+    string_at(memory, class_ro(memory, class)? + RO_NAME)
+}
+
+fn read_class(memory: &dyn ImageMemory, class: u64, image: Option<&str>) -> Option<ObjCClass> {
+    let ro = class_ro(memory, class)?;
+    let name = string_at(memory, ro + RO_NAME)?;
+
+    // Classes from other images are binds to their `_OBJC_CLASS_$_` symbol
+    let superclass = match memory.pointer_at(class + CLASS_SUPERCLASS) {
+        Some(Pointer::Address(0)) | None => None,
+        Some(Pointer::Address(superclass)) => class_name(memory, superclass),
+        Some(Pointer::Bind(ordinal)) => memory
+            .import_name(ordinal)
+            .and_then(|symbol| symbol.strip_prefix(CLASS_SYMBOL_PREFIX))
+            .map(str::to_string),
+    };
+
+    let class_methods = address(memory, class + CLASS_ISA)
+        .and_then(|metaclass| class_ro(memory, metaclass))
+        .and_then(|meta_ro| address(memory, meta_ro + RO_BASE_METHODS))
+        .map(|list| read_methods(memory, list))
+        .unwrap_or_default();
+
+    Some(ObjCClass {
+        name,
+        superclass,
+        image: image.map(str::to_string),
+        methods: address(memory, ro + RO_BASE_METHODS)
+            .map(|list| read_methods(memory, list))
+            .unwrap_or_default(),
+        class_methods,
+        properties: address(memory, ro + RO_BASE_PROPERTIES)
+            .map(|list| read_properties(memory, list))
+            .unwrap_or_default(),
+        ivars: address(memory, ro + RO_IVARS)
+            .map(|list| read_ivars(memory, list))
+            .unwrap_or_default(),
+    })
+}
+
+/// The entry addresses of an `entsize_list_tt` (method, ivar and property lists)
+fn list_entries(memory: &dyn ImageMemory, list: u64) -> Option<(u32, impl Iterator<Item = u64>)> {
+    let entsize_and_flags = read_u32_at(memory, list)?;
+    let count = read_u32_at(memory, list + 4)?;
+    let entsize = (entsize_and_flags & ENTSIZE_MASK) as u64;
+    Some((entsize_and_flags, (0..count as u64).map(move |i| list + 8 + i * entsize)))
+}
+
+fn read_methods(memory: &dyn ImageMemory, list: u64) -> Vec<ObjCMethod> {
+    let Some((flags, entries)) = list_entries(memory, list) else {
+        return Vec::new();
+    };
+    let small = flags & SMALL_METHOD_LIST_FLAG != 0;
+    let direct = flags & DIRECT_SELECTORS_FLAG != 0;
+
+    entries
+        .filter_map(|entry| {
+            let (name, type_encoding) = if small {
+                read_small_method(memory, entry, direct)?
+            } else {
+                // method_t::big: name, types, imp
+                (string_at(memory, entry)?, string_at(memory, entry + 8)?)
+            };
+            Some(ObjCMethod {
+                name,
+                type_encoding,
+                nullability: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// A `method_t::small`: 32-bit offsets from each field to its target
+///
+/// The name is an offset from the selector base if the list has `direct`
+/// selectors, otherwise from the entry to a selector reference.
+fn read_small_method(memory: &dyn ImageMemory, entry: u64, direct: bool) -> Option<(String, String)> {
+    let name_offset = read_i32_at(memory, entry)?;
+    let types_offset = read_i32_at(memory, entry + 4)?;
+
+    let name = if direct {
+        let base = memory.relative_selector_base()?;
+        c_str(memory.bytes_at(base.checked_add_signed(name_offset)?)?)?.to_string()
+    } else {
+        string_at(memory, entry.checked_add_signed(name_offset)?)?
+    };
+    let types = c_str(memory.bytes_at((entry + 4).checked_add_signed(types_offset)?)?)?.to_string();
+    Some((name, types))
+}
+
+fn read_ivars(memory: &dyn ImageMemory, list: u64) -> Vec<ObjCIvar> {
+    let Some((_, entries)) = list_entries(memory, list) else {
+        return Vec::new();
+    };
+
+    // ivar_t: offset pointer, name, type, alignment, size
+    entries
+        .filter_map(|entry| {
+            let offset = address(memory, entry).and_then(|offset| read_i32_at(memory, offset));
+            Some(ObjCIvar {
+                name: string_at(memory, entry + 8)?,
+                type_encoding: string_at(memory, entry + 16).unwrap_or_default(),
+                offset: offset.unwrap_or(0) as isize,
+            })
+        })
+        .collect()
+}
+
+fn read_properties(memory: &dyn ImageMemory, list: u64) -> Vec<ObjCProperty> {
+    let Some((_, entries)) = list_entries(memory, list) else {
+        return Vec::new();
+    };
+
+    // property_t: name, attributes
+    entries
+        .filter_map(|entry| {
+            Some(ObjCProperty {
+                name: string_at(memory, entry)?,
+                attributes: string_at(memory, entry + 8).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::super::macho::tests::{BASE, Fixture, HEADER_SIZE};
+    use super::*;
+
+    fn fixture() -> Vec<u8> {
+        fixture_at(BASE)
+    }
+
+    /// An image at `base` defining `SBIconView : UIView`, with a plain method
+    /// list on the class, a relative one on the metaclass, an ivar and a property
+    pub(crate) fn fixture_at(base: u64) -> Vec<u8> {
+        const CLASSLIST: usize = HEADER_SIZE;
+        const CLASS: usize = HEADER_SIZE + 0x10;
+        const METACLASS: usize = HEADER_SIZE + 0x40;
+        const SUPERCLASS: usize = HEADER_SIZE + 0x70;
+        const RO: usize = HEADER_SIZE + 0xa0;
+        const META_RO: usize = HEADER_SIZE + 0xf0;
+        const SUPER_RO: usize = HEADER_SIZE + 0x140;
+        const METHODS: usize = HEADER_SIZE + 0x190;
+        const CLASS_METHODS: usize = HEADER_SIZE + 0x1d0;
+        const IVARS: usize = HEADER_SIZE + 0x1f0;
+        const IVAR_OFFSET: usize = HEADER_SIZE + 0x220;
+        const PROPERTIES: usize = HEADER_SIZE + 0x230;
+        const SELREF: usize = HEADER_SIZE + 0x250;
+        const STRINGS: usize = HEADER_SIZE + 0x300;
+
+        let addr = |offset: usize| base + offset as u64;
+        let mut fixture = Fixture::with_base(base, 0x500);
+        fixture.install_name("/System/Library/PrivateFrameworks/SpringBoardHome.framework/SpringBoardHome");
+        fixture.section("__objc_classlist", CLASSLIST, 8);
+
+        let strings = [
+            "SBIconView",
+            "UIView",
+            "setHighlighted:",
+            "v20@0:8B16",
+            "defaultIconImageSize",
+            "{CGSize=dd}16@0:8",
+            "_icon",
+            "@\"SBIcon\"",
+            "highlighted",
+            "TB,N,GisHighlighted",
+        ];
+        let mut string_addrs = Vec::new();
+        let mut offset = STRINGS;
+        for string in strings {
+            fixture.put_str(offset, string);
+            string_addrs.push(addr(offset));
+            offset += string.len() + 1;
+        }
+        let string = |s: &str| string_addrs[strings.iter().position(|&x| x == s).unwrap()];
+
+        fixture.put_u64(CLASSLIST, addr(CLASS));
+
+        // class_t: isa, superclass, cache, vtable, bits (with a Swift flag bit set)
+        fixture.put_u64(CLASS, addr(METACLASS));
+        fixture.put_u64(CLASS + 8, addr(SUPERCLASS));
+        fixture.put_u64(CLASS + 32, addr(RO) | 1);
+        fixture.put_u64(METACLASS + 32, addr(META_RO));
+        fixture.put_u64(SUPERCLASS + 32, addr(SUPER_RO));
+
+        fixture.put_u64(RO + 24, string("SBIconView"));
+        fixture.put_u64(RO + 32, addr(METHODS));
+        fixture.put_u64(RO + 48, addr(IVARS));
+        fixture.put_u64(RO + 64, addr(PROPERTIES));
+        fixture.put_u64(META_RO + 24, string("SBIconView"));
+        fixture.put_u64(META_RO + 32, addr(CLASS_METHODS));
+        fixture.put_u64(SUPER_RO + 24, string("UIView"));
+
+        // method_t::big list
+        fixture.put_u32(METHODS, 24);
+        fixture.put_u32(METHODS + 4, 1);
+        fixture.put_u64(METHODS + 8, string("setHighlighted:"));
+        fixture.put_u64(METHODS + 16, string("v20@0:8B16"));
+
+        // method_t::small list, whose name goes through a selector reference
+        fixture.put_u32(CLASS_METHODS, SMALL_METHOD_LIST_FLAG | 12);
+        fixture.put_u32(CLASS_METHODS + 4, 1);
+        let entry = CLASS_METHODS + 8;
+        fixture.put_u64(SELREF, string("defaultIconImageSize"));
+        fixture.put_u32(entry, (SELREF as i64 - entry as i64) as u32);
+        fixture.put_u32(entry + 4, (string("{CGSize=dd}16@0:8") - addr(entry + 4)) as u32);
+
+        fixture.put_u32(IVARS, 32);
+        fixture.put_u32(IVARS + 4, 1);
+        fixture.put_u64(IVARS + 8, addr(IVAR_OFFSET));
+        fixture.put_u64(IVARS + 16, string("_icon"));
+        fixture.put_u64(IVARS + 24, string("@\"SBIcon\""));
+        fixture.put_u32(IVAR_OFFSET, 608);
+
+        fixture.put_u32(PROPERTIES, 16);
+        fixture.put_u32(PROPERTIES + 4, 1);
+        fixture.put_u64(PROPERTIES + 8, string("highlighted"));
+        fixture.put_u64(PROPERTIES + 16, string("TB,N,GisHighlighted"));
+
+        fixture.build()
+    }
+
+    #[test]
+    fn test_classes_in_image() {
+        let data = fixture();
+        let macho = MachO::parse(&data).unwrap();
+        let classes = classes_in_image(&macho, &macho, macho.install_name.as_deref());
+
+        assert_eq!(classes.len(), 1);
+        let class = &classes[0];
+        assert_eq!(class.name, "SBIconView");
+        assert_eq!(class.superclass.as_deref(), Some("UIView"));
+        assert_eq!(
+            class.image.as_deref(),
+            Some("/System/Library/PrivateFrameworks/SpringBoardHome.framework/SpringBoardHome")
+        );
+
+        assert_eq!(class.methods.len(), 1);
+        assert_eq!(class.methods[0].name, "setHighlighted:");
+        assert_eq!(class.methods[0].type_encoding, "v20@0:8B16");
+        assert_eq!(class.class_methods.len(), 1);
+        assert_eq!(class.class_methods[0].name, "defaultIconImageSize");
+        assert_eq!(class.class_methods[0].type_encoding, "{CGSize=dd}16@0:8");
+
+        assert_eq!(
+            class.ivars,
+            [ObjCIvar {
+                name: "_icon".to_string(),
+                type_encoding: "@\"SBIcon\"".to_string(),
+                offset: 608,
+            }]
+        );
+        assert_eq!(
+            class.properties,
+            [ObjCProperty {
+                name: "highlighted".to_string(),
+                attributes: "TB,N,GisHighlighted".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_classes_in_file() {
+        let path = std::env::temp_dir().join(format!("ios-sys-objc-metadata-{}", std::process::id()));
+        fs::write(&path, fixture()).unwrap();
+        let classes = classes_in_file(&path);
+        fs::remove_file(&path).unwrap();
+
+        let classes = classes.unwrap();
+        assert_eq!(classes[0].name, "SBIconView");
+        assert!(classes_in_file(Path::new("/nonexistent/ios-sys")).is_err());
+    }
+
+    #[test]
+    fn test_imported_superclass() {
+        struct Bound<'a>(MachO<'a>);
+
+        impl ImageMemory for Bound<'_> {
+            fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+                self.0.bytes_at(addr)
+            }
+
+            fn pointer_at(&self, addr: u64) -> Option<Pointer> {
+                // The superclass slot of the fixture's class
+                if addr == BASE + HEADER_SIZE as u64 + 0x18 {
+                    return Some(Pointer::Bind(0));
+                }
+                self.0.pointer_at(addr)
+            }
+
+            fn import_name(&self, ordinal: u32) -> Option<&str> {
+                (ordinal == 0).then_some("_OBJC_CLASS_$_UIView")
+            }
+        }
+
+        let data = fixture();
+        let macho = MachO::parse(&data).unwrap();
+        let memory = Bound(MachO::parse(&data).unwrap());
+        let classes = classes_in_image(&macho, &memory, None);
+        assert_eq!(classes[0].superclass.as_deref(), Some("UIView"));
+        assert_eq!(classes[0].image, None);
+    }
+
+    #[test]
+    fn test_direct_selectors_only_when_flagged() {
+        struct Cached<'a>(MachO<'a>);
+
+        impl ImageMemory for Cached<'_> {
+            fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+                self.0.bytes_at(addr)
+            }
+
+            fn pointer_at(&self, addr: u64) -> Option<Pointer> {
+                self.0.pointer_at(addr)
+            }
+
+            fn relative_selector_base(&self) -> Option<u64> {
+                Some(BASE)
+            }
+        }
+
+        let class_methods = |data: &[u8]| {
+            let macho = MachO::parse(data).unwrap();
+            let memory = Cached(MachO::parse(data).unwrap());
+            let classes = classes_in_image(&macho, &memory, None);
+            classes[0].class_methods.iter().map(|method| method.name.clone()).collect::<Vec<_>>()
+        };
+
+        // Without the flag the name still goes through the selector reference
+        let mut data = fixture();
+        assert_eq!(class_methods(&data), ["defaultIconImageSize"]);
+
+        // With it, the name is an offset from the selector base
+        let list = HEADER_SIZE + 0x1d0;
+        let name = data.windows(21).position(|bytes| bytes == b"defaultIconImageSize\0").unwrap();
+        data[list..list + 4].copy_from_slice(&(SMALL_METHOD_LIST_FLAG | DIRECT_SELECTORS_FLAG | 12).to_le_bytes());
+        data[list + 8..list + 12].copy_from_slice(&(name as u32).to_le_bytes());
+        assert_eq!(class_methods(&data), ["defaultIconImageSize"]);
+    }
+}
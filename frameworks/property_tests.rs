@@ -0,0 +1,24 @@
+//! Tests of the property attribute parser (src/introspect/property.rs)
+
+use super::introspect_property::PropertyInfo;
+
+fn property(attributes: &str) -> PropertyInfo {
+    PropertyInfo { name: "name".to_string(), attributes: attributes.to_string() }
+}
+
+#[test]
+fn test_attribute_list() {
+    let name = property("T@\"NSString\",C,N,V_name");
+    assert_eq!(name.attribute_list(), [('T', "@\"NSString\""), ('C', ""), ('N', ""), ('V', "_name")]);
+    assert_eq!(name.type_encoding(), Some("@\"NSString\""));
+    assert_eq!(name.ivar(), Some("_name"));
+    assert!(!name.is_readonly());
+}
+
+#[test]
+fn test_quoted_comma_stays_in_attribute() {
+    let delegate = property("T@\"<Observer,Delegate>\",R,W");
+    assert_eq!(delegate.attribute_list(), [('T', "@\"<Observer,Delegate>\""), ('R', ""), ('W', "")]);
+    assert!(delegate.is_readonly());
+    assert_eq!(delegate.ivar(), None);
+}
@@ -0,0 +1,603 @@
+//! dyld shared cache reader for the build script
+//!
+//! On device, system libraries and private frameworks only exist inside the
+//! shared cache (`/System/Library/Caches/com.apple.dyld/dyld_shared_cache_arm64e`
+//! or the cryptex equivalent, also found in IPSWs). This reads the cache
+//! header, its mappings (across the subcache files of iOS 15+ split caches),
+//! the image list, the local symbols and the slide info needed to decode the
+//! pointers in the data mappings, and hands images to the Mach-O reader and
+//! the ObjC metadata extractor.
+//!
+//! Subcache files are only read when an address inside them is.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use super::macho::{
+    ImageMemory, MachO, Pointer, Symbol, c_str, cache_header, cache_header_has_field, cache_header_u32,
+    cache_header_u64, cache_local_symbols, cache_local_symbols_info, read_u32, read_u64,
+};
+use super::objc_codegen::ObjCClass;
+use super::objc_metadata::classes_in_image;
+use super::tbd::TbdInfo;
+
+const MAGIC_PREFIX: &[u8] = b"dyld_v1";
+
+/// `ObjCOptimizationHeader::relativeMethodSelectorBaseAddressOffset`
+const OBJC_OPTS_RELATIVE_SELECTOR_BASE: usize = 48;
+/// `objc_opt_t::relativeMethodSelectorBaseAddressOffset`, in libobjc's `__objc_opt_ro`
+const OBJC_OPT_RELATIVE_SELECTOR_BASE: u64 = 40;
+const LIBOBJC: &str = "/usr/lib/libobjc.A.dylib";
+
+/// How much of a cache file to keep as its header, with the mappings
+const SUBCACHE_HEADER_SIZE: u64 = 0x4000;
+
+/// How pointers in the data mappings are stored, from the slide info
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlideInfo {
+    /// Plain unslid addresses (no slide info, or version 1)
+    Plain,
+    /// Version 2 (arm64): the chain delta in `delta_mask`, the rest plus `value_add`
+    V2 { delta_mask: u64, value_add: u64 },
+    /// Version 3 (arm64e): authenticated pointers are offsets from `auth_value_add`
+    V3 { auth_value_add: u64 },
+    /// Version 5 (arm64e, iOS 18): every pointer is an offset from `value_add`
+    V5 { value_add: u64 },
+}
+
+impl SlideInfo {
+    /// Parse a `dyld_cache_slide_info*` header
+    fn parse(data: &[u8]) -> Option<Self> {
+        match read_u32(data, 0)? {
+            1 => Some(SlideInfo::Plain),
+            2 => Some(SlideInfo::V2 {
+                delta_mask: read_u64(data, 24)?,
+                value_add: read_u64(data, 32)?,
+            }),
+            3 => Some(SlideInfo::V3 {
+                auth_value_add: read_u64(data, 16)?,
+            }),
+            5 => Some(SlideInfo::V5 {
+                value_add: read_u64(data, 16)?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The unslid address a raw pointer refers to
+    pub fn decode(self, raw: u64) -> u64 {
+        match self {
+            SlideInfo::Plain => raw,
+            SlideInfo::V2 { delta_mask, value_add } => {
+                let value = raw & !delta_mask;
+                if value == 0 { 0 } else { value + value_add }
+            }
+            SlideInfo::V3 { auth_value_add } => {
+                if raw >> 63 != 0 {
+                    // Authenticated: 32-bit offset from the cache, then diversity and key
+                    (raw & 0xffff_ffff) + auth_value_add
+                } else {
+                    // 51 bits of pointer, the top byte packed down next to the low 43
+                    let value = raw & 0x0007_ffff_ffff_ffff;
+                    ((value & 0x0007_f800_0000_0000) << 13) | (value & 0x0000_07ff_ffff_ffff)
+                }
+            }
+            SlideInfo::V5 { value_add } => {
+                let offset = raw & 0x3_ffff_ffff;
+                if raw >> 63 != 0 {
+                    offset + value_add
+                } else {
+                    (offset + value_add) | (((raw >> 34) & 0xff) << 56)
+                }
+            }
+        }
+    }
+}
+
+/// One of the files a cache is split into
+struct CacheFile {
+    path: Option<PathBuf>,
+    /// The start of the file, enough for the header and the mappings
+    header: Vec<u8>,
+    data: OnceLock<Vec<u8>>,
+}
+
+impl CacheFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        let mut header = Vec::new();
+        File::open(&path)?.take(SUBCACHE_HEADER_SIZE).read_to_end(&mut header)?;
+        Ok(CacheFile {
+            path: Some(path),
+            header,
+            data: OnceLock::new(),
+        })
+    }
+
+    fn from_bytes(data: Vec<u8>) -> Self {
+        CacheFile {
+            path: None,
+            header: data[..data.len().min(SUBCACHE_HEADER_SIZE as usize)].to_vec(),
+            data: OnceLock::from(data),
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        self.data.get_or_init(|| {
+            let path = self.path.as_deref().expect("in-memory cache files are always loaded");
+            fs::read(path).unwrap_or_else(|e| {
+                println!("cargo:warning=Failed to read {}: {}", path.display(), e);
+                Vec::new()
+            })
+        })
+    }
+}
+
+/// A range of vm addresses backed by part of one of the cache files
+#[derive(Debug, Clone, Copy)]
+struct Mapping {
+    address: u64,
+    size: u64,
+    file: usize,
+    file_offset: u64,
+}
+
+/// An image in the cache
+#[derive(Debug, Clone)]
+pub struct CacheImage {
+    /// Install name, e.g. `/System/Library/PrivateFrameworks/SpringBoardHome.framework/SpringBoardHome`
+    pub path: String,
+    /// Unslid address of its mach header
+    pub address: u64,
+}
+
+/// A dyld shared cache, with its subcaches and symbols file
+pub struct SharedCache {
+    files: Vec<CacheFile>,
+    symbols_file: Option<CacheFile>,
+    mappings: Vec<Mapping>,
+    images: Vec<CacheImage>,
+    base_address: u64,
+    slide: SlideInfo,
+    relative_selector_base: Option<u64>,
+}
+
+fn header_u32(header: &[u8], offset: usize) -> u32 {
+    cache_header_u32(header, offset).unwrap_or(0)
+}
+
+fn header_u64(header: &[u8], offset: usize) -> u64 {
+    cache_header_u64(header, offset).unwrap_or(0)
+}
+
+impl SharedCache {
+    /// Open the main cache file at `path`, with the subcaches and `.symbols`
+    /// file next to it
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let main = CacheFile::from_bytes(fs::read(path)?);
+        let header = &main.header;
+        let invalid = |message: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message))
+        };
+        if !header.starts_with(MAGIC_PREFIX) {
+            return Err(invalid("not a dyld shared cache"));
+        }
+
+        // Subcaches are named by suffix: ".1", ".2"... or a suffix in each entry
+        let mut files = Vec::new();
+        let subcache_count = header_u32(header, cache_header::SUBCACHE_ARRAY_COUNT) as usize;
+        let subcaches = header_u32(header, cache_header::SUBCACHE_ARRAY_OFFSET) as usize;
+        let named = cache_header_has_field(header, cache_header::CACHE_SUB_TYPE, 4);
+        for i in 0..subcache_count {
+            let suffix = if named {
+                // dyld_subcache_entry: uuid, cacheVMOffset, fileSuffix[32]
+                let entry = main.data().get(subcaches + i * 56 + 24..);
+                c_str(entry.ok_or_else(|| invalid("truncated subcache array"))?).unwrap_or("").to_string()
+            } else {
+                format!(".{}", i + 1)
+            };
+            files.push(CacheFile::open(PathBuf::from(format!("{}{}", path.display(), suffix)))?);
+        }
+
+        let has_symbols_file = cache_header_has_field(header, cache_header::SYMBOL_FILE_UUID, 16)
+            && header[cache_header::SYMBOL_FILE_UUID..cache_header::SYMBOL_FILE_UUID + 16].iter().any(|&b| b != 0);
+        let symbols_file = if has_symbols_file {
+            Some(CacheFile::from_bytes(fs::read(format!("{}.symbols", path.display()))?))
+        } else {
+            None
+        };
+
+        files.insert(0, main);
+        Self::new(files, symbols_file).ok_or_else(|| invalid("malformed cache header"))
+    }
+
+    /// A cache from the contents of its files, main cache first
+    #[cfg(test)]
+    fn from_bytes(files: Vec<Vec<u8>>, symbols_file: Option<Vec<u8>>) -> Option<Self> {
+        Self::new(
+            files.into_iter().map(CacheFile::from_bytes).collect(),
+            symbols_file.map(CacheFile::from_bytes),
+        )
+    }
+
+    fn new(files: Vec<CacheFile>, symbols_file: Option<CacheFile>) -> Option<Self> {
+        let mut mappings = Vec::new();
+        let mut slide = None;
+
+        for (index, file) in files.iter().enumerate() {
+            let header = &file.header;
+            if !header.starts_with(MAGIC_PREFIX) {
+                return None;
+            }
+
+            let with_slide = header_u32(header, cache_header::MAPPING_WITH_SLIDE_COUNT) as usize;
+            if with_slide > 0 {
+                // dyld_cache_mapping_and_slide_info: address, size, fileOffset,
+                // slideInfoFileOffset, slideInfoFileSize, flags, maxProt, initProt
+                let offset = header_u32(header, cache_header::MAPPING_WITH_SLIDE_OFFSET) as usize;
+                for i in 0..with_slide {
+                    let entry = offset + i * 56;
+                    mappings.push(Mapping {
+                        address: read_u64(header, entry)?,
+                        size: read_u64(header, entry + 8)?,
+                        file: index,
+                        file_offset: read_u64(header, entry + 16)?,
+                    });
+                    let slide_offset = read_u64(header, entry + 24)? as usize;
+                    if slide.is_none() && read_u64(header, entry + 32)? != 0 {
+                        slide = file.data().get(slide_offset..).and_then(SlideInfo::parse);
+                    }
+                }
+            } else {
+                // dyld_cache_mapping_info: address, size, fileOffset, maxProt, initProt
+                let offset = read_u32(header, cache_header::MAPPING_OFFSET)? as usize;
+                for i in 0..read_u32(header, cache_header::MAPPING_COUNT)? as usize {
+                    let entry = offset + i * 32;
+                    mappings.push(Mapping {
+                        address: read_u64(header, entry)?,
+                        size: read_u64(header, entry + 8)?,
+                        file: index,
+                        file_offset: read_u64(header, entry + 16)?,
+                    });
+                }
+                if slide.is_none() && header_u64(header, cache_header::SLIDE_INFO_SIZE_OLD) != 0 {
+                    let slide_offset = header_u64(header, cache_header::SLIDE_INFO_OFFSET_OLD) as usize;
+                    slide = file.data().get(slide_offset..).and_then(SlideInfo::parse);
+                }
+            }
+        }
+
+        let main = files[0].data();
+        let base_address = mappings.first()?.address;
+
+        // dyld_cache_image_info: address, modTime, inode, pathFileOffset, pad
+        let (images_offset, images_count) = match header_u32(main, cache_header::IMAGES_COUNT) {
+            0 => (header_u32(main, cache_header::IMAGES_OFFSET_OLD), header_u32(main, cache_header::IMAGES_COUNT_OLD)),
+            count => (header_u32(main, cache_header::IMAGES_OFFSET), count),
+        };
+        let images = (0..images_count as usize)
+            .map(|i| {
+                let entry = images_offset as usize + i * 32;
+                let path_offset = read_u32(main, entry + 24)? as usize;
+                Some(CacheImage {
+                    path: main.get(path_offset..).and_then(c_str)?.to_string(),
+                    address: read_u64(main, entry)?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut cache = SharedCache {
+            files,
+            symbols_file,
+            mappings,
+            images,
+            base_address,
+            slide: slide.unwrap_or(SlideInfo::Plain),
+            relative_selector_base: None,
+        };
+        cache.relative_selector_base = cache.find_relative_selector_base();
+        Some(cache)
+    }
+
+    /// Where relative method list selector offsets start from, if the cache has one
+    fn find_relative_selector_base(&self) -> Option<u64> {
+        let main = self.files[0].data();
+
+        // Newer caches describe their ObjC optimizations in the header
+        let objc_opts = header_u64(main, cache_header::OBJC_OPTS_OFFSET) as usize;
+        if objc_opts != 0 {
+            let offset = read_u64(main, objc_opts + OBJC_OPTS_RELATIVE_SELECTOR_BASE)?;
+            return (offset != 0).then(|| self.base_address + offset);
+        }
+
+        // Older ones in libobjc's objc_opt_t, from version 16
+        let libobjc = self.macho(self.image(LIBOBJC)?)?;
+        let section = libobjc.section("__objc_opt_ro")?;
+        let version = read_u32(self.bytes_at(section.addr)?, 0)?;
+        let offset = read_u64(self.bytes_at(section.addr + OBJC_OPT_RELATIVE_SELECTOR_BASE)?, 0)? as i64;
+        (version >= 16 && offset != 0).then(|| section.addr.wrapping_add_signed(offset))
+    }
+
+    pub fn images(&self) -> &[CacheImage] {
+        &self.images
+    }
+
+    /// The image with install name `path`
+    pub fn image(&self, path: &str) -> Option<&CacheImage> {
+        self.images.iter().find(|image| image.path == path)
+    }
+
+    /// The names of the private frameworks in the cache, e.g. `SpringBoardHome`
+    pub fn private_frameworks(&self) -> Vec<String> {
+        self.images
+            .iter()
+            .filter_map(|image| {
+                let (name, binary) =
+                    image.path.strip_prefix("/System/Library/PrivateFrameworks/")?.split_once(".framework/")?;
+                (name == binary).then(|| name.to_string())
+            })
+            .collect()
+    }
+
+    /// The load commands of `image`
+    ///
+    /// Addresses in it must be read through the cache, which is an
+    /// [`ImageMemory`]; the file offsets in its load commands are cache offsets.
+    pub fn macho(&self, image: &CacheImage) -> Option<MachO<'_>> {
+        MachO::parse(self.bytes_at(image.address)?)
+    }
+
+    /// The names of the symbols `image` exports
+    pub fn exported_symbols(&self, image: &CacheImage) -> Vec<String> {
+        self.macho(image)
+            .map(|macho| macho.exports(self).into_iter().map(|(name, _)| name).collect())
+            .unwrap_or_default()
+    }
+
+    /// The local (non-exported) symbols of `image`
+    ///
+    /// The cache builder moves them out of the images into one table, in
+    /// the `.symbols` file for split caches. Bindings can't link to them, so
+    /// only tools looking for private functions use this.
+    #[allow(dead_code)]
+    pub fn local_symbols(&self, image: &CacheImage) -> Vec<Symbol> {
+        // The header of the file holding the table says where it is
+        let file = self.symbols_file.as_ref().unwrap_or(&self.files[0]);
+        let Some((info, wide)) = cache_local_symbols_info(file.data()) else {
+            return Vec::new();
+        };
+        match image.address.checked_sub(self.base_address) {
+            Some(dylib_offset) => cache_local_symbols(info, dylib_offset, wide),
+            None => Vec::new(),
+        }
+    }
+
+    /// The Objective-C classes `image` defines
+    pub fn classes(&self, image: &CacheImage) -> Vec<ObjCClass> {
+        self.macho(image)
+            .map(|macho| classes_in_image(&macho, self, Some(&image.path)))
+            .unwrap_or_default()
+    }
+
+    /// What a TBD stub for `image` would list: its exports and ObjC classes
+    pub fn tbd_info(&self, path: &str) -> Option<TbdInfo> {
+        let image = self.image(path)?;
+        Some(TbdInfo::from_symbols(self.exported_symbols(image)))
+    }
+}
+
+impl ImageMemory for SharedCache {
+    fn bytes_at(&self, addr: u64) -> Option<&[u8]> {
+        let mapping = self
+            .mappings
+            .iter()
+            .find(|mapping| addr >= mapping.address && addr - mapping.address < mapping.size)?;
+        let start = (mapping.file_offset + (addr - mapping.address)) as usize;
+        let end = (mapping.file_offset + mapping.size) as usize;
+        self.files[mapping.file].data().get(start..end)
+    }
+
+    fn pointer_at(&self, addr: u64) -> Option<Pointer> {
+        // Binds to other images are resolved when the cache is built
+        let raw = read_u64(self.bytes_at(addr)?, 0)?;
+        Some(Pointer::Address(self.slide.decode(raw)))
+    }
+
+    fn relative_selector_base(&self) -> Option<u64> {
+        self.relative_selector_base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::macho::tests::{Fixture, HEADER_SIZE};
+    use super::super::macho::{LC_DYLD_EXPORTS_TRIE, N_SECT};
+    use super::super::objc_metadata::tests::fixture_at;
+    use super::*;
+
+    const CACHE_BASE: u64 = 0x1_8000_0000;
+    const CACHE_HEADER_SIZE: usize = 0x200;
+    const IMAGE_OFFSET: usize = 0x1000;
+    const FOUNDATION_OFFSET: usize = 0x2000;
+    const SUBCACHE_BASE: u64 = 0x1_9000_0000;
+
+    fn put_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn put_u64(data: &mut [u8], offset: usize, value: u64) {
+        data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// A cache header mapping all of `data` at `address`
+    fn header(data: &mut [u8], address: u64) {
+        let size = data.len() as u64;
+        data[..16].copy_from_slice(b"dyld_v1  arm64e\0");
+        put_u32(data, cache_header::MAPPING_OFFSET, CACHE_HEADER_SIZE as u32);
+        put_u32(data, cache_header::MAPPING_WITH_SLIDE_OFFSET, CACHE_HEADER_SIZE as u32);
+        put_u32(data, cache_header::MAPPING_WITH_SLIDE_COUNT, 1);
+        put_u64(data, CACHE_HEADER_SIZE, address);
+        put_u64(data, CACHE_HEADER_SIZE + 8, size);
+    }
+
+    /// A split cache: SpringBoardHome and Foundation in the main file, and a
+    /// subcache holding a string the SpringBoardHome image doesn't use
+    fn cache() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let mut main = vec![0u8; 0x3000];
+        header(&mut main, CACHE_BASE);
+        put_u32(&mut main, cache_header::SUBCACHE_ARRAY_COUNT, 1);
+        main[cache_header::SYMBOL_FILE_UUID] = 1;
+
+        // Two dyld_cache_image_info, with their paths after them
+        let images = CACHE_HEADER_SIZE + 0x40;
+        put_u32(&mut main, cache_header::IMAGES_OFFSET, images as u32);
+        put_u32(&mut main, cache_header::IMAGES_COUNT, 2);
+        let paths = [
+            (IMAGE_OFFSET, "/System/Library/PrivateFrameworks/SpringBoardHome.framework/SpringBoardHome"),
+            (FOUNDATION_OFFSET, "/System/Library/Frameworks/Foundation.framework/Foundation"),
+        ];
+        let mut path_offset = images + 0x40;
+        for (i, (offset, path)) in paths.iter().enumerate() {
+            put_u64(&mut main, images + i * 32, CACHE_BASE + *offset as u64);
+            put_u32(&mut main, images + i * 32 + 24, path_offset as u32);
+            main[path_offset..path_offset + path.len()].copy_from_slice(path.as_bytes());
+            path_offset += path.len() + 1;
+        }
+
+        let image = fixture_at(CACHE_BASE + IMAGE_OFFSET as u64);
+        main[IMAGE_OFFSET..IMAGE_OFFSET + image.len()].copy_from_slice(&image);
+
+        // Foundation exports one function and one class, through a trie
+        // spelling "_" -> { "NSLog", "OBJC_CLASS_$_NSString" }
+        let trie_offset = FOUNDATION_OFFSET + HEADER_SIZE;
+        let mut foundation = Fixture::with_base(CACHE_BASE + FOUNDATION_OFFSET as u64, 0x80);
+        foundation.file_offset(FOUNDATION_OFFSET as u64);
+        let mut trie = vec![0, 1, b'_', 0, 5, 0, 2];
+        trie.extend_from_slice(b"NSLog\0");
+        trie.push(40);
+        trie.extend_from_slice(b"OBJC_CLASS_$_NSString\0");
+        trie.push(44);
+        trie.resize(40, 0);
+        trie.extend_from_slice(&[2, 0, 0x10, 0, 2, 0, 0x20, 0]);
+        foundation.load_command(LC_DYLD_EXPORTS_TRIE, &[trie_offset as u32, trie.len() as u32]);
+        foundation.data[HEADER_SIZE..HEADER_SIZE + trie.len()].copy_from_slice(&trie);
+        let foundation = foundation.build();
+        main[FOUNDATION_OFFSET..FOUNDATION_OFFSET + foundation.len()].copy_from_slice(&foundation);
+
+        let mut subcache = vec![0u8; 0x400];
+        header(&mut subcache, SUBCACHE_BASE);
+        subcache[0x300..0x30c].copy_from_slice(b"in subcache\0");
+
+        // The symbols file: a header up to symbolFileUUID, then a
+        // dyld_cache_local_symbols_info with one 64-bit entry, two nlists and their strings
+        let mut symbols = vec![0u8; 0x300];
+        let info = 0x200;
+        symbols[..16].copy_from_slice(b"dyld_v1  arm64e\0");
+        put_u32(&mut symbols, cache_header::MAPPING_OFFSET, (cache_header::SYMBOL_FILE_UUID + 16) as u32);
+        put_u64(&mut symbols, cache_header::LOCAL_SYMBOLS_OFFSET, info as u64);
+        put_u64(&mut symbols, cache_header::LOCAL_SYMBOLS_SIZE, 0x100);
+        for (i, value) in [0x18u32, 2, 0x38, 0x20, 0x60, 1].into_iter().enumerate() {
+            put_u32(&mut symbols, info + i * 4, value);
+        }
+        put_u64(&mut symbols, info + 0x60, IMAGE_OFFSET as u64);
+        put_u32(&mut symbols, info + 0x68, 0);
+        put_u32(&mut symbols, info + 0x6c, 2);
+        for (i, (strx, value)) in [(1u32, 0x1100u64), (14, 0x1200)].into_iter().enumerate() {
+            let nlist = info + 0x18 + i * 16;
+            put_u32(&mut symbols, nlist, strx);
+            symbols[nlist + 4] = N_SECT;
+            put_u64(&mut symbols, nlist + 8, CACHE_BASE + value);
+        }
+        symbols[info + 0x38 + 1..info + 0x38 + 14].copy_from_slice(b"_SBLocalFunc\0");
+        symbols[info + 0x38 + 14..info + 0x38 + 27].copy_from_slice(b"_SBOtherFunc\0");
+
+        (main, subcache, symbols)
+    }
+
+    #[test]
+    fn test_images_and_mappings() {
+        let (main, subcache, symbols) = cache();
+        let cache = SharedCache::from_bytes(vec![main, subcache], Some(symbols)).unwrap();
+
+        let paths: Vec<&str> = cache.images().iter().map(|image| image.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "/System/Library/PrivateFrameworks/SpringBoardHome.framework/SpringBoardHome",
+                "/System/Library/Frameworks/Foundation.framework/Foundation",
+            ]
+        );
+        assert_eq!(c_str(cache.bytes_at(SUBCACHE_BASE + 0x300).unwrap()), Some("in subcache"));
+        assert_eq!(cache.bytes_at(SUBCACHE_BASE + 0x400), None);
+        assert_eq!(cache.relative_selector_base(), None);
+        assert_eq!(cache.private_frameworks(), ["SpringBoardHome"]);
+    }
+
+    #[test]
+    fn test_classes_from_cache_image() {
+        let (main, subcache, symbols) = cache();
+        let cache = SharedCache::from_bytes(vec![main, subcache], Some(symbols)).unwrap();
+        let image = cache.image("/System/Library/PrivateFrameworks/SpringBoardHome.framework/SpringBoardHome").unwrap();
+
+        let classes = cache.classes(image);
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes[0].name, "SBIconView");
+        assert_eq!(classes[0].superclass.as_deref(), Some("UIView"));
+        assert_eq!(classes[0].image.as_deref(), Some(image.path.as_str()));
+        assert_eq!(classes[0].class_methods[0].name, "defaultIconImageSize");
+    }
+
+    #[test]
+    fn test_exports_and_local_symbols() {
+        let (main, subcache, symbols) = cache();
+        let cache = SharedCache::from_bytes(vec![main, subcache], Some(symbols)).unwrap();
+
+        let foundation = cache.image("/System/Library/Frameworks/Foundation.framework/Foundation").unwrap();
+        assert_eq!(cache.exported_symbols(foundation), ["_NSLog", "_OBJC_CLASS_$_NSString"]);
+        let info = cache.tbd_info(&foundation.path).unwrap();
+        assert_eq!(info.symbols, ["_NSLog"]);
+        assert_eq!(info.objc_classes, ["NSString"]);
+
+        let image = &cache.images()[0];
+        let locals: Vec<(String, u64)> = cache
+            .local_symbols(image)
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.value - CACHE_BASE))
+            .collect();
+        assert_eq!(locals, [("_SBLocalFunc".to_string(), 0x1100), ("_SBOtherFunc".to_string(), 0x1200)]);
+        assert!(cache.local_symbols(foundation).is_empty());
+    }
+
+    #[test]
+    fn test_local_symbols_of_image_below_cache() {
+        let (main, subcache, symbols) = cache();
+        let cache = SharedCache::from_bytes(vec![main, subcache], Some(symbols)).unwrap();
+
+        let image = CacheImage { path: "/usr/lib/libbad.dylib".to_string(), address: CACHE_BASE - 0x1000 };
+        assert!(cache.local_symbols(&image).is_empty());
+    }
+
+    #[test]
+    fn test_slide_info_decode() {
+        let v2 = SlideInfo::V2 {
+            delta_mask: 0x00ff_ff00_0000_0000,
+            value_add: 0,
+        };
+        assert_eq!(v2.decode(0x0004_0001_8000_1000), 0x1_8000_1000);
+        assert_eq!(v2.decode(0x0004_0000_0000_0000), 0);
+
+        let v3 = SlideInfo::V3 {
+            auth_value_add: CACHE_BASE,
+        };
+        // Authenticated, with a key, diversity and next delta above the offset
+        assert_eq!(v3.decode((1 << 63) | (0x1234 << 32) | 0x4000), CACHE_BASE + 0x4000);
+        assert_eq!(v3.decode((3 << 51) | 0x1_8000_4000), 0x1_8000_4000);
+        // The top byte is stored at bits 43..51
+        assert_eq!(v3.decode((0x80 << 43) | 0x4000), 0x8000_0000_0000_4000);
+
+        let v5 = SlideInfo::V5 { value_add: CACHE_BASE };
+        assert_eq!(v5.decode((1 << 63) | 0x4000), CACHE_BASE + 0x4000);
+        assert_eq!(v5.decode((0x80 << 34) | 0x4000), (CACHE_BASE + 0x4000) | (0x80 << 56));
+    }
+}
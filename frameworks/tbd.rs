@@ -1,11 +1,24 @@
-//! TBD (Text-Based Dylib) file parser
+//! TBD (Text-Based Dylib) file parser and writer
 //!
 //! Parses Apple's TBD stub library format to extract exported symbols and Objective-C classes.
 //! Supports both TBD v3 and v4 formats.
+//!
+//! Libraries the SDK has no stubs for (ElleKit, our own dylibs, newer private
+//! frameworks) get v4 stubs written from the Mach-O binary or a symbol list.
 
 use serde::Deserialize;
+use std::collections::HashSet;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::macho::{ImageMemory, MachO, arm64_slice};
+
+/// Targets of the stubs we write
+const TARGETS: &[&str] = &["arm64-ios", "arm64e-ios"];
+
+/// Column the values of a stub are aligned to, as `tapi` writes them
+const VALUE_COLUMN: usize = 17;
 
 /// Information extracted from a TBD file
 #[derive(Debug, Default)]
@@ -15,7 +28,43 @@ pub struct TbdInfo {
     pub objc_ivars: Vec<String>,
 }
 
-// Removed unused TbdInfo methods - they were never called
+impl TbdInfo {
+    /// Add the symbols, classes and ivars of `other` that aren't already listed
+    pub fn merge(&mut self, other: TbdInfo) {
+        fn extend_unique(list: &mut Vec<String>, other: Vec<String>) {
+            let known: HashSet<String> = list.iter().cloned().collect();
+            let mut seen = HashSet::new();
+            list.extend(other.into_iter().filter(|item| !known.contains(item) && seen.insert(item.clone())));
+        }
+
+        extend_unique(&mut self.symbols, other.symbols);
+        extend_unique(&mut self.objc_classes, other.objc_classes);
+        extend_unique(&mut self.objc_ivars, other.objc_ivars);
+    }
+
+    /// Group exported symbol names the way a TBD lists them, in their order
+    ///
+    /// Classes and ivars lose their `_OBJC_CLASS_$_` and `_OBJC_IVAR_$_`
+    /// prefixes; metaclasses are implied by their class.
+    pub fn from_symbols(symbols: impl IntoIterator<Item = String>) -> Self {
+        let mut info = TbdInfo::default();
+        for symbol in symbols {
+            if let Some(class) = symbol.strip_prefix("_OBJC_CLASS_$_") {
+                info.objc_classes.push(class.to_string());
+            } else if let Some(ivar) = symbol.strip_prefix("_OBJC_IVAR_$_") {
+                info.objc_ivars.push(ivar.to_string());
+            } else if !symbol.starts_with("_OBJC_METACLASS_$_") {
+                info.symbols.push(symbol);
+            }
+        }
+        info
+    }
+
+    /// The exports of `macho`, read from `memory`
+    pub fn from_macho(macho: &MachO, memory: &dyn ImageMemory) -> Self {
+        Self::from_symbols(macho.exports(memory).into_iter().map(|(name, _)| name))
+    }
+}
 
 /// TBD file structure (v3 format - most common in Theos SDKs)
 #[derive(Debug, Deserialize)]
@@ -96,6 +145,134 @@ pub fn parse_tbd_file(path: &Path) -> Option<TbdInfo> {
     None
 }
 
+/// Write a TBD v4 stub for the library at `install_name` exporting `info`
+pub fn write_tbd(info: &TbdInfo, install_name: &str) -> String {
+    let mut output = String::from("--- !tapi-tbd\n");
+    push_field(&mut output, "tbd-version", "4");
+    push_list(&mut output, "", "targets", TARGETS);
+    push_field(&mut output, "install-name", &single_quoted(install_name));
+
+    if !info.symbols.is_empty() || !info.objc_classes.is_empty() || !info.objc_ivars.is_empty() {
+        output.push_str("exports:\n");
+        push_list(&mut output, "  - ", "targets", TARGETS);
+        for (key, items) in [
+            ("symbols", &info.symbols),
+            ("objc-classes", &info.objc_classes),
+            ("objc-ivars", &info.objc_ivars),
+        ] {
+            if !items.is_empty() {
+                let mut items: Vec<&str> = items.iter().map(String::as_str).collect();
+                items.sort_unstable();
+                items.dedup();
+                push_list(&mut output, "    ", key, &items);
+            }
+        }
+    }
+
+    output.push_str("...\n");
+    output
+}
+
+/// A top-level `key: value`, with the value at [`VALUE_COLUMN`]
+fn push_field(output: &mut String, key: &str, value: &str) {
+    output.push_str(&format!("{:<width$}{}\n", format!("{}:", key), value, width = VALUE_COLUMN));
+}
+
+/// A flow sequence at [`VALUE_COLUMN`] past `indent`, wrapped to 80 columns
+fn push_list(output: &mut String, indent: &str, key: &str, items: &[&str]) {
+    let start = VALUE_COLUMN + indent.len();
+    let mut line = format!("{:<width$}[ ", format!("{}{}:", indent, key), width = start);
+    for (i, item) in items.iter().enumerate() {
+        let item = quote(item);
+        let separator = if i + 1 < items.len() { "," } else { "" };
+        if i > 0 && line.len() + 1 + item.len() + separator.len() > 80 {
+            output.push_str(line.trim_end());
+            output.push('\n');
+            line = " ".repeat(start + 2);
+        }
+        line.push_str(&item);
+        line.push_str(separator);
+        line.push(' ');
+    }
+    output.push_str(&line);
+    output.push_str("]\n");
+}
+
+/// `value` as a YAML scalar, single-quoted when it isn't a plain one
+fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && !value.starts_with(|c: char| "-?:!&*|>'\"%@`#".contains(c) || c.is_whitespace())
+        && !value.ends_with(char::is_whitespace)
+        && !value.contains(|c: char| ",[]{}#'\"".contains(c))
+        && !value.contains(": ")
+        && !value.ends_with(':');
+    if plain {
+        value.to_string()
+    } else {
+        single_quoted(value)
+    }
+}
+
+fn single_quoted(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// The install name and exports of the arm64 Mach-O dylib at `path`
+pub fn tbd_info_for_file(path: &Path) -> io::Result<(String, TbdInfo)> {
+    let data = fs::read(path)?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let macho = arm64_slice(&data)
+        .and_then(MachO::parse)
+        .ok_or_else(|| invalid("not an arm64 Mach-O file"))?;
+    let install_name = macho.install_name.clone().ok_or_else(|| invalid("no LC_ID_DYLIB, not a dylib"))?;
+    Ok((install_name, TbdInfo::from_macho(&macho, &macho)))
+}
+
+/// Parse a symbol list: one exported name per line, `#` starting a comment
+pub fn parse_symbol_list(content: &str) -> TbdInfo {
+    TbdInfo::from_symbols(
+        content
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim())
+            .filter(|line| !line.is_empty())
+            .map(str::to_string),
+    )
+}
+
+/// Write and link stubs for the libraries listed in `stubs`
+///
+/// Each entry is either the path of a Mach-O dylib, or
+/// `INSTALL_NAME=SYMBOL_LIST` for a library only known by its exports, split
+/// at its last `=`. The stubs are written to `out_path` and passed to the
+/// linker by path.
+#[allow(dead_code)]
+pub fn link_tbd_stubs(stubs: &[PathBuf], out_path: &Path) {
+    for stub in stubs {
+        println!("cargo:rerun-if-changed={}", stub.display());
+        let entry = stub.to_string_lossy();
+        let result = match entry.rsplit_once('=') {
+            Some((install_name, list)) => {
+                fs::read_to_string(list).map(|content| (install_name.to_string(), parse_symbol_list(&content)))
+            }
+            None => tbd_info_for_file(stub),
+        };
+        let (install_name, info) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                println!("cargo:warning=ios-sys: Cannot write a TBD stub for {}: {}", entry, e);
+                continue;
+            }
+        };
+
+        let name = Path::new(&install_name).file_stem().unwrap_or_default().to_string_lossy();
+        let tbd_path = out_path.join(format!("{}.tbd", name));
+        match fs::write(&tbd_path, write_tbd(&info, &install_name)) {
+            Ok(()) => println!("cargo:rustc-link-arg={}", tbd_path.display()),
+            Err(e) => println!("cargo:warning=ios-sys: Failed to write {}: {}", tbd_path.display(), e),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -122,4 +299,116 @@ exports:
 
         fs::remove_file(temp_file).ok();
     }
+
+    #[test]
+    fn test_merge() {
+        let mut info = TbdInfo {
+            symbols: vec!["_SBFunction".to_string()],
+            objc_classes: vec!["SBIconView".to_string()],
+            objc_ivars: Vec::new(),
+        };
+        info.merge(TbdInfo {
+            symbols: vec!["_SBFunction".to_string(), "_SBOther".to_string(), "_SBOther".to_string()],
+            objc_classes: vec!["SBIconView".to_string(), "SBIconListView".to_string()],
+            objc_ivars: vec!["SBIconView._icon".to_string()],
+        });
+
+        assert_eq!(info.symbols, ["_SBFunction", "_SBOther"]);
+        assert_eq!(info.objc_classes, ["SBIconView", "SBIconListView"]);
+        assert_eq!(info.objc_ivars, ["SBIconView._icon"]);
+    }
+
+    fn round_trip(info: &TbdInfo, name: &str) -> TbdInfo {
+        let temp_file = std::env::temp_dir().join(name);
+        fs::write(&temp_file, write_tbd(info, "/usr/lib/libellekit.dylib")).unwrap();
+        let parsed = parse_tbd_file(&temp_file).unwrap();
+        fs::remove_file(temp_file).ok();
+        parsed
+    }
+
+    #[test]
+    fn test_write_tbd_round_trip() {
+        let info = TbdInfo::from_symbols(
+            [
+                "_MSHookFunction",
+                "_OBJC_CLASS_$_ELHook",
+                "_OBJC_METACLASS_$_ELHook",
+                "_OBJC_IVAR_$_ELHook._target",
+                "__ZN5hooks7installEv",
+                "_$s8ElleKit4hookyyF",
+                "_odd, name",
+            ]
+            .map(String::from),
+        );
+        assert_eq!(info.objc_classes, ["ELHook"]);
+        assert_eq!(info.objc_ivars, ["ELHook._target"]);
+        assert_eq!(info.symbols.len(), 4);
+
+        let tbd = write_tbd(&info, "/usr/lib/libellekit.dylib");
+        assert!(tbd.starts_with("--- !tapi-tbd\ntbd-version:     4\n"));
+        assert!(tbd.contains("install-name:    '/usr/lib/libellekit.dylib'\n"));
+        assert!(tbd.contains("    objc-classes:    [ ELHook ]\n"));
+        assert!(tbd.ends_with("...\n"));
+
+        let mut parsed = round_trip(&info, "test_write_v4.tbd");
+        let mut symbols = info.symbols.clone();
+        symbols.sort();
+        parsed.symbols.sort();
+        assert_eq!(parsed.symbols, symbols);
+        assert_eq!(parsed.objc_classes, info.objc_classes);
+        assert_eq!(parsed.objc_ivars, info.objc_ivars);
+    }
+
+    #[test]
+    fn test_write_tbd_wraps_long_lists() {
+        let info = TbdInfo {
+            symbols: (0..40).map(|i| format!("_SBFunction{:02}", i)).collect(),
+            ..TbdInfo::default()
+        };
+        let tbd = write_tbd(&info, "/System/Library/PrivateFrameworks/SpringBoard.framework/SpringBoard");
+        assert!(tbd.lines().filter(|line| line.starts_with(' ')).all(|line| line.len() <= 80));
+        assert!(tbd.contains(",\n                       _SBFunction"));
+        assert_eq!(round_trip(&info, "test_write_wrapped.tbd").symbols, info.symbols);
+
+        // Nothing exported: no exports section at all
+        let empty = write_tbd(&TbdInfo::default(), "/usr/lib/libempty.dylib");
+        assert!(!empty.contains("exports"));
+    }
+
+    #[test]
+    fn test_tbd_info_from_macho_and_symbol_list() {
+        use super::super::macho::tests::{BASE, Fixture, HEADER_SIZE};
+        use super::super::macho::{LC_SYMTAB, N_EXT, N_SECT};
+
+        const SYMBOLS: usize = HEADER_SIZE;
+        const STRINGS: usize = HEADER_SIZE + 0x80;
+        let names = ["_LHHookFunctions", "_OBJC_CLASS_$_LHHook", "_OBJC_METACLASS_$_LHHook", "_local"];
+
+        let mut fixture = Fixture::new(0x200);
+        fixture.install_name("/usr/lib/libhooker.dylib");
+        fixture.load_command(LC_SYMTAB, &[SYMBOLS as u32, names.len() as u32, STRINGS as u32, 0x80]);
+        let mut string = 1;
+        for (i, name) in names.iter().enumerate() {
+            let nlist = SYMBOLS + i * 16;
+            fixture.put_u32(nlist, string as u32);
+            fixture.data[nlist + 4] = if *name == "_local" { N_SECT } else { N_SECT | N_EXT };
+            fixture.data[nlist + 5] = 1;
+            fixture.put_u64(nlist + 8, BASE + 0x100 * i as u64);
+            fixture.put_str(STRINGS + string, name);
+            string += name.len() + 1;
+        }
+
+        let temp_file = std::env::temp_dir().join("test_libhooker.dylib");
+        fs::write(&temp_file, fixture.build()).unwrap();
+        let (install_name, info) = tbd_info_for_file(&temp_file).unwrap();
+        fs::remove_file(temp_file).ok();
+
+        assert_eq!(install_name, "/usr/lib/libhooker.dylib");
+        assert_eq!(info.symbols, ["_LHHookFunctions"]);
+        assert_eq!(info.objc_classes, ["LHHook"]);
+
+        let list = parse_symbol_list("# libhooker\n_LHHookFunctions\n\n_OBJC_CLASS_$_LHHook  # class\n");
+        assert_eq!(list.symbols, info.symbols);
+        assert_eq!(list.objc_classes, info.objc_classes);
+    }
 }
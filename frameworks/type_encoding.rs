@@ -19,14 +19,65 @@ pub enum ObjCType {
     Float,
     Double,
     Id,           // @
+    Block,        // @?
     Class,        // #
     SEL,          // :
     CharPointer,  // *
-    Pointer(Box<ObjCType>),  // ^type
-    Struct(String),          // {name=fields}
+    Undefined,    // ? (e.g. function pointers, ^?)
+    Bitfield(u32),                          // b<bits>
+    Pointer(Box<ObjCType>),                 // ^type
+    Array(usize, Box<ObjCType>),            // [<count>type]
+    Struct(String, Option<Vec<ObjCType>>),  // {name=fields}, or {name} without fields
+    Union(String, Option<Vec<ObjCType>>),   // (name=fields), or (name) without fields
+    Qualified(Qualifier, Box<ObjCType>),    // const, in, out, ... followed by the type
     Unknown(String),
 }
 
+/// Type qualifiers that can precede a type in an encoding
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Qualifier {
+    Const,   // r
+    In,      // n
+    InOut,   // N
+    Out,     // o
+    Bycopy,  // O
+    Byref,   // R
+    Oneway,  // V
+    Atomic,  // A
+    Complex, // j
+}
+
+impl Qualifier {
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'r' => Qualifier::Const,
+            'n' => Qualifier::In,
+            'N' => Qualifier::InOut,
+            'o' => Qualifier::Out,
+            'O' => Qualifier::Bycopy,
+            'R' => Qualifier::Byref,
+            'V' => Qualifier::Oneway,
+            'A' => Qualifier::Atomic,
+            'j' => Qualifier::Complex,
+            _ => return None,
+        })
+    }
+
+    fn to_char(self) -> char {
+        match self {
+            Qualifier::Const => 'r',
+            Qualifier::In => 'n',
+            Qualifier::InOut => 'N',
+            Qualifier::Out => 'o',
+            Qualifier::Bycopy => 'O',
+            Qualifier::Byref => 'R',
+            Qualifier::Oneway => 'V',
+            Qualifier::Atomic => 'A',
+            Qualifier::Complex => 'j',
+        }
+    }
+}
+
 impl ObjCType {
     /// Convert ObjC type encoding to Rust type string
     pub fn to_rust_type(&self) -> String {
@@ -46,18 +97,26 @@ impl ObjCType {
             ObjCType::Float => "f32".to_string(),
             ObjCType::Double => "f64".to_string(),
             ObjCType::Id => "id".to_string(),
+            // Blocks are objects; pass crate::block::Block::as_ptr()
+            ObjCType::Block => "id".to_string(),
             ObjCType::Class => "Class".to_string(),
             ObjCType::SEL => "SEL".to_string(),
             ObjCType::CharPointer => "*const i8".to_string(),
+            ObjCType::Undefined => "c_void".to_string(),
+            ObjCType::Bitfield(_) => "u32".to_string(),
             ObjCType::Pointer(inner) => {
                 // Special handling for unknown pointer types
-                match &**inner {
-                    ObjCType::Unknown(_) => "*mut c_void".to_string(),
-                    ObjCType::Struct(s) if s.starts_with('?') || s.is_empty() => "*mut c_void".to_string(),
-                    _ => format!("*mut {}", inner.to_rust_type()),
+                match inner.unqualified() {
+                    ObjCType::Unknown(_) | ObjCType::Undefined | ObjCType::Union(..) => "*mut c_void".to_string(),
+                    ObjCType::Struct(s, _) if s.starts_with('?') || s.is_empty() => "*mut c_void".to_string(),
+                    inner => format!("*mut {}", inner.to_rust_type()),
                 }
             }
-            ObjCType::Struct(name) => {
+            ObjCType::Array(count, element) => format!("[{}; {}]", element.to_rust_type(), count),
+            // Union definitions aren't generated
+            ObjCType::Union(..) => "c_void".to_string(),
+            ObjCType::Qualified(_, inner) => inner.to_rust_type(),
+            ObjCType::Struct(name, _) => {
                 // If struct name is unknown (?), use c_void
                 if name.starts_with('?') || name.is_empty() {
                     return "c_void".to_string();
@@ -95,16 +154,135 @@ impl ObjCType {
             ObjCType::Unknown(s) => format!("/* {} */ c_void", s),
         }
     }
+
+    /// Convert an argument's type encoding to the Rust type it's passed as
+    ///
+    /// C arrays decay to a pointer to their first element when passed, so
+    /// `[4i]` is `*mut i32` here; `[i32; 4]` is only for fields and ivars.
+    pub fn to_rust_arg_type(&self) -> String {
+        match self.unqualified() {
+            ObjCType::Array(_, element) => format!("*mut {}", element.to_rust_type()),
+            ty => ty.to_rust_type(),
+        }
+    }
+
+    /// The type without its qualifiers (`r*` is `*`)
+    pub fn unqualified(&self) -> &ObjCType {
+        match self {
+            ObjCType::Qualified(_, inner) => inner.unqualified(),
+            ty => ty,
+        }
+    }
+
+    /// Serialize back to a type encoding
+    ///
+    /// Produces the canonical form `parse_type_encoding` reads back to the
+    /// same value: no quoted class or field names, which the parser skips.
+    /// `Unknown` types are written as they were read.
+    pub fn to_encoding(&self) -> String {
+        let mut encoding = String::new();
+        self.write_encoding(&mut encoding);
+        encoding
+    }
+
+    fn write_encoding(&self, out: &mut String) {
+        let code = match self {
+            ObjCType::Void => 'v',
+            ObjCType::Bool => 'B',
+            ObjCType::Char => 'c',
+            ObjCType::UnsignedChar => 'C',
+            ObjCType::Short => 's',
+            ObjCType::UnsignedShort => 'S',
+            ObjCType::Int => 'i',
+            ObjCType::UnsignedInt => 'I',
+            ObjCType::Long => 'l',
+            ObjCType::UnsignedLong => 'L',
+            ObjCType::LongLong => 'q',
+            ObjCType::UnsignedLongLong => 'Q',
+            ObjCType::Float => 'f',
+            ObjCType::Double => 'd',
+            ObjCType::Id => '@',
+            ObjCType::Block => {
+                out.push_str("@?");
+                return;
+            }
+            ObjCType::Class => '#',
+            ObjCType::SEL => ':',
+            ObjCType::CharPointer => '*',
+            ObjCType::Undefined => '?',
+            ObjCType::Bitfield(bits) => {
+                out.push('b');
+                out.push_str(&bits.to_string());
+                return;
+            }
+            ObjCType::Pointer(inner) => {
+                out.push('^');
+                inner.write_encoding(out);
+                return;
+            }
+            ObjCType::Array(count, element) => {
+                out.push('[');
+                out.push_str(&count.to_string());
+                element.write_encoding(out);
+                out.push(']');
+                return;
+            }
+            ObjCType::Struct(name, fields) => {
+                write_aggregate(out, '{', name, fields.as_deref(), '}');
+                return;
+            }
+            ObjCType::Union(name, fields) => {
+                write_aggregate(out, '(', name, fields.as_deref(), ')');
+                return;
+            }
+            ObjCType::Qualified(qualifier, inner) => {
+                out.push(qualifier.to_char());
+                inner.write_encoding(out);
+                return;
+            }
+            ObjCType::Unknown(s) => {
+                out.push_str(s);
+                return;
+            }
+        };
+        out.push(code);
+    }
+}
+
+fn write_aggregate(out: &mut String, open: char, name: &str, fields: Option<&[ObjCType]>, close: char) {
+    out.push(open);
+    out.push_str(name);
+    if let Some(fields) = fields {
+        out.push('=');
+        for field in fields {
+            field.write_encoding(out);
+        }
+    }
+    out.push(close);
 }
 
+/// Nesting depth past which types are reported as `Unknown`, so malformed
+/// input like `^^^^...` can't overflow the stack
+const MAX_DEPTH: usize = 64;
+
 /// Parse a single type encoding character/sequence
+///
+/// Returns the type and the number of bytes consumed. Never panics:
+/// malformed input yields `ObjCType::Unknown`, consuming at least one
+/// character of non-empty input.
 pub fn parse_type_encoding(encoding: &str) -> (ObjCType, usize) {
-    if encoding.is_empty() {
+    parse_type_at_depth(encoding, 0)
+}
+
+fn parse_type_at_depth(encoding: &str, depth: usize) -> (ObjCType, usize) {
+    let Some(first_char) = encoding.chars().next() else {
         return (ObjCType::Unknown("empty".to_string()), 0);
+    };
+    if depth > MAX_DEPTH {
+        return (ObjCType::Unknown(encoding.to_string()), encoding.len());
     }
 
-    let first_char = encoding.chars().next().unwrap();
-
+    let rest = &encoding[first_char.len_utf8()..];
     match first_char {
         'v' => (ObjCType::Void, 1),
         'B' => (ObjCType::Bool, 1),
@@ -120,31 +298,135 @@ pub fn parse_type_encoding(encoding: &str) -> (ObjCType, usize) {
         'Q' => (ObjCType::UnsignedLongLong, 1),
         'f' => (ObjCType::Float, 1),
         'd' => (ObjCType::Double, 1),
-        '@' => (ObjCType::Id, 1),
+        // The signature in @?<v@?B> is dropped
+        '@' if rest.starts_with('?') => (ObjCType::Block, 2 + angle_bracketed_len(&rest[1..])),
+        // The class name in @"NSString" is dropped
+        '@' => (ObjCType::Id, 1 + quoted_len(rest)),
         '#' => (ObjCType::Class, 1),
         ':' => (ObjCType::SEL, 1),
         '*' => (ObjCType::CharPointer, 1),
+        '?' => (ObjCType::Undefined, 1),
+        'b' => match leading_number(rest) {
+            Some((bits, digits)) => match u32::try_from(bits) {
+                Ok(bits) => (ObjCType::Bitfield(bits), 1 + digits),
+                Err(_) => (ObjCType::Unknown(encoding[..1 + digits].to_string()), 1 + digits),
+            },
+            None => (ObjCType::Unknown(first_char.to_string()), 1),
+        },
         '^' => {
             // Pointer to type
-            let (inner_type, consumed) = parse_type_encoding(&encoding[1..]);
+            if rest.is_empty() {
+                return (ObjCType::Unknown(first_char.to_string()), 1);
+            }
+            let (inner_type, consumed) = parse_type_at_depth(rest, depth + 1);
             (ObjCType::Pointer(Box::new(inner_type)), consumed + 1)
         }
-        '{' => {
-            // Struct type: {name=fields}
-            if let Some(end_pos) = encoding.find('}') {
-                let struct_encoding = &encoding[1..end_pos];
-                let struct_name = if let Some(eq_pos) = struct_encoding.find('=') {
-                    &struct_encoding[..eq_pos]
-                } else {
-                    struct_encoding
-                };
-                (ObjCType::Struct(struct_name.to_string()), end_pos + 1)
+        '[' => {
+            // Array type: [count type]
+            let Some((count, digits)) = leading_number(rest) else {
+                return unterminated(encoding);
+            };
+            let element_encoding = &rest[digits..];
+            if element_encoding.starts_with(']') || element_encoding.is_empty() {
+                return unterminated(encoding);
+            }
+            let (element, consumed) = parse_type_at_depth(element_encoding, depth + 1);
+            let end = 1 + digits + consumed;
+            if encoding[end..].starts_with(']') {
+                (ObjCType::Array(count, Box::new(element)), end + 1)
             } else {
-                (ObjCType::Unknown(encoding[..10.min(encoding.len())].to_string()), 1)
+                unterminated(encoding)
             }
         }
-        _ => (ObjCType::Unknown(first_char.to_string()), 1),
+        '{' => match parse_aggregate(encoding, '}', depth) {
+            Some((name, fields, consumed)) => (ObjCType::Struct(name, fields), consumed),
+            None => unterminated(encoding),
+        },
+        '(' => match parse_aggregate(encoding, ')', depth) {
+            Some((name, fields, consumed)) => (ObjCType::Union(name, fields), consumed),
+            None => unterminated(encoding),
+        },
+        _ => match Qualifier::from_char(first_char) {
+            Some(qualifier) if !rest.is_empty() => {
+                let (inner, consumed) = parse_type_at_depth(rest, depth + 1);
+                (ObjCType::Qualified(qualifier, Box::new(inner)), consumed + 1)
+            }
+            _ => (ObjCType::Unknown(first_char.to_string()), first_char.len_utf8()),
+        },
+    }
+}
+
+/// Parse `{name=fields}` or `(name=fields)` starting at the opening bracket
+///
+/// Field names (`{CGPoint="x"d"y"d}`) are skipped. Returns None if the
+/// closing bracket is missing.
+fn parse_aggregate(encoding: &str, close: char, depth: usize) -> Option<(String, Option<Vec<ObjCType>>, usize)> {
+    let body = &encoding[1..];
+    let name_end = body.find(['=', close])?;
+    let name = body[..name_end].to_string();
+    if body[name_end..].starts_with(close) {
+        return Some((name, None, 1 + name_end + 1));
     }
+
+    let mut fields = Vec::new();
+    let mut pos = name_end + 1;
+    loop {
+        pos += quoted_len(&body[pos..]);
+        let remaining = &body[pos..];
+        if remaining.starts_with(close) {
+            return Some((name, Some(fields), 1 + pos + 1));
+        }
+        if remaining.is_empty() {
+            return None;
+        }
+        let (field, consumed) = parse_type_at_depth(remaining, depth + 1);
+        if matches!(field, ObjCType::Unknown(_)) && consumed == remaining.len() {
+            return None;
+        }
+        fields.push(field);
+        pos += consumed;
+    }
+}
+
+/// Length of a leading `"..."`, 0 if there is none or it isn't closed
+fn quoted_len(encoding: &str) -> usize {
+    match encoding.strip_prefix('"').and_then(|rest| rest.find('"')) {
+        Some(end) => end + 2,
+        None => 0,
+    }
+}
+
+/// Length of a leading `<...>`, with nesting, 0 if there is none or it isn't closed
+fn angle_bracketed_len(encoding: &str) -> usize {
+    if !encoding.starts_with('<') {
+        return 0;
+    }
+    let mut depth = 0;
+    for (i, b) in encoding.bytes().enumerate() {
+        match b {
+            b'<' => depth += 1,
+            b'>' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    0
+}
+
+/// A leading decimal number and its length in bytes
+fn leading_number(encoding: &str) -> Option<(usize, usize)> {
+    let digits = encoding.bytes().take_while(u8::is_ascii_digit).count();
+    let number = encoding[..digits].parse().ok()?;
+    Some((number, digits))
+}
+
+/// An aggregate without its closing bracket, consuming the rest of the input
+fn unterminated(encoding: &str) -> (ObjCType, usize) {
+    (ObjCType::Unknown(encoding.to_string()), encoding.len())
 }
 
 #[derive(Debug, Clone)]
@@ -155,9 +437,16 @@ pub struct MethodSignature {
 
 /// Parse a full method type encoding like "@24@0:8@16"
 /// Format: return_type[stack_size]arg1_type[offset]arg2_type[offset]...
+///
+/// Qualifiers of the return and argument types (`Vv16@0:8`, `r*16`) don't
+/// change how they are passed and are dropped.
 pub fn parse_method_encoding(encoding: &str) -> Option<MethodSignature> {
     // Remove the brackets if present: [v16@0:8] -> v16@0:8
-    let clean = encoding.trim().trim_matches(|c| c == '[' || c == ']');
+    let trimmed = encoding.trim();
+    let clean = trimmed
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .unwrap_or(trimmed);
 
     if clean.is_empty() {
         return None;
@@ -166,21 +455,21 @@ pub fn parse_method_encoding(encoding: &str) -> Option<MethodSignature> {
     let mut types = Vec::new();
     let mut pos = 0;
 
-    // Parse types, skipping numeric offsets/sizes
+    // Parse types, skipping numeric offsets/sizes (negative for some register arguments)
     while pos < clean.len() {
-        let ch = clean.chars().nth(pos).unwrap();
-
-        // Skip numbers (stack sizes/offsets)
-        if ch.is_ascii_digit() {
-            while pos < clean.len() && clean.chars().nth(pos).unwrap().is_ascii_digit() {
-                pos += 1;
-            }
+        let remaining = &clean[pos..];
+        let offset_len = remaining.strip_prefix('-').unwrap_or(remaining).bytes().take_while(u8::is_ascii_digit).count();
+        if offset_len > 0 {
+            pos += offset_len + usize::from(remaining.starts_with('-'));
             continue;
         }
 
         // Parse the type
-        let (parsed_type, consumed) = parse_type_encoding(&clean[pos..]);
-        types.push(parsed_type);
+        let (parsed_type, consumed) = parse_type_encoding(remaining);
+        types.push(match parsed_type {
+            ObjCType::Qualified(..) => parsed_type.unqualified().clone(),
+            ty => ty,
+        });
         pos += consumed;
     }
 
@@ -228,6 +517,121 @@ mod tests {
         assert_eq!(sig.arg_types[2], ObjCType::Class);
     }
 
+    /// Deterministic xorshift64* generator for the round-trip tests
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    const PRIMITIVES: &[ObjCType] = &[
+        ObjCType::Void, ObjCType::Bool, ObjCType::Char, ObjCType::UnsignedChar, ObjCType::Short,
+        ObjCType::UnsignedShort, ObjCType::Int, ObjCType::UnsignedInt, ObjCType::Long,
+        ObjCType::UnsignedLong, ObjCType::LongLong, ObjCType::UnsignedLongLong, ObjCType::Float,
+        ObjCType::Double, ObjCType::Id, ObjCType::Block, ObjCType::Class, ObjCType::SEL, ObjCType::CharPointer,
+        ObjCType::Undefined,
+    ];
+
+    const QUALIFIERS: &[Qualifier] = &[
+        Qualifier::Const, Qualifier::In, Qualifier::InOut, Qualifier::Out, Qualifier::Bycopy,
+        Qualifier::Byref, Qualifier::Oneway, Qualifier::Atomic, Qualifier::Complex,
+    ];
+
+    const AGGREGATE_NAMES: &[&str] = &["CGRect", "_NSRange", "__CFString", "?", "", "vector<int, 4>"];
+
+    fn random_type(rng: &mut XorShift, depth: usize) -> ObjCType {
+        // Only leaves once nested deep enough
+        let kinds = if depth >= 4 { PRIMITIVES.len() } else { PRIMITIVES.len() + 5 };
+        match rng.below(kinds) {
+            i if i < PRIMITIVES.len() => PRIMITIVES[i].clone(),
+            i => match i - PRIMITIVES.len() {
+                0 => ObjCType::Pointer(Box::new(random_type(rng, depth + 1))),
+                1 => ObjCType::Array(rng.below(100), Box::new(random_type(rng, depth + 1))),
+                2 => ObjCType::Struct(random_name(rng), random_fields(rng, depth)),
+                3 => ObjCType::Union(random_name(rng), random_fields(rng, depth)),
+                _ => ObjCType::Qualified(QUALIFIERS[rng.below(QUALIFIERS.len())], Box::new(random_type(rng, depth + 1))),
+            },
+        }
+    }
+
+    fn random_name(rng: &mut XorShift) -> String {
+        AGGREGATE_NAMES[rng.below(AGGREGATE_NAMES.len())].to_string()
+    }
+
+    fn random_fields(rng: &mut XorShift, depth: usize) -> Option<Vec<ObjCType>> {
+        if rng.below(4) == 0 {
+            return None;
+        }
+        let mut fields: Vec<ObjCType> = Vec::new();
+        for _ in 0..rng.below(5) {
+            // Bitfields only occur as fields
+            let field = if rng.below(6) == 0 { ObjCType::Bitfield(rng.below(64) as u32 + 1) } else { random_type(rng, depth + 1) };
+            // `@` followed by `?` is the block encoding, which compilers never emit for two fields
+            let after_object = fields.last().is_some_and(|last| last.to_encoding().ends_with('@'));
+            if after_object && field.to_encoding().starts_with('?') {
+                continue;
+            }
+            fields.push(field);
+        }
+        Some(fields)
+    }
+
+    #[test]
+    fn test_type_encoding_round_trip() {
+        let mut rng = XorShift(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..5000 {
+            let ty = random_type(&mut rng, 0);
+            let encoding = ty.to_encoding();
+            assert_eq!(parse_type_encoding(&encoding), (ty, encoding.len()), "round trip of {:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn test_method_encoding_round_trip() {
+        let mut rng = XorShift(0xD1B5_4A32_D192_ED03);
+        for _ in 0..1000 {
+            let return_type = random_type(&mut rng, 0);
+            let args: Vec<ObjCType> = (0..rng.below(5)).map(|_| random_type(&mut rng, 0)).collect();
+
+            let mut encoding = format!("{}{}@0:8", return_type.to_encoding(), 16 + 8 * args.len());
+            for (i, arg) in args.iter().enumerate() {
+                encoding.push_str(&format!("{}{}", arg.to_encoding(), 16 + 8 * i));
+            }
+
+            let sig = parse_method_encoding(&encoding).unwrap();
+            let expected_args: Vec<ObjCType> = [ObjCType::Id, ObjCType::SEL]
+                .into_iter()
+                .chain(args.iter().map(|arg| arg.unqualified().clone()))
+                .collect();
+            assert_eq!(sig.return_type, *return_type.unqualified(), "return type of {:?}", encoding);
+            assert_eq!(sig.arg_types, expected_args, "arguments of {:?}", encoding);
+        }
+    }
+
+    #[test]
+    fn test_random_input_does_not_panic() {
+        const ALPHABET: &[&str] = &[
+            "{", "}", "(", ")", "[", "]", "=", "^", "@", "\"", "b", "r", "?", "i", "d", "0", "9", "-", "é", "€", "😀",
+        ];
+        let mut rng = XorShift(0x0123_4567_89AB_CDEF);
+        for _ in 0..5000 {
+            let input: String = (0..rng.below(24)).map(|_| ALPHABET[rng.below(ALPHABET.len())]).collect();
+            let (_, consumed) = parse_type_encoding(&input);
+            assert!(consumed <= input.len() && input.is_char_boundary(consumed), "{:?} consumed {}", input, consumed);
+            assert!(consumed > 0 || input.is_empty());
+            let _ = parse_method_encoding(&input);
+        }
+    }
+
     #[test]
     fn test_to_rust_type() {
         assert_eq!(ObjCType::Void.to_rust_type(), "()");
@@ -240,21 +644,98 @@ mod tests {
     #[test]
     fn test_parse_complex_struct() {
         // {CGRect={CGPoint=dd}{CGSize=dd}}
-        let (ty, _) = parse_type_encoding("{CGRect={CGPoint=dd}{CGSize=dd}}");
-        match ty {
-            ObjCType::Struct(name) => assert_eq!(name, "CGRect"),
-            _ => panic!("Expected Struct type"),
+        let encoding = "{CGRect={CGPoint=dd}{CGSize=dd}}";
+        let (ty, consumed) = parse_type_encoding(encoding);
+        let point = ObjCType::Struct("CGPoint".to_string(), Some(vec![ObjCType::Double, ObjCType::Double]));
+        let size = ObjCType::Struct("CGSize".to_string(), Some(vec![ObjCType::Double, ObjCType::Double]));
+        assert_eq!(ty, ObjCType::Struct("CGRect".to_string(), Some(vec![point, size])));
+        assert_eq!(consumed, encoding.len());
+    }
+
+    #[test]
+    fn test_parse_nested_struct_method_arguments() {
+        // - initWithFrame: takes a CGRect; the nested '}' must not end the outer struct
+        let sig = parse_method_encoding("@48@0:8{CGRect={CGPoint=dd}{CGSize=dd}}16").unwrap();
+        assert_eq!(sig.arg_types.len(), 3);
+        assert!(matches!(&sig.arg_types[2], ObjCType::Struct(name, Some(fields)) if name == "CGRect" && fields.len() == 2));
+    }
+
+    #[test]
+    fn test_parse_arrays_unions_bitfields_and_qualifiers() {
+        assert_eq!(parse_type_encoding("[4i]"), (ObjCType::Array(4, Box::new(ObjCType::Int)), 4));
+        assert_eq!(
+            parse_type_encoding("(?=iQ)"),
+            (ObjCType::Union("?".to_string(), Some(vec![ObjCType::Int, ObjCType::UnsignedLongLong])), 6)
+        );
+        assert_eq!(
+            parse_type_encoding("{flags=b1b31}"),
+            (ObjCType::Struct("flags".to_string(), Some(vec![ObjCType::Bitfield(1), ObjCType::Bitfield(31)])), 13)
+        );
+        assert_eq!(
+            parse_type_encoding("r^v"),
+            (ObjCType::Qualified(Qualifier::Const, Box::new(ObjCType::Pointer(Box::new(ObjCType::Void)))), 3)
+        );
+        assert_eq!(parse_type_encoding("^?"), (ObjCType::Pointer(Box::new(ObjCType::Undefined)), 2));
+        assert_eq!(parse_type_encoding("@?"), (ObjCType::Block, 2));
+        assert_eq!(parse_type_encoding("@?<v@?@\"NSError\">"), (ObjCType::Block, 17));
+        assert_eq!(parse_type_encoding("{__CFString}"), (ObjCType::Struct("__CFString".to_string(), None), 12));
+    }
+
+    #[test]
+    fn test_parse_skips_quoted_names() {
+        assert_eq!(parse_type_encoding("@\"NSString\""), (ObjCType::Id, 11));
+        let (ty, _) = parse_type_encoding("{CGPoint=\"x\"d\"y\"d}");
+        assert_eq!(ty, ObjCType::Struct("CGPoint".to_string(), Some(vec![ObjCType::Double, ObjCType::Double])));
+    }
+
+    #[test]
+    fn test_block_arguments() {
+        // - performBatchUpdates:completion: takes two blocks
+        let sig = parse_method_encoding("v32@0:8@?16@?24").unwrap();
+        assert_eq!(sig.arg_types, vec![ObjCType::Id, ObjCType::SEL, ObjCType::Block, ObjCType::Block]);
+        assert_eq!(ObjCType::Block.to_encoding(), "@?");
+    }
+
+    #[test]
+    fn test_method_encoding_drops_qualifiers() {
+        // - (oneway void)release with a const char * argument
+        let sig = parse_method_encoding("Vv24@0:8r*16").unwrap();
+        assert_eq!(sig.return_type, ObjCType::Void);
+        assert_eq!(sig.arg_types, vec![ObjCType::Id, ObjCType::SEL, ObjCType::CharPointer]);
+    }
+
+    #[test]
+    fn test_malformed_input_does_not_panic() {
+        for input in ["é", "{é", "^", "[", "[3", "[3]", "{CGRect={CGPoint=dd}", "(", "b", "@\"Unclosed", "{a=\"é", "ré"] {
+            let (_, consumed) = parse_type_encoding(input);
+            assert!(consumed > 0 && consumed <= input.len(), "{:?} consumed {}", input, consumed);
+            assert!(input.is_char_boundary(consumed));
+        }
+        for input in ["v16@0:8é", "é16", "{é}8@0:8", "[12345678901234567890i]"] {
+            let _ = parse_method_encoding(input);
         }
+        let deep = "^".repeat(10_000) + "v";
+        assert!(matches!(parse_type_encoding(&deep).0, ObjCType::Pointer(_)));
+    }
+
+    #[test]
+    fn test_array_arguments_decay_to_pointers() {
+        let (ty, _) = parse_type_encoding("[4i]");
+        assert_eq!(ty.to_rust_type(), "[i32; 4]");
+        assert_eq!(ty.to_rust_arg_type(), "*mut i32");
+        let (ty, _) = parse_type_encoding("[2[3d]]");
+        assert_eq!(ty.to_rust_arg_type(), "*mut [f64; 3]");
+        assert_eq!(ObjCType::Id.to_rust_arg_type(), "id");
     }
 
     #[test]
     fn test_struct_underscore_stripping() {
         // __CFString should strip to CFString
-        let ty = ObjCType::Struct("__CFString".to_string());
+        let ty = ObjCType::Struct("__CFString".to_string(), None);
         assert_eq!(ty.to_rust_type(), "c_void"); // CF* types map to c_void
 
         // _NSRange should strip to NSRange
-        let ty = ObjCType::Struct("_NSRange".to_string());
+        let ty = ObjCType::Struct("_NSRange".to_string(), None);
         assert_eq!(ty.to_rust_type(), "NSRange");
     }
 
@@ -272,21 +753,21 @@ mod tests {
     #[test]
     fn test_cf_types_map_to_c_void() {
         // All CF* types should map to c_void
-        assert_eq!(ObjCType::Struct("CFString".to_string()).to_rust_type(), "c_void");
-        assert_eq!(ObjCType::Struct("CGPoint".to_string()).to_rust_type(), "c_void");
-        assert_eq!(ObjCType::Struct("CFDictionary".to_string()).to_rust_type(), "c_void");
+        assert_eq!(ObjCType::Struct("CFString".to_string(), None).to_rust_type(), "c_void");
+        assert_eq!(ObjCType::Struct("CGPoint".to_string(), None).to_rust_type(), "c_void");
+        assert_eq!(ObjCType::Struct("CFDictionary".to_string(), None).to_rust_type(), "c_void");
     }
 
     #[test]
     fn test_security_types_map_to_c_void() {
         // Security framework types
-        assert_eq!(ObjCType::Struct("SecKey".to_string()).to_rust_type(), "c_void");
-        assert_eq!(ObjCType::Struct("SecTrust".to_string()).to_rust_type(), "c_void");
+        assert_eq!(ObjCType::Struct("SecKey".to_string(), None).to_rust_type(), "c_void");
+        assert_eq!(ObjCType::Struct("SecTrust".to_string(), None).to_rust_type(), "c_void");
     }
 
     #[test]
     fn test_unknown_struct_becomes_identifier() {
         // Unknown custom structs pass through
-        assert_eq!(ObjCType::Struct("MyCustomStruct".to_string()).to_rust_type(), "MyCustomStruct");
+        assert_eq!(ObjCType::Struct("MyCustomStruct".to_string(), None).to_rust_type(), "MyCustomStruct");
     }
 }
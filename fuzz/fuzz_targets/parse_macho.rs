@@ -0,0 +1,29 @@
+//! Fuzz the Mach-O reader
+//!
+//! Run with `cargo fuzz run parse_macho` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/macho.rs"]
+mod macho;
+
+use macho::{CPU_TYPE_ARM64, MachO};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = macho::fat_arches(data);
+    let Some(slice) = macho::slice(data, CPU_TYPE_ARM64) else {
+        return;
+    };
+
+    // The same bytes read as a file and as a mapped image
+    for image in [MachO::parse(slice), MachO::parse_loaded(slice)].into_iter().flatten() {
+        assert!(image.load_commands().count() <= image.header.ncmds as usize);
+        let _ = image.symbols();
+        let _ = image.indirect_symbols();
+        let _ = image.exports();
+        let _ = image.bindings();
+    }
+});
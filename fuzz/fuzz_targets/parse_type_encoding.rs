@@ -0,0 +1,41 @@
+//! Fuzz the build script's type encoding parser
+//!
+//! Run with `cargo fuzz run parse_type_encoding` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../frameworks/type_encoding.rs"]
+mod type_encoding;
+
+use type_encoding::{ObjCType, parse_method_encoding, parse_type_encoding};
+
+/// Whether the parser gave up somewhere inside `ty`
+fn contains_unknown(ty: &ObjCType) -> bool {
+    match ty {
+        ObjCType::Unknown(_) => true,
+        ObjCType::Pointer(inner) | ObjCType::Array(_, inner) | ObjCType::Qualified(_, inner) => contains_unknown(inner),
+        ObjCType::Struct(_, Some(fields)) | ObjCType::Union(_, Some(fields)) => fields.iter().any(contains_unknown),
+        _ => false,
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let (ty, consumed) = parse_type_encoding(input);
+    assert!(consumed <= input.len() && input.is_char_boundary(consumed));
+    assert!(consumed > 0 || input.is_empty());
+
+    // Anything that parsed cleanly must survive a round trip through its canonical encoding
+    if !contains_unknown(&ty) {
+        let encoding = ty.to_encoding();
+        assert_eq!(parse_type_encoding(&encoding), (ty, encoding.len()));
+    }
+
+    let _ = parse_method_encoding(input);
+});
@@ -0,0 +1,217 @@
+//! `#[derive(Encode)]`
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Ident, LitStr, Type, WherePredicate, parse_quote};
+
+/// Layout from the `#[repr]` attributes
+enum Repr {
+    C,
+    Transparent,
+    Int(Ident),
+}
+
+const INT_REPRS: &[&str] = &["i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize"];
+
+pub(crate) fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let ident = &input.ident;
+    let name = objc_name(input)?.unwrap_or_else(|| ident.to_string());
+    let repr = repr(input)?;
+    let mut field_types = field_types(input);
+    // A transparent struct can have zero-sized `PhantomData` fields beside its one real field
+    if matches!(repr, Some(Repr::Transparent)) {
+        field_types.retain(|ty| !is_phantom_data(ty));
+    }
+
+    let encoding = match (&input.data, repr) {
+        (Data::Struct(_), Some(Repr::C)) => {
+            quote! { ::ios_sys::encode::Encoding::Struct(#name, &[#(<#field_types as ::ios_sys::encode::Encode>::ENCODING),*]) }
+        }
+        (Data::Union(_), Some(Repr::C)) => {
+            quote! { ::ios_sys::encode::Encoding::Union(#name, &[#(<#field_types as ::ios_sys::encode::Encode>::ENCODING),*]) }
+        }
+        (Data::Struct(_), Some(Repr::Transparent)) => match field_types.as_slice() {
+            [field] => quote! { <#field as ::ios_sys::encode::Encode>::ENCODING },
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    "derive(Encode) on a #[repr(transparent)] struct needs exactly one field besides PhantomData",
+                ));
+            }
+        },
+        (Data::Enum(data), repr) => {
+            if let Some(variant) = data.variants.iter().find(|v| !matches!(v.fields, Fields::Unit)) {
+                return Err(syn::Error::new_spanned(variant, "derive(Encode) only supports fieldless enums"));
+            }
+            // A #[repr(C)] enum has the size of a C `int`
+            let int = match repr {
+                Some(Repr::Int(int)) => int,
+                Some(Repr::C) => Ident::new("i32", ident.span()),
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        ident,
+                        "derive(Encode) on an enum requires an integer #[repr] or #[repr(C)]",
+                    ));
+                }
+            };
+            quote! { <#int as ::ios_sys::encode::Encode>::ENCODING }
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "derive(Encode) requires #[repr(C)] or #[repr(transparent)]",
+            ));
+        }
+    };
+
+    let mut generics = input.generics.clone();
+    let bounds: Vec<WherePredicate> = field_types
+        .iter()
+        .map(|ty| parse_quote! { #ty: ::ios_sys::encode::Encode })
+        .collect();
+    generics.make_where_clause().predicates.extend(bounds);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        unsafe impl #impl_generics ::ios_sys::encode::Encode for #ident #ty_generics #where_clause {
+            const ENCODING: ::ios_sys::encode::Encoding = #encoding;
+        }
+
+        unsafe impl #impl_generics ::ios_sys::encode::RefEncode for #ident #ty_generics #where_clause {
+            const ENCODING_REF: ::ios_sys::encode::Encoding =
+                ::ios_sys::encode::Encoding::Pointer(&<Self as ::ios_sys::encode::Encode>::ENCODING);
+        }
+    })
+}
+
+fn repr(input: &DeriveInput) -> syn::Result<Option<Repr>> {
+    let mut repr = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("transparent") {
+                repr = Some(Repr::Transparent);
+            } else if meta.path.is_ident("C") {
+                // `repr(C, u8)` keeps the integer
+                if repr.is_none() {
+                    repr = Some(Repr::C);
+                }
+            } else if let Some(int) = meta.path.get_ident().filter(|i| INT_REPRS.contains(&i.to_string().as_str())) {
+                repr = Some(Repr::Int(int.clone()));
+            } else if meta.input.peek(syn::token::Paren) {
+                // `align(8)` and `packed(2)` don't change the encoding
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<TokenStream>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr)
+}
+
+/// The `name` from `#[encoding(name = "...")]`
+fn objc_name(input: &DeriveInput) -> syn::Result<Option<String>> {
+    let mut name = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("encoding")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unknown encoding attribute, expected `name`"))
+            }
+        })?;
+    }
+    Ok(name)
+}
+
+fn field_types(input: &DeriveInput) -> Vec<&Type> {
+    match &input.data {
+        Data::Struct(data) => data.fields.iter().map(|f| &f.ty).collect(),
+        Data::Union(data) => data.fields.named.iter().map(|f| &f.ty).collect(),
+        Data::Enum(_) => Vec::new(),
+    }
+}
+
+/// Whether `ty` is `PhantomData<_>`, by any path
+fn is_phantom_data(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path.qself.is_none() && path.path.segments.last().is_some_and(|s| s.ident == "PhantomData"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pretty(input: DeriveInput) -> String {
+        prettyplease::unparse(&syn::parse2(derive(&input).unwrap()).unwrap())
+    }
+
+    fn expand(input: DeriveInput) -> String {
+        derive(&input).unwrap().to_string()
+    }
+
+    fn error(input: DeriveInput) -> String {
+        derive(&input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_struct() {
+        let output = expand(parse_quote! {
+            #[repr(C)]
+            #[encoding(name = "_NSRange")]
+            struct NSRange {
+                location: usize,
+                length: usize,
+            }
+        });
+        assert!(output.contains("Encoding :: Struct (\"_NSRange\" , & [< usize as :: ios_sys :: encode :: Encode > :: ENCODING"));
+        assert!(output.contains("RefEncode for NSRange"));
+    }
+
+    #[test]
+    fn test_generic_struct_bounds() {
+        let output = expand(parse_quote! {
+            #[repr(C, align(16))]
+            struct Pair<T> {
+                a: T,
+                b: T,
+            }
+        });
+        assert!(output.contains("Encode for Pair < T > where T : :: ios_sys :: encode :: Encode"));
+        assert!(output.contains("Encoding :: Struct (\"Pair\""));
+    }
+
+    #[test]
+    fn test_transparent_and_enum() {
+        let output = expand(parse_quote! {
+            #[repr(transparent)]
+            struct Handle(*mut u8);
+        });
+        assert!(output.contains("const ENCODING : :: ios_sys :: encode :: Encoding = < * mut u8 as :: ios_sys :: encode :: Encode > :: ENCODING"));
+
+        let output = expand(parse_quote! {
+            #[repr(u8)]
+            enum Style { Plain, Grouped }
+        });
+        assert!(output.contains("< u8 as :: ios_sys :: encode :: Encode > :: ENCODING"));
+    }
+
+    #[test]
+    fn test_transparent_with_phantom_data() {
+        insta::assert_snapshot!(pretty(parse_quote! {
+            #[repr(transparent)]
+            struct Id<T>(*mut objc_object, PhantomData<T>);
+        }));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(error(parse_quote! { struct Point { x: f64 } }).contains("requires #[repr(C)]"));
+        assert!(error(parse_quote! { #[repr(C)] enum Value { Int(i32) } }).contains("fieldless"));
+        assert!(error(parse_quote! { #[repr(transparent)] struct Empty; }).contains("exactly one field"));
+        assert!(error(parse_quote! { #[repr(C)] #[encoding(title = "x")] struct S; }).contains("expected `name`"));
+    }
+}
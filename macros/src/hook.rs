@@ -0,0 +1,171 @@
+//! `#[hook]`
+
+use std::ffi::CString;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{FnArg, ItemFn, LitCStr, LitStr, Pat, Type, meta::ParseNestedMeta};
+
+/// The arguments of `#[hook(class = "...", sel = "...", class_method)]`
+#[derive(Default)]
+pub(crate) struct HookArgs {
+    class: Option<LitStr>,
+    sel: Option<LitStr>,
+    class_method: bool,
+}
+
+impl HookArgs {
+    pub(crate) fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("class") {
+            self.class = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("sel") {
+            self.sel = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("class_method") {
+            self.class_method = true;
+        } else {
+            return Err(meta.error("unknown hook attribute, expected `class`, `sel` or `class_method`"));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn expand(args: HookArgs, item: ItemFn) -> syn::Result<TokenStream> {
+    let (Some(class), Some(sel)) = (&args.class, &args.sel) else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "#[hook] needs `class = \"...\"` and `sel = \"...\"`",
+        ));
+    };
+    let class = c_str(class)?;
+    let sel = c_str(sel)?;
+    let class_method = args.class_method;
+
+    let sig = &item.sig;
+    if let Some(generics) = sig.generics.lt_token {
+        return Err(syn::Error::new_spanned(generics, "a hook can't be generic"));
+    }
+    if let Some(asyncness) = sig.asyncness {
+        return Err(syn::Error::new_spanned(asyncness, "a hook can't be async"));
+    }
+    if let Some(variadic) = &sig.variadic {
+        return Err(syn::Error::new_spanned(variadic, "a hook can't be variadic"));
+    }
+
+    let mut patterns: Vec<&Pat> = Vec::new();
+    let mut types: Vec<&Type> = Vec::new();
+    for input in &sig.inputs {
+        match input {
+            FnArg::Typed(arg) => {
+                patterns.push(&arg.pat);
+                types.push(&arg.ty);
+            }
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(receiver, "a hook takes the receiver as an argument, not `self`"));
+            }
+        }
+    }
+    if types.len() < 2 {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            "a hook takes the receiver and the selector before the method's arguments",
+        ));
+    }
+
+    let attrs = &item.attrs;
+    let vis = &item.vis;
+    let name = &sig.ident;
+    let output = &sig.output;
+    let stmts = &item.block.stmts;
+    let module_doc = format!("The original implementation of [`{}`] and its installer", name);
+    let args: Vec<_> = (0..types.len()).map(|i| format_ident!("__hook_arg{}", i)).collect();
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis unsafe extern "C" fn #name(#(#args: #types),*) #output {
+            #[allow(unused_macros)]
+            macro_rules! orig {
+                () => {
+                    unsafe { (#name::HOOK.original())(#(#args),*) }
+                };
+                ($($arg:expr),+ $(,)?) => {
+                    unsafe { (#name::HOOK.original())($($arg),+) }
+                };
+            }
+            #(let #patterns: #types = #args;)*
+            #(#stmts)*
+        }
+
+        #[doc = #module_doc]
+        #vis mod #name {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub static HOOK: ::ios_sys::hook::HookCell<unsafe extern "C" fn(#(#types),*) #output> =
+                ::ios_sys::hook::HookCell::new(#class, #sel, #class_method);
+
+            /// Install the hook, unless it is already installed
+            ///
+            /// # Safety
+            ///
+            /// The hook must be safe to call wherever the method is, from any thread.
+            pub unsafe fn install() -> ::core::result::Result<(), ::ios_sys::hook::HookError> {
+                unsafe { HOOK.install(super::#name) }
+            }
+        }
+    })
+}
+
+fn c_str(lit: &LitStr) -> syn::Result<LitCStr> {
+    let value = CString::new(lit.value()).map_err(|_| syn::Error::new_spanned(lit, "name contains a nul byte"))?;
+    Ok(LitCStr::new(&value, lit.span()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn args(class: &str, sel: &str, class_method: bool) -> HookArgs {
+        HookArgs {
+            class: Some(LitStr::new(class, Span::call_site())),
+            sel: Some(LitStr::new(sel, Span::call_site())),
+            class_method,
+        }
+    }
+
+    fn pretty(tokens: TokenStream) -> String {
+        prettyplease::unparse(&syn::parse2(tokens).unwrap())
+    }
+
+    #[test]
+    fn test_instance_method() {
+        let item = parse_quote! {
+            /// Never highlight icons
+            fn set_highlighted(this: id, _cmd: SEL, highlighted: bool) {
+                let _ = highlighted;
+                orig!(this, _cmd, false);
+            }
+        };
+        insta::assert_snapshot!(pretty(expand(args("SBIconView", "setHighlighted:", false), item).unwrap()));
+    }
+
+    #[test]
+    fn test_class_method() {
+        let item = parse_quote! {
+            pub(crate) fn shared_application(class: Class, _: SEL) -> id {
+                orig!()
+            }
+        };
+        insta::assert_snapshot!(pretty(expand(args("UIApplication", "sharedApplication", true), item).unwrap()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |args: HookArgs, item: ItemFn| expand(args, item).unwrap_err().to_string();
+
+        assert!(error(HookArgs::default(), parse_quote! { fn f(a: id, b: SEL) {} }).contains("needs `class"));
+        assert!(error(args("A", "b", false), parse_quote! { fn f(a: id) {} }).contains("receiver and the selector"));
+        assert!(error(args("A", "b", false), parse_quote! { fn f<T>(a: id, b: SEL) {} }).contains("generic"));
+        assert!(error(args("A\0", "b", false), parse_quote! { fn f(a: id, b: SEL) {} }).contains("nul byte"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Procedural macros for `ios-sys`
+//!
+//! Use them through the re-exports in `ios-sys` (e.g.
+//! `ios_sys::encode::Encode`); the expansions refer to `::ios_sys`.
+
+use proc_macro::TokenStream;
+use syn::{DeriveInput, ItemFn, parse_macro_input};
+
+mod encode;
+mod hook;
+
+/// Derive `ios_sys::encode::Encode` and `RefEncode`
+///
+/// `#[repr(C)]` structs and unions encode as `{Name=fields}` and
+/// `(Name=fields)`, `#[repr(transparent)]` structs as their field and enums
+/// as their integer `repr`. The Objective-C name defaults to the Rust name
+/// and can be overridden with `#[encoding(name = "_NSRange")]`.
+#[proc_macro_derive(Encode, attributes(encoding))]
+pub fn derive_encode(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    encode::derive(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Replace an Objective-C method with the annotated fn
+///
+/// `#[hook(class = "SBIconView", sel = "setHighlighted:")]` on a fn taking
+/// the receiver, the selector and the method's arguments makes it an
+/// `unsafe extern "C"` fn and adds a module of the same name with the
+/// `HookCell` holding the original implementation and an `install()` fn,
+/// which checks the signature against the method's type encoding. Add
+/// `class_method` to hook a class method.
+///
+/// Inside the fn, `orig!()` calls the original implementation with the
+/// hook's arguments and `orig!(this, _cmd, ...)` with other ones.
+#[proc_macro_attribute]
+pub fn hook(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut hook_args = hook::HookArgs::default();
+    let parser = syn::meta::parser(|meta| hook_args.parse(meta));
+    parse_macro_input!(args with parser);
+    let item = parse_macro_input!(item as ItemFn);
+    hook::expand(hook_args, item).unwrap_or_else(syn::Error::into_compile_error).into()
+}
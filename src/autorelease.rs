@@ -0,0 +1,98 @@
+//! Autorelease pool scopes
+//!
+//! Methods outside the `alloc`/`new`/`copy`/`init` families (e.g.
+//! `stringWithUTF8String:`) return autoreleased objects, which are only freed
+//! when the innermost autorelease pool is drained. Threads without a pool of
+//! their own, like the main loop of a daemon, never drain them.
+//!
+//! [`autoreleasepool`] is the equivalent of `@autoreleasepool { ... }`:
+//!
+//! ```ignore
+//! for path in paths {
+//!     ios_sys::autorelease::autoreleasepool(|_| unsafe { process(path) });
+//! }
+//! ```
+//!
+//! Pools must be popped in the reverse order they were pushed. Debug builds
+//! track the pools of each thread and panic when one is popped out of order.
+
+use core::ffi::c_void;
+use core::marker::PhantomData;
+
+use crate::objc::{objc_autoreleasePoolPop, objc_autoreleasePoolPush};
+
+/// A pushed autorelease pool, popped (and drained) when dropped
+///
+/// Bound to the thread that pushed it.
+pub struct AutoreleasePool {
+    context: *mut c_void,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl AutoreleasePool {
+    /// Push a new innermost pool on the current thread
+    ///
+    /// # Safety
+    ///
+    /// The pool must be dropped before any pool pushed earlier on this
+    /// thread, and must not be leaked. Prefer [`autoreleasepool`], which
+    /// guarantees both.
+    pub unsafe fn new() -> Self {
+        let context = unsafe { objc_autoreleasePoolPush() };
+        #[cfg(debug_assertions)]
+        debug::push(context);
+        Self {
+            context,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Drop for AutoreleasePool {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        debug::pop(self.context);
+        unsafe { objc_autoreleasePoolPop(self.context) }
+    }
+}
+
+/// Run `f` inside a new autorelease pool, draining it afterwards
+///
+/// Objects autoreleased inside `f` are released when it returns, so anything
+/// that has to outlive the pool must be retained (e.g. held as a
+/// [`Retained`](crate::rc::Retained)).
+pub fn autoreleasepool<R>(f: impl FnOnce(&AutoreleasePool) -> R) -> R {
+    // The pool lives on this stack frame, so it is popped before any pool the caller is inside
+    let pool = unsafe { AutoreleasePool::new() };
+    f(&pool)
+}
+
+/// LIFO bookkeeping of each thread's pools in debug builds
+#[cfg(debug_assertions)]
+mod debug {
+    use core::ffi::c_void;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static POOLS: RefCell<Vec<*mut c_void>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub(super) fn push(context: *mut c_void) {
+        POOLS.with_borrow_mut(|pools| pools.push(context));
+    }
+
+    pub(super) fn pop(context: *mut c_void) {
+        POOLS.with_borrow_mut(|pools| match pools.last() {
+            Some(&innermost) if innermost == context => {
+                pools.pop();
+            }
+            Some(&innermost) => panic!(
+                "autorelease pool {:p} popped out of order: innermost pool is {:p} ({} pools pushed on this thread)",
+                context,
+                innermost,
+                pools.len()
+            ),
+            None => panic!("autorelease pool {:p} popped, but no pool is pushed on this thread", context),
+        });
+    }
+}
@@ -0,0 +1,415 @@
+//! Objective-C blocks
+//!
+//! Implements the block ABI (`Block_private.h`) so Rust closures can be passed
+//! to APIs taking completion handlers, and blocks received from Objective-C
+//! can be called.
+//!
+//! [`RcBlock::new`] turns a closure into a heap block:
+//!
+//! ```ignore
+//! let handler = RcBlock::new(move |finished: bool| println!("done: {finished}"));
+//! let _: () = unsafe { msg_send![view, setHidden: true, completion: &*handler] };
+//! ```
+//!
+//! Closures without captures can be static global blocks, see
+//! [`global_block!`](crate::global_block). Blocks received as arguments are
+//! `&Block<(A, ...), R>` and can be called with [`Block::call`].
+
+use core::ffi::{CStr, c_char, c_ulong, c_void};
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::{Mutex, OnceLock};
+
+use crate::encode::{Encode, Encoding, RefEncode};
+
+const BLOCK_HAS_COPY_DISPOSE: i32 = 1 << 25;
+const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+const BLOCK_HAS_SIGNATURE: i32 = 1 << 30;
+
+// Block runtime (libsystem_blocks, re-exported by libSystem)
+#[cfg_attr(feature = "runtime", link(name = "System", kind = "dylib"))]
+unsafe extern "C" {
+    /// Class of blocks on the stack, copied to the heap by `_Block_copy`
+    pub static _NSConcreteStackBlock: [*const c_void; 32];
+    /// Class of blocks without captures, stored in static memory
+    pub static _NSConcreteGlobalBlock: [*const c_void; 32];
+
+    pub fn _Block_copy(block: *const c_void) -> *mut c_void;
+    pub fn _Block_release(block: *const c_void);
+}
+
+/// Fields every block literal starts with
+#[repr(C)]
+struct BlockHeader {
+    isa: *const c_void,
+    flags: i32,
+    reserved: i32,
+    invoke: Option<unsafe extern "C" fn()>,
+    descriptor: *const BlockDescriptor,
+}
+
+/// Descriptor of blocks without copy/dispose helpers or signature
+#[doc(hidden)]
+#[repr(C)]
+pub struct BlockDescriptor {
+    reserved: c_ulong,
+    size: c_ulong,
+}
+
+/// Descriptor of blocks with `BLOCK_HAS_COPY_DISPOSE | BLOCK_HAS_SIGNATURE`
+#[repr(C)]
+struct BlockDescriptorCopyDispose {
+    reserved: c_ulong,
+    size: c_ulong,
+    copy: unsafe extern "C" fn(*mut c_void, *const c_void),
+    dispose: unsafe extern "C" fn(*mut c_void),
+    signature: *const c_char,
+}
+
+/// A block taking the arguments `A` (a tuple) and returning `R`
+///
+/// Only exists behind references and pointers: `&Block<(id, bool), ()>` is
+/// a `void (^)(id, BOOL)`.
+#[repr(C)]
+pub struct Block<A, R> {
+    header: BlockHeader,
+    _marker: PhantomData<fn(A) -> R>,
+}
+
+unsafe impl<A: BlockArguments, R: Encode> RefEncode for Block<A, R> {
+    const ENCODING_REF: Encoding = Encoding::Block;
+}
+
+impl<A: BlockArguments, R: Encode> Block<A, R> {
+    /// View a block pointer received from Objective-C
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a block of this signature that outlives `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *mut c_void) -> Option<&'a Self> {
+        unsafe { ptr.cast::<Self>().as_ref() }
+    }
+
+    /// The block as an object pointer
+    pub fn as_ptr(&self) -> *mut c_void {
+        ptr::from_ref(self).cast_mut().cast()
+    }
+
+    /// Call the block
+    ///
+    /// Debug builds check `A` and `R` against the block's signature, if it
+    /// has one, and panic on a mismatch.
+    ///
+    /// # Safety
+    ///
+    /// The block must actually have this signature.
+    pub unsafe fn call(&self, args: A) -> R {
+        #[cfg(debug_assertions)]
+        self.verify_signature();
+        unsafe { args.call_block(self.as_ptr()) }
+    }
+
+    /// Copy the block to the heap, or retain it if it already is there
+    pub fn copy(&self) -> RcBlock<A, R> {
+        let copy = unsafe { _Block_copy(self.as_ptr()) };
+        RcBlock {
+            ptr: NonNull::new(copy.cast()).expect("_Block_copy returned NULL"),
+        }
+    }
+
+    /// The block's type encoding, e.g. `v12@?0B8`
+    pub fn signature(&self) -> Option<&CStr> {
+        let flags = self.header.flags;
+        if flags & BLOCK_HAS_SIGNATURE == 0 || self.header.descriptor.is_null() {
+            return None;
+        }
+        unsafe {
+            // The signature follows the copy/dispose helpers if there are any
+            let mut field = self.header.descriptor.cast::<u8>().add(mem::size_of::<BlockDescriptor>());
+            if flags & BLOCK_HAS_COPY_DISPOSE != 0 {
+                field = field.add(2 * mem::size_of::<usize>());
+            }
+            let signature = *field.cast::<*const c_char>();
+            (!signature.is_null()).then(|| CStr::from_ptr(signature))
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn verify_signature(&self) {
+        use crate::encode::{encodings_match, split_method_encoding};
+
+        let Some(signature) = self.signature() else {
+            return;
+        };
+        let actual = split_method_encoding(&signature.to_string_lossy());
+        let expected = expected_signature::<A, R>();
+        let expected = split_method_encoding(&expected);
+        let matches = expected.len() == actual.len() && expected.iter().zip(&actual).all(|(e, a)| encodings_match(e, a));
+        assert!(
+            matches,
+            "block signature mismatch: called as {} but the block is {}",
+            expected.concat(),
+            actual.concat()
+        );
+    }
+}
+
+/// `R @? A...`, the signature of a block as `_Block_signature` reports it, without offsets
+fn expected_signature<A: BlockArguments, R: Encode>() -> String {
+    let mut signature = format!("{}@?", R::ENCODING);
+    for arg in A::ENCODINGS {
+        signature.push_str(&arg.to_string());
+    }
+    signature
+}
+
+/// An owned reference to a heap block
+pub struct RcBlock<A, R> {
+    ptr: NonNull<Block<A, R>>,
+}
+
+/// A block literal with its captured closure
+#[repr(C)]
+struct BlockLiteral<F> {
+    header: BlockHeader,
+    closure: F,
+}
+
+impl<A: BlockArguments, R: Encode> RcBlock<A, R> {
+    /// Turn `closure` into a heap block
+    ///
+    /// The closure is dropped when the last copy of the block is released.
+    pub fn new<F: IntoBlock<A, R>>(closure: F) -> Self {
+        // Build a stack literal and let the runtime move it to the heap
+        let literal = ManuallyDrop::new(BlockLiteral {
+            header: BlockHeader {
+                isa: (&raw const _NSConcreteStackBlock).cast(),
+                flags: BLOCK_HAS_COPY_DISPOSE | BLOCK_HAS_SIGNATURE,
+                reserved: 0,
+                invoke: Some(F::invoke()),
+                descriptor: ptr::from_ref(descriptor::<F, A, R>()).cast(),
+            },
+            closure,
+        });
+        let copy = unsafe { _Block_copy(ptr::from_ref(&*literal).cast()) };
+        Self {
+            ptr: NonNull::new(copy.cast()).expect("_Block_copy returned NULL"),
+        }
+    }
+
+    /// Take over a +1 block reference, e.g. from `_Block_copy`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a heap or global block of this signature.
+    pub unsafe fn from_raw(ptr: *mut c_void) -> Option<Self> {
+        NonNull::new(ptr.cast()).map(|ptr| Self { ptr })
+    }
+
+    /// Give up ownership without releasing
+    pub fn into_raw(this: Self) -> *mut c_void {
+        ManuallyDrop::new(this).ptr.as_ptr().cast()
+    }
+}
+
+/// The descriptor shared by all blocks made from closures of type `F`
+fn descriptor<F: IntoBlock<A, R>, A: BlockArguments, R: Encode>() -> &'static BlockDescriptorCopyDispose {
+    static DESCRIPTORS: OnceLock<Mutex<HashMap<TypeId, usize>>> = OnceLock::new();
+
+    let mut descriptors = DESCRIPTORS.get_or_init(Default::default).lock().unwrap();
+    let descriptor = *descriptors.entry(TypeId::of::<F>()).or_insert_with(|| {
+        let signature = CString::new(expected_signature::<A, R>()).expect("encodings never contain nul bytes");
+        // Leaked once per closure type; blocks can outlive any scope
+        let descriptor = Box::leak(Box::new(BlockDescriptorCopyDispose {
+            reserved: 0,
+            size: mem::size_of::<BlockLiteral<F>>() as c_ulong,
+            copy: copy_helper,
+            dispose: dispose_helper::<F>,
+            signature: signature.into_raw(),
+        }));
+        ptr::from_ref(descriptor) as usize
+    });
+    unsafe { &*(descriptor as *const BlockDescriptorCopyDispose) }
+}
+
+/// `_Block_copy` has already moved the closure bytes to the heap and the
+/// stack literal is never dropped, so there is nothing left to copy
+unsafe extern "C" fn copy_helper(_dst: *mut c_void, _src: *const c_void) {}
+
+/// Drop the closure of a heap block that is being freed
+unsafe extern "C" fn dispose_helper<F>(block: *mut c_void) {
+    unsafe { ptr::drop_in_place(&raw mut (*block.cast::<BlockLiteral<F>>()).closure) }
+}
+
+impl<A, R> Deref for RcBlock<A, R> {
+    type Target = Block<A, R>;
+
+    #[inline]
+    fn deref(&self) -> &Block<A, R> {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<A, R> Clone for RcBlock<A, R> {
+    #[inline]
+    fn clone(&self) -> Self {
+        // Copying a heap block only increments its reference count
+        unsafe { _Block_copy(self.ptr.as_ptr().cast()) };
+        Self { ptr: self.ptr }
+    }
+}
+
+impl<A, R> Drop for RcBlock<A, R> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { _Block_release(self.ptr.as_ptr().cast()) }
+    }
+}
+
+/// A block in static memory, made by [`global_block!`](crate::global_block)
+#[repr(C)]
+pub struct GlobalBlock<A, R> {
+    block: Block<A, R>,
+}
+
+// Global blocks have no captures and are never mutated by the runtime
+unsafe impl<A, R> Sync for GlobalBlock<A, R> {}
+
+impl<A, R> GlobalBlock<A, R> {
+    const DESCRIPTOR: BlockDescriptor = BlockDescriptor {
+        reserved: 0,
+        size: mem::size_of::<Self>() as c_ulong,
+    };
+
+    #[doc(hidden)]
+    pub const unsafe fn __new(isa: *const [*const c_void; 32], invoke: unsafe extern "C" fn()) -> Self {
+        Self {
+            block: Block {
+                header: BlockHeader {
+                    isa: isa.cast(),
+                    flags: BLOCK_IS_GLOBAL,
+                    reserved: 0,
+                    invoke: Some(invoke),
+                    descriptor: &Self::DESCRIPTOR,
+                },
+                _marker: PhantomData,
+            },
+        }
+    }
+}
+
+impl<A, R> Deref for GlobalBlock<A, R> {
+    type Target = Block<A, R>;
+
+    #[inline]
+    fn deref(&self) -> &Block<A, R> {
+        &self.block
+    }
+}
+
+/// Declare a static global block from a closure without captures
+///
+/// ```ignore
+/// global_block! {
+///     static LOG_FINISHED = |finished: bool| {
+///         println!("finished: {finished}");
+///     };
+/// }
+/// ```
+///
+/// Global blocks carry no signature, since it can't be built at compile time.
+#[macro_export]
+macro_rules! global_block {
+    ($(#[$meta:meta])* $vis:vis static $name:ident = |$($arg:ident : $ty:ty),* $(,)?| -> $ret:ty $body:block;) => {
+        $(#[$meta])*
+        $vis static $name: $crate::block::GlobalBlock<($($ty,)*), $ret> = {
+            unsafe extern "C" fn invoke(_block: *mut ::core::ffi::c_void $(, $arg: $ty)*) -> $ret $body
+
+            unsafe {
+                $crate::block::GlobalBlock::__new(
+                    &raw const $crate::block::_NSConcreteGlobalBlock,
+                    ::core::mem::transmute::<unsafe extern "C" fn(*mut ::core::ffi::c_void $(, $ty)*) -> $ret, unsafe extern "C" fn()>(invoke),
+                )
+            }
+        };
+    };
+    ($(#[$meta:meta])* $vis:vis static $name:ident = |$($arg:ident : $ty:ty),* $(,)?| $body:block;) => {
+        $crate::global_block!($(#[$meta])* $vis static $name = |$($arg: $ty),*| -> () $body;);
+    };
+}
+
+/// A tuple of block arguments
+///
+/// # Safety
+///
+/// `ENCODINGS` must match the tuple's element types.
+pub unsafe trait BlockArguments: Sized {
+    /// The encodings of the arguments, in order
+    const ENCODINGS: &'static [Encoding];
+
+    /// Call the block's invoke function with the arguments
+    ///
+    /// # Safety
+    ///
+    /// `block` must be a block taking these arguments and returning `R`.
+    unsafe fn call_block<R>(self, block: *mut c_void) -> R;
+}
+
+/// A closure that can become a block taking `A` and returning `R`
+pub trait IntoBlock<A, R>: 'static {
+    /// The invoke function of blocks holding this closure
+    fn invoke() -> unsafe extern "C" fn();
+}
+
+macro_rules! block_arguments {
+    ($($arg:ident),*) => {
+        unsafe impl<$($arg: Encode),*> BlockArguments for ($($arg,)*) {
+            const ENCODINGS: &'static [Encoding] = &[$($arg::ENCODING),*];
+
+            #[inline]
+            #[allow(non_snake_case)]
+            unsafe fn call_block<R>(self, block: *mut c_void) -> R {
+                type Invoke<$($arg,)* R> = unsafe extern "C" fn(*mut c_void $(, $arg)*) -> R;
+                let ($($arg,)*) = self;
+                unsafe {
+                    let invoke = (*block.cast::<BlockHeader>()).invoke.expect("block without invoke function");
+                    let invoke: Invoke<$($arg,)* R> = mem::transmute(invoke);
+                    invoke(block $(, $arg)*)
+                }
+            }
+        }
+
+        impl<Func, $($arg: Encode,)* R: Encode> IntoBlock<($($arg,)*), R> for Func
+        where
+            Func: Fn($($arg),*) -> R + 'static,
+        {
+            fn invoke() -> unsafe extern "C" fn() {
+                #[allow(non_snake_case)]
+                unsafe extern "C" fn invoke<Func: Fn($($arg),*) -> R, $($arg,)* R>(
+                    block: *mut BlockLiteral<Func>
+                    $(, $arg: $arg)*
+                ) -> R {
+                    unsafe { ((*block).closure)($($arg),*) }
+                }
+
+                let invoke: unsafe extern "C" fn(*mut BlockLiteral<Func> $(, $arg)*) -> R = invoke::<Func, $($arg,)* R>;
+                unsafe { mem::transmute::<_, unsafe extern "C" fn()>(invoke) }
+            }
+        }
+    };
+}
+
+block_arguments!();
+block_arguments!(A);
+block_arguments!(A, B);
+block_arguments!(A, B, C);
+block_arguments!(A, B, C, D);
+block_arguments!(A, B, C, D, E);
+block_arguments!(A, B, C, D, E, F);
+block_arguments!(A, B, C, D, E, F, G);
+block_arguments!(A, B, C, D, E, F, G, H);
@@ -0,0 +1,83 @@
+//! Lazily resolved selector and class caches
+//!
+//! Generated method wrappers keep one of these in a `static` per selector and
+//! per class, so `sel_registerName` and `objc_getClass` run once per process
+//! instead of on every call.
+
+use core::ffi::CStr;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::objc::{Class, SEL, objc_class, objc_getClass, objc_selector, sel_registerName};
+
+/// A selector that is registered on first use
+pub struct CachedSel {
+    name: &'static CStr,
+    sel: AtomicPtr<objc_selector>,
+}
+
+impl CachedSel {
+    /// Create a cache for the selector `name`, e.g. `c"initWithFrame:"`
+    pub const fn new(name: &'static CStr) -> Self {
+        Self {
+            name,
+            sel: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Get the selector, registering it on the first call
+    #[inline]
+    pub fn get(&self) -> SEL {
+        let sel = self.sel.load(Ordering::Acquire);
+        if !sel.is_null() {
+            return sel;
+        }
+        self.register()
+    }
+
+    #[cold]
+    fn register(&self) -> SEL {
+        // Racing threads all get the same selector back, so the last store wins harmlessly
+        let sel = unsafe { sel_registerName(self.name.as_ptr()) };
+        self.sel.store(sel, Ordering::Release);
+        sel
+    }
+}
+
+/// A class that is looked up on first use
+///
+/// A failed lookup (class not loaded yet) is not cached, so the next call
+/// tries again.
+pub struct CachedClass {
+    name: &'static CStr,
+    class: AtomicPtr<objc_class>,
+}
+
+impl CachedClass {
+    /// Create a cache for the class `name`, e.g. `c"NSString"`
+    pub const fn new(name: &'static CStr) -> Self {
+        Self {
+            name,
+            class: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Get the class, or null if no class with this name is loaded
+    #[inline]
+    pub fn get(&self) -> Class {
+        let class = self.class.load(Ordering::Acquire);
+        if !class.is_null() {
+            return class;
+        }
+        self.lookup()
+    }
+
+    #[cold]
+    fn lookup(&self) -> Class {
+        let class = unsafe { objc_getClass(self.name.as_ptr()) };
+        if !class.is_null() {
+            self.class.store(class, Ordering::Release);
+        }
+        class
+    }
+}
@@ -0,0 +1,94 @@
+//! Dumping the classes loaded in the process
+//!
+//! Produces the text the build script reads from `/tmp/all_objc_classes.txt`
+//! to generate the class modules, so bindings can be refreshed from a device
+//! without an external class-dump tool:
+//!
+//! ```ignore
+//! use ios_sys::class_dump;
+//!
+//! let classes = class_dump::dump_classes();
+//! let mut file = std::fs::File::create("/tmp/all_objc_classes.txt")?;
+//! class_dump::write_class_dump(&mut file, &classes)?;
+//! ```
+//!
+//! The `class_dump` example does the same from the command line.
+
+mod format;
+
+use core::ffi::CStr;
+use std::ffi::CString;
+use std::io;
+
+pub use format::{DumpedClass, DumpedIvar, DumpedMethod, DumpedProperty};
+
+use crate::introspect::{self, MethodInfo};
+use crate::objc::{Class, class_getImageName, objc_getClass};
+
+/// Record `class`: its superclass, image, ivars, properties and methods
+///
+/// # Safety
+///
+/// `class` must be a valid class.
+pub unsafe fn dump_class(class: Class) -> DumpedClass {
+    unsafe {
+        let image = class_getImageName(class);
+        DumpedClass {
+            name: introspect::class_name(class),
+            superclass: introspect::superclass(class).map(|superclass| introspect::class_name(superclass)),
+            image: (!image.is_null()).then(|| CStr::from_ptr(image).to_string_lossy().into_owned()),
+            ivars: introspect::ivars(class)
+                .map(|ivar| DumpedIvar {
+                    name: ivar.name,
+                    type_encoding: ivar.types.unwrap_or_default(),
+                    offset: ivar.offset,
+                })
+                .collect(),
+            properties: introspect::properties(class)
+                .map(|property| DumpedProperty {
+                    name: property.name,
+                    attributes: property.attributes,
+                })
+                .collect(),
+            class_methods: introspect::class_methods(class).map(dumped_method).collect(),
+            methods: introspect::methods(class).map(dumped_method).collect(),
+        }
+    }
+}
+
+fn dumped_method(method: MethodInfo) -> DumpedMethod {
+    DumpedMethod {
+        name: method.name,
+        type_encoding: method.types.unwrap_or_default(),
+    }
+}
+
+/// Record every registered class, sorted by name
+pub fn dump_classes() -> Vec<DumpedClass> {
+    let mut classes: Vec<_> = introspect::classes().map(|class| unsafe { dump_class(class) }).collect();
+    classes.sort_by(|a, b| a.name.cmp(&b.name));
+    classes
+}
+
+/// Record the classes defined in the image at `image`, sorted by name
+///
+/// `image` is a path from [`introspect::image_names`].
+pub fn dump_image(image: &CStr) -> Vec<DumpedClass> {
+    let mut classes: Vec<_> = introspect::class_names_for_image(image)
+        .filter_map(|name| {
+            let name = CString::new(name).ok()?;
+            let class = unsafe { objc_getClass(name.as_ptr()) };
+            (!class.is_null()).then(|| unsafe { dump_class(class) })
+        })
+        .collect();
+    classes.sort_by(|a, b| a.name.cmp(&b.name));
+    classes
+}
+
+/// Write `classes` in the format `parse_class_dump` reads
+pub fn write_class_dump(out: &mut impl io::Write, classes: &[DumpedClass]) -> io::Result<()> {
+    for class in classes {
+        write!(out, "{}", class)?;
+    }
+    Ok(())
+}
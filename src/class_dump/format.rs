@@ -0,0 +1,93 @@
+//! The class dump text format
+//!
+//! Also compiled into the build script's tests, which feed it to
+//! `parse_class_dump`, so it only depends on `std`:
+//!
+//! ```text
+//! @interface UIView
+//!   Superclass: UIResponder
+//!   Image: /System/Library/PrivateFrameworks/UIKitCore.framework/UIKitCore
+//!   Ivars (1):
+//!     _layer [@"CALayer"] 40
+//!   Properties (1):
+//!     @property frame [T{CGRect={CGPoint=dd}{CGSize=dd}},N]
+//!   Class Methods (1):
+//!     + layerClass [#16@0:8]
+//!   Methods (1):
+//!     - initWithFrame: [@48@0:8{CGRect={CGPoint=dd}{CGSize=dd}}16]
+//! @end
+//! ```
+
+use std::fmt;
+
+/// A class as recorded in a dump
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpedClass {
+    pub name: String,
+    pub superclass: Option<String>,
+    /// Path of the image the class was loaded from
+    pub image: Option<String>,
+    pub ivars: Vec<DumpedIvar>,
+    pub properties: Vec<DumpedProperty>,
+    pub class_methods: Vec<DumpedMethod>,
+    pub methods: Vec<DumpedMethod>,
+}
+
+/// A method: selector and type encoding
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpedMethod {
+    pub name: String,
+    pub type_encoding: String,
+}
+
+/// A property: name and attribute string
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpedProperty {
+    pub name: String,
+    pub attributes: String,
+}
+
+/// An instance variable: name, type encoding and offset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DumpedIvar {
+    pub name: String,
+    pub type_encoding: String,
+    pub offset: isize,
+}
+
+impl fmt::Display for DumpedClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "@interface {}", self.name)?;
+        if let Some(superclass) = &self.superclass {
+            writeln!(f, "  Superclass: {}", superclass)?;
+        }
+        if let Some(image) = &self.image {
+            writeln!(f, "  Image: {}", image)?;
+        }
+        if !self.ivars.is_empty() {
+            writeln!(f, "  Ivars ({}):", self.ivars.len())?;
+            for ivar in &self.ivars {
+                writeln!(f, "    {} [{}] {}", ivar.name, ivar.type_encoding, ivar.offset)?;
+            }
+        }
+        if !self.properties.is_empty() {
+            writeln!(f, "  Properties ({}):", self.properties.len())?;
+            for property in &self.properties {
+                writeln!(f, "    @property {} [{}]", property.name, property.attributes)?;
+            }
+        }
+        if !self.class_methods.is_empty() {
+            writeln!(f, "  Class Methods ({}):", self.class_methods.len())?;
+            for method in &self.class_methods {
+                writeln!(f, "    + {} [{}]", method.name, method.type_encoding)?;
+            }
+        }
+        if !self.methods.is_empty() {
+            writeln!(f, "  Methods ({}):", self.methods.len())?;
+            for method in &self.methods {
+                writeln!(f, "    - {} [{}]", method.name, method.type_encoding)?;
+            }
+        }
+        writeln!(f, "@end")
+    }
+}
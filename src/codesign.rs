@@ -0,0 +1,611 @@
+//! Ad-hoc code signatures, like `ldid -S`
+//!
+//! Tweaks and tools have to be signed before iOS loads them. This writes
+//! the signature a jailbroken device accepts without a certificate: a
+//! `CodeDirectory` of SHA-256 page hashes, an empty requirements set, and
+//! optionally entitlements, in a `SuperBlob` at the end of `__LINKEDIT` that
+//! `LC_CODE_SIGNATURE` points to. Existing signatures are replaced.
+//! Entitlements are written both as the plist and in the DER encoding iOS 15
+//! checks executables against.
+//!
+//! Enabled by the `codesign` feature, for build helpers that run on the host
+//! after linking. Also compiled into the build script's tests with the
+//! feature on, so it only depends on `std`, `sha2` and [`macho`](super::macho).
+//!
+//! ```ignore
+//! let entitlements = std::fs::read("entitlements.plist")?;
+//! codesign::sign_file(Path::new("target/aarch64-apple-ios/release/libtweak.dylib"), None, Some(&entitlements))?;
+//! ```
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use super::macho::{LC_CODE_SIGNATURE, LC_SEGMENT_64, MH_MAGIC_64, c_str, read_u32, read_u64};
+
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSMAGIC_REQUIREMENTS: u32 = 0xfade_0c01;
+const CSMAGIC_EMBEDDED_ENTITLEMENTS: u32 = 0xfade_7171;
+const CSMAGIC_EMBEDDED_DER_ENTITLEMENTS: u32 = 0xfade_7172;
+
+const CSSLOT_CODEDIRECTORY: u32 = 0;
+const CSSLOT_REQUIREMENTS: u32 = 2;
+const CSSLOT_ENTITLEMENTS: u32 = 5;
+const CSSLOT_DER_ENTITLEMENTS: u32 = 7;
+
+/// The version with the executable segment fields
+const CS_SUPPORTSEXECSEG: u32 = 0x20400;
+const CS_ADHOC: u32 = 0x2;
+const CS_HASHTYPE_SHA256: u8 = 2;
+const CS_EXECSEG_MAIN_BINARY: u64 = 0x1;
+const CODE_DIRECTORY_HEADER_SIZE: usize = 88;
+
+const MH_EXECUTE: u32 = 0x2;
+const PAGE_SIZE_LOG2: u8 = 12;
+const PAGE_SIZE: usize = 1 << PAGE_SIZE_LOG2;
+/// Segments are mapped in 16K pages on arm64
+const SEGMENT_ALIGNMENT: u64 = 0x4000;
+const HASH_SIZE: usize = 32;
+
+/// Why an image couldn't be signed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignError {
+    /// Not a thin 64-bit Mach-O image; sign each slice of a fat binary
+    NotMachO,
+    /// The image has no `__LINKEDIT` segment to put the signature in
+    NoLinkedit,
+    /// `__LINKEDIT`, or the existing signature, isn't at the end of the file
+    LinkeditNotLast,
+    /// The load commands fill the space before the first section
+    NoRoomForLoadCommand,
+    /// The entitlements aren't an XML plist dictionary of dictionaries,
+    /// arrays, strings, integers and booleans, so have no DER encoding
+    BadEntitlements,
+}
+
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignError::NotMachO => write!(f, "not a thin 64-bit Mach-O image"),
+            SignError::NoLinkedit => write!(f, "image has no __LINKEDIT segment"),
+            SignError::LinkeditNotLast => write!(f, "__LINKEDIT isn't at the end of the file"),
+            SignError::NoRoomForLoadCommand => write!(f, "no room after the load commands for LC_CODE_SIGNATURE"),
+            SignError::BadEntitlements => write!(f, "entitlements aren't a plist dictionary that can be DER encoded"),
+        }
+    }
+}
+
+impl std::error::Error for SignError {}
+
+/// Where the load commands signing rewrites are
+struct Layout {
+    ncmds: u32,
+    sizeofcmds: usize,
+    filetype: u32,
+    /// Offset of the `__LINKEDIT` segment command
+    linkedit: usize,
+    /// Offset of the `LC_CODE_SIGNATURE` command, if already signed
+    code_signature: Option<usize>,
+    /// File offset of the first section's contents; commands must end before it
+    first_section: usize,
+    /// File offset the load commands end at
+    commands_end: usize,
+    /// `__TEXT`'s file offset and size
+    text: (u64, u64),
+}
+
+impl Layout {
+    fn parse(image: &[u8]) -> Result<Self, SignError> {
+        if read_u32(image, 0) != Some(MH_MAGIC_64) {
+            return Err(SignError::NotMachO);
+        }
+        let filetype = read_u32(image, 12).ok_or(SignError::NotMachO)?;
+        let ncmds = read_u32(image, 16).ok_or(SignError::NotMachO)?;
+        let sizeofcmds = read_u32(image, 20).ok_or(SignError::NotMachO)? as usize;
+
+        let mut layout = Layout {
+            ncmds,
+            sizeofcmds,
+            filetype,
+            linkedit: 0,
+            code_signature: None,
+            first_section: image.len(),
+            commands_end: 0,
+            text: (0, 0),
+        };
+        let mut linkedit = None;
+        let mut offset = 32;
+        for _ in 0..ncmds {
+            let cmd = read_u32(image, offset).ok_or(SignError::NotMachO)?;
+            let cmdsize = read_u32(image, offset + 4).ok_or(SignError::NotMachO)? as usize;
+            if cmdsize < 8 || offset + cmdsize > image.len() {
+                return Err(SignError::NotMachO);
+            }
+            match cmd {
+                LC_SEGMENT_64 if cmdsize >= 72 => {
+                    let name = &image[offset + 8..offset + 24];
+                    let fileoff = read_u64(image, offset + 40).unwrap_or(0);
+                    let filesize = read_u64(image, offset + 48).unwrap_or(0);
+                    if name.starts_with(b"__LINKEDIT\0") {
+                        linkedit = Some(offset);
+                    } else if name.starts_with(b"__TEXT\0") {
+                        layout.text = (fileoff, filesize);
+                    }
+                    // Only the sections that fit in the command
+                    let nsects = (read_u32(image, offset + 64).unwrap_or(0) as usize).min((cmdsize - 72) / 80);
+                    for section in (0..nsects).map(|i| offset + 72 + i * 80) {
+                        let section_offset = read_u32(image, section + 48).unwrap_or(0) as usize;
+                        if section_offset != 0 {
+                            layout.first_section = layout.first_section.min(section_offset);
+                        }
+                    }
+                }
+                LC_CODE_SIGNATURE if cmdsize >= 16 => layout.code_signature = Some(offset),
+                LC_CODE_SIGNATURE => return Err(SignError::NotMachO),
+                _ => {}
+            }
+            offset += cmdsize;
+        }
+        layout.commands_end = offset;
+        layout.linkedit = linkedit.ok_or(SignError::NoLinkedit)?;
+        Ok(layout)
+    }
+}
+
+/// Sign `image` ad hoc as `identifier`, with `entitlements` (a plist) if given
+///
+/// Returns the signed image. `identifier` is usually the bundle identifier
+/// for apps and the file name for dylibs.
+pub fn sign_adhoc(image: &[u8], identifier: &str, entitlements: Option<&[u8]>) -> Result<Vec<u8>, SignError> {
+    let layout = Layout::parse(image)?;
+    let fileoff = read_u64(image, layout.linkedit + 40).ok_or(SignError::NotMachO)?;
+    let linkedit_end = read_u64(image, layout.linkedit + 48)
+        .and_then(|filesize| fileoff.checked_add(filesize))
+        .ok_or(SignError::NotMachO)?;
+    if linkedit_end != image.len() as u64 {
+        return Err(SignError::LinkeditNotLast);
+    }
+
+    // Drop any old signature; the new one goes at the next 16-byte boundary
+    let end = match layout.code_signature {
+        Some(command) => read_u32(image, command + 8).ok_or(SignError::NotMachO)? as usize,
+        None => image.len(),
+    };
+    // It must be in __LINKEDIT, after the load commands
+    if end > image.len() || (end as u64) < fileoff || end < layout.commands_end {
+        return Err(SignError::LinkeditNotLast);
+    }
+    let mut data = image[..end].to_vec();
+    let code_limit = end.next_multiple_of(16);
+    data.resize(code_limit, 0);
+
+    let requirements = blob(CSMAGIC_REQUIREMENTS, &0u32.to_be_bytes());
+    let der_entitlements = entitlements
+        .map(|plist| der_entitlements(plist).ok_or(SignError::BadEntitlements))
+        .transpose()?
+        .map(|der| blob(CSMAGIC_EMBEDDED_DER_ENTITLEMENTS, &der));
+    let entitlements = entitlements.map(|plist| blob(CSMAGIC_EMBEDDED_ENTITLEMENTS, plist));
+    let special_slots = if entitlements.is_some() { CSSLOT_DER_ENTITLEMENTS } else { CSSLOT_REQUIREMENTS } as usize;
+    let code_slots = code_limit.div_ceil(PAGE_SIZE);
+    let hash_offset = CODE_DIRECTORY_HEADER_SIZE + identifier.len() + 1 + special_slots * HASH_SIZE;
+    let code_directory_size = hash_offset + code_slots * HASH_SIZE;
+    let blob_count = 2 + 2 * entitlements.is_some() as usize;
+    let signature_size = 12
+        + blob_count * 8
+        + code_directory_size
+        + requirements.len()
+        + entitlements.as_ref().map_or(0, Vec::len)
+        + der_entitlements.as_ref().map_or(0, Vec::len);
+    let signature_size = signature_size.next_multiple_of(16);
+
+    // Point LC_CODE_SIGNATURE at it, adding the command if there's none
+    let command = match layout.code_signature {
+        Some(command) => command,
+        None => {
+            let command = 32 + layout.sizeofcmds;
+            if command + 16 > layout.first_section {
+                return Err(SignError::NoRoomForLoadCommand);
+            }
+            data[16..20].copy_from_slice(&(layout.ncmds + 1).to_le_bytes());
+            data[20..24].copy_from_slice(&(layout.sizeofcmds as u32 + 16).to_le_bytes());
+            data[command..command + 4].copy_from_slice(&LC_CODE_SIGNATURE.to_le_bytes());
+            data[command + 4..command + 8].copy_from_slice(&16u32.to_le_bytes());
+            command
+        }
+    };
+    data[command + 8..command + 12].copy_from_slice(&(code_limit as u32).to_le_bytes());
+    data[command + 12..command + 16].copy_from_slice(&(signature_size as u32).to_le_bytes());
+
+    // and grow __LINKEDIT over it
+    let filesize = ((code_limit + signature_size) as u64).checked_sub(fileoff).ok_or(SignError::LinkeditNotLast)?;
+    let linkedit = layout.linkedit;
+    data[linkedit + 32..linkedit + 40].copy_from_slice(&filesize.next_multiple_of(SEGMENT_ALIGNMENT).to_le_bytes());
+    data[linkedit + 48..linkedit + 56].copy_from_slice(&filesize.to_le_bytes());
+
+    // The page hashes cover the rewritten header and load commands
+    let mut code_directory = Vec::with_capacity(code_directory_size);
+    let exec_seg_flags = if layout.filetype == MH_EXECUTE { CS_EXECSEG_MAIN_BINARY } else { 0 };
+    for field in [
+        CSMAGIC_CODEDIRECTORY,
+        code_directory_size as u32,
+        CS_SUPPORTSEXECSEG,
+        CS_ADHOC,
+        hash_offset as u32,
+        CODE_DIRECTORY_HEADER_SIZE as u32,
+        special_slots as u32,
+        code_slots as u32,
+        code_limit as u32,
+    ] {
+        code_directory.extend_from_slice(&field.to_be_bytes());
+    }
+    code_directory.extend_from_slice(&[HASH_SIZE as u8, CS_HASHTYPE_SHA256, 0, PAGE_SIZE_LOG2]);
+    // spare2, scatterOffset, teamOffset, spare3, codeLimit64
+    code_directory.extend_from_slice(&[0; 24]);
+    for field in [layout.text.0, layout.text.1, exec_seg_flags] {
+        code_directory.extend_from_slice(&field.to_be_bytes());
+    }
+    code_directory.extend_from_slice(identifier.as_bytes());
+    code_directory.push(0);
+
+    // Special slots are stored last slot first, ending right before the code slots
+    for slot in (1..=special_slots as u32).rev() {
+        let hash = match slot {
+            CSSLOT_REQUIREMENTS => Some(sha256(&requirements)),
+            CSSLOT_ENTITLEMENTS => entitlements.as_deref().map(sha256),
+            CSSLOT_DER_ENTITLEMENTS => der_entitlements.as_deref().map(sha256),
+            _ => None,
+        };
+        code_directory.extend_from_slice(&hash.unwrap_or_default());
+    }
+    for page in data.chunks(PAGE_SIZE) {
+        code_directory.extend_from_slice(&sha256(page));
+    }
+
+    let mut blobs = vec![(CSSLOT_CODEDIRECTORY, code_directory), (CSSLOT_REQUIREMENTS, requirements)];
+    blobs.extend(entitlements.map(|entitlements| (CSSLOT_ENTITLEMENTS, entitlements)));
+    blobs.extend(der_entitlements.map(|der| (CSSLOT_DER_ENTITLEMENTS, der)));
+    let mut signature = Vec::with_capacity(signature_size);
+    signature.extend_from_slice(&CSMAGIC_EMBEDDED_SIGNATURE.to_be_bytes());
+    signature.extend_from_slice(&(signature_size as u32).to_be_bytes());
+    signature.extend_from_slice(&(blobs.len() as u32).to_be_bytes());
+    let mut offset = 12 + blobs.len() * 8;
+    for (slot, blob) in &blobs {
+        signature.extend_from_slice(&slot.to_be_bytes());
+        signature.extend_from_slice(&(offset as u32).to_be_bytes());
+        offset += blob.len();
+    }
+    for (_, blob) in &blobs {
+        signature.extend_from_slice(blob);
+    }
+    signature.resize(signature_size, 0);
+
+    data.extend_from_slice(&signature);
+    Ok(data)
+}
+
+/// Sign the image at `path` in place
+///
+/// `identifier` defaults to the file name, as `ldid` does; `entitlements`
+/// is the contents of an entitlements plist.
+pub fn sign_file(path: &Path, identifier: Option<&str>, entitlements: Option<&[u8]>) -> io::Result<()> {
+    let image = std::fs::read(path)?;
+    let file_name = path.file_name().map(|name| name.to_string_lossy());
+    let identifier = identifier.or(file_name.as_deref()).unwrap_or_default();
+    let signed =
+        sign_adhoc(&image, identifier, entitlements).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    std::fs::write(path, signed)
+}
+
+/// A blob: its magic and total length, big-endian, then `payload`
+fn blob(magic: u32, payload: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(8 + payload.len());
+    blob.extend_from_slice(&magic.to_be_bytes());
+    blob.extend_from_slice(&((8 + payload.len()) as u32).to_be_bytes());
+    blob.extend_from_slice(payload);
+    blob
+}
+
+/// The DER encoding of an entitlements plist, as `CoreEntitlements` reads it
+///
+/// A version of 1, then the dictionary, with its entries sorted by key.
+fn der_entitlements(plist: &[u8]) -> Option<Vec<u8>> {
+    let mut reader = PlistReader { rest: std::str::from_utf8(plist).ok()? };
+    if reader.tag()?.0 != "plist" {
+        return None;
+    }
+    let dict = reader.tag().filter(|&(name, _)| name == "dict")?;
+    let entitlements = reader.value(dict)?;
+    if reader.tag()?.0 != "/plist" {
+        return None;
+    }
+    Some(der(0x70, &[der(0x02, &[1]), entitlements].concat()))
+}
+
+/// A DER element: `tag`, the length of `content`, then `content`
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut der = vec![tag];
+    if content.len() < 0x80 {
+        der.push(content.len() as u8);
+    } else {
+        let length = content.len().to_be_bytes();
+        let skip = length.iter().take_while(|&&byte| byte == 0).count();
+        der.push(0x80 | (length.len() - skip) as u8);
+        der.extend_from_slice(&length[skip..]);
+    }
+    der.extend_from_slice(content);
+    der
+}
+
+/// Reads the values of an XML plist, encoding each as DER
+struct PlistReader<'a> {
+    rest: &'a str,
+}
+
+impl<'a> PlistReader<'a> {
+    /// The next tag's name, e.g. `key` or `/dict`, and whether it closes itself
+    ///
+    /// Skips whitespace, comments, and the XML and DOCTYPE declarations.
+    fn tag(&mut self) -> Option<(&'a str, bool)> {
+        loop {
+            self.rest = self.rest.trim_start();
+            let skip = if self.rest.starts_with("<!--") {
+                self.rest.find("-->")? + 3
+            } else if self.rest.starts_with("<?") || self.rest.starts_with("<!") {
+                self.rest.find('>')? + 1
+            } else {
+                break;
+            };
+            self.rest = &self.rest[skip..];
+        }
+        let tag = self.rest.strip_prefix('<')?;
+        let end = tag.find('>')?;
+        self.rest = &tag[end + 1..];
+        let tag = &tag[..end];
+        // Attributes, like the plist's version, don't matter
+        let name = tag.trim_end_matches('/').split_whitespace().next()?;
+        Some((name, tag.ends_with('/')))
+    }
+
+    /// The text up to the tag closing `name`
+    fn text(&mut self, (name, empty): (&str, bool)) -> Option<String> {
+        if empty {
+            return Some(String::new());
+        }
+        let end = self.rest.find("</")?;
+        let text = unescape(&self.rest[..end])?;
+        self.rest = &self.rest[end..];
+        (self.tag()?.0.strip_prefix('/') == Some(name)).then_some(text)
+    }
+
+    /// The next tag inside the element `tag` opens, or `None` at its end
+    fn child(&mut self, (name, empty): (&str, bool)) -> Option<Option<(&'a str, bool)>> {
+        if empty {
+            return Some(None);
+        }
+        let tag = self.tag()?;
+        Some((tag.0.strip_prefix('/') != Some(name)).then_some(tag))
+    }
+
+    /// The value starting with `tag`, as DER
+    fn value(&mut self, tag: (&str, bool)) -> Option<Vec<u8>> {
+        let (name, empty) = tag;
+        match name {
+            "true" | "false" if empty => Some(der(0x01, &[if name == "true" { 0xff } else { 0 }])),
+            "string" => Some(der(0x0c, self.text(tag)?.as_bytes())),
+            "integer" => {
+                let value: i64 = self.text(tag)?.trim().parse().ok()?;
+                // Two's complement, without the leading bytes that only repeat the sign
+                let bytes = value.to_be_bytes();
+                let skip = bytes
+                    .windows(2)
+                    .take_while(|pair| (pair[0] == 0 && pair[1] < 0x80) || (pair[0] == 0xff && pair[1] >= 0x80))
+                    .count();
+                Some(der(0x02, &bytes[skip..]))
+            }
+            "array" => {
+                let mut items = Vec::new();
+                while let Some(item) = self.child(tag)? {
+                    items.extend(self.value(item)?);
+                }
+                Some(der(0x30, &items))
+            }
+            "dict" => {
+                let mut entries = Vec::new();
+                while let Some(key) = self.child(tag)? {
+                    if key.0 != "key" {
+                        return None;
+                    }
+                    let key = self.text(key)?;
+                    let next = self.tag()?;
+                    let value = self.value(next)?;
+                    let entry = der(0x30, &[der(0x0c, key.as_bytes()), value].concat());
+                    entries.push((key, entry));
+                }
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Some(der(0xb0, &entries.into_iter().flat_map(|(_, entry)| entry).collect::<Vec<_>>()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Replace the XML entities in `text` with the characters they stand for
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = rest.find(';')?;
+        unescaped.push(match &rest[..end] {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            entity => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        });
+        rest = &rest[end + 1..];
+    }
+    unescaped.push_str(rest);
+    Some(unescaped)
+}
+
+fn sha256(data: &[u8]) -> [u8; HASH_SIZE] {
+    Sha256::digest(data).into()
+}
+
+fn read_be32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_be64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// The `CodeDirectory` of a signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeDirectory {
+    pub version: u32,
+    pub flags: u32,
+    pub identifier: String,
+    /// The file offset the page hashes stop at, where the signature starts
+    pub code_limit: u64,
+    /// 0 if the code is hashed in one piece
+    pub page_size: usize,
+    pub hash_type: u8,
+    /// Hashes of the blobs in special slots 1, 2, ...; zero for empty slots
+    pub special_hashes: Vec<Vec<u8>>,
+    /// Hashes of each page up to `code_limit`
+    pub code_hashes: Vec<Vec<u8>>,
+    pub exec_seg_base: u64,
+    pub exec_seg_limit: u64,
+    pub exec_seg_flags: u64,
+}
+
+/// An embedded signature, split into its blobs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub code_directory: CodeDirectory,
+    /// The requirements blob, header included
+    pub requirements: Option<Vec<u8>>,
+    /// The entitlements plist
+    pub entitlements: Option<Vec<u8>>,
+    /// The entitlements' DER encoding
+    pub der_entitlements: Option<Vec<u8>>,
+}
+
+impl Signature {
+    /// Parse the `SuperBlob` `LC_CODE_SIGNATURE` points to
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if read_be32(data, 0)? != CSMAGIC_EMBEDDED_SIGNATURE {
+            return None;
+        }
+        let count = read_be32(data, 8)? as usize;
+        let mut code_directory = None;
+        let mut requirements = None;
+        let mut entitlements = None;
+        let mut der_entitlements = None;
+        for index in (0..count).map(|i| 12 + i * 8) {
+            let slot = read_be32(data, index)?;
+            let offset = read_be32(data, index + 4)? as usize;
+            let length = read_be32(data, offset + 4)? as usize;
+            let blob = data.get(offset..offset.checked_add(length)?)?;
+            match (slot, read_be32(blob, 0)?) {
+                (CSSLOT_CODEDIRECTORY, CSMAGIC_CODEDIRECTORY) => code_directory = parse_code_directory(blob),
+                (CSSLOT_REQUIREMENTS, CSMAGIC_REQUIREMENTS) => requirements = Some(blob.to_vec()),
+                (CSSLOT_ENTITLEMENTS, CSMAGIC_EMBEDDED_ENTITLEMENTS) => entitlements = Some(blob.get(8..)?.to_vec()),
+                (CSSLOT_DER_ENTITLEMENTS, CSMAGIC_EMBEDDED_DER_ENTITLEMENTS) => {
+                    der_entitlements = Some(blob.get(8..)?.to_vec())
+                }
+                _ => {}
+            }
+        }
+        Some(Signature { code_directory: code_directory?, requirements, entitlements, der_entitlements })
+    }
+
+    /// The signature of `image`, if it has one
+    pub fn of_image(image: &[u8]) -> Option<Self> {
+        let command = Layout::parse(image).ok()?.code_signature?;
+        let offset = read_u32(image, command + 8)? as usize;
+        let size = read_u32(image, command + 12)? as usize;
+        Self::parse(image.get(offset..offset.checked_add(size)?)?)
+    }
+
+    /// Whether the hashes match the pages of `image` and the other blobs
+    pub fn matches(&self, image: &[u8]) -> bool {
+        let directory = &self.code_directory;
+        let Some(code) = image.get(..directory.code_limit as usize) else {
+            return false;
+        };
+        if directory.hash_type != CS_HASHTYPE_SHA256 {
+            return false;
+        }
+        // A page size of 0 hashes the code in one piece
+        let page_size = if directory.page_size == 0 { code.len().max(1) } else { directory.page_size };
+        let pages = code.chunks(page_size);
+        if pages.len() != directory.code_hashes.len()
+            || !pages.zip(&directory.code_hashes).all(|(page, hash)| sha256(page)[..] == hash[..])
+        {
+            return false;
+        }
+
+        // Slots without a blob are zero, or past the end
+        let special_matches = |slot: u32, hash: Option<[u8; HASH_SIZE]>| {
+            let stored = directory.special_hashes.get(slot as usize - 1);
+            match (hash, stored) {
+                (Some(hash), Some(stored)) => stored[..] == hash[..],
+                (Some(_), None) => false,
+                (None, stored) => stored.is_none_or(|stored| stored.iter().all(|&byte| byte == 0)),
+            }
+        };
+        let entitlements = self.entitlements.as_deref().map(|plist| blob(CSMAGIC_EMBEDDED_ENTITLEMENTS, plist));
+        let der_entitlements = self.der_entitlements.as_deref().map(|der| blob(CSMAGIC_EMBEDDED_DER_ENTITLEMENTS, der));
+        special_matches(CSSLOT_REQUIREMENTS, self.requirements.as_deref().map(sha256))
+            && special_matches(CSSLOT_ENTITLEMENTS, entitlements.as_deref().map(sha256))
+            && special_matches(CSSLOT_DER_ENTITLEMENTS, der_entitlements.as_deref().map(sha256))
+    }
+}
+
+fn parse_code_directory(blob: &[u8]) -> Option<CodeDirectory> {
+    let version = read_be32(blob, 8)?;
+    let hash_offset = read_be32(blob, 16)? as usize;
+    let identifier_offset = read_be32(blob, 20)? as usize;
+    let special_slots = read_be32(blob, 24)? as usize;
+    let code_slots = read_be32(blob, 28)? as usize;
+    let hash_size = *blob.get(36)? as usize;
+    let hash = |index: usize| blob.get(index..index + hash_size).map(<[u8]>::to_vec);
+
+    let exec_seg = |offset| if version >= CS_SUPPORTSEXECSEG { read_be64(blob, offset) } else { Some(0) };
+    let code_limit = match read_be64(blob, 56).filter(|_| version >= 0x20300) {
+        Some(limit) if limit != 0 => limit,
+        _ => read_be32(blob, 32)? as u64,
+    };
+    Some(CodeDirectory {
+        version,
+        flags: read_be32(blob, 12)?,
+        identifier: c_str(blob.get(identifier_offset..)?)?.to_string(),
+        code_limit,
+        page_size: match *blob.get(39)? {
+            0 => 0,
+            shift => 1usize.checked_shl(shift as u32)?,
+        },
+        hash_type: *blob.get(37)?,
+        special_hashes: (1..=special_slots)
+            .map(|slot| hash(hash_offset.checked_sub(slot * hash_size)?))
+            .collect::<Option<_>>()?,
+        code_hashes: (0..code_slots).map(|slot| hash(hash_offset + slot * hash_size)).collect::<Option<_>>()?,
+        exec_seg_base: exec_seg(64)?,
+        exec_seg_limit: exec_seg(72)?,
+        exec_seg_flags: exec_seg(80)?,
+    })
+}
@@ -0,0 +1,373 @@
+//! Declaring Objective-C classes from Rust
+//!
+//! Delegates and subclasses of UIKit classes are declared with a
+//! [`ClassBuilder`]: a superclass, protocols, instance variables and methods,
+//! with the method type encodings derived from the Rust fn signatures.
+//! [`DeclaredClass`] registers the class the first time it is needed:
+//!
+//! ```ignore
+//! struct State { taps: Cell<usize> }
+//!
+//! unsafe extern "C" fn did_tap(this: id, _cmd: SEL, _sender: id) {
+//!     let state = unsafe { declare::state::<State>(this) };
+//!     state.taps.set(state.taps.get() + 1);
+//! }
+//!
+//! static TAP_HANDLER: DeclaredClass = DeclaredClass::new(c"RustTapHandler", c"NSObject");
+//!
+//! let class = TAP_HANDLER.get(|builder| unsafe {
+//!     builder.add_state::<State>();
+//!     builder.add_method(c"didTap:", did_tap as unsafe extern "C" fn(id, SEL, id));
+//! });
+//! ```
+
+use core::ffi::CStr;
+use core::mem;
+use core::ptr;
+use std::ffi::CString;
+use std::sync::OnceLock;
+
+use crate::encode::{Encode, Encoding, method_encoding};
+use crate::objc::{
+    Class, IMP, SEL, class_addIvar, class_addMethod, class_addProtocol, class_getInstanceVariable,
+    class_getMethodImplementation, class_getName, class_getSuperclass, id, ivar_getOffset, objc_allocateClassPair,
+    objc_getClass, objc_getProtocol, objc_registerClassPair, object_getClass, sel_registerName,
+};
+
+/// Name of the instance variable holding the state added by [`ClassBuilder::add_state`]
+pub const STATE_IVAR: &CStr = c"__rust_state";
+
+/// A Rust fn that can be added as a method implementation
+///
+/// Implemented for `extern "C"` fns taking the receiver (`id`, `Class` or a
+/// class wrapper) and the selector, followed by up to 10 [`Encode`] arguments.
+///
+/// # Safety
+///
+/// `encoding` must describe the fn's signature.
+pub unsafe trait MethodImplementation: Copy {
+    /// The receiver type
+    type Receiver: Encode;
+
+    /// The type encoding of the method, as `class_addMethod` takes it
+    fn encoding() -> CString;
+
+    /// The fn as an untyped IMP
+    fn imp(self) -> IMP;
+
+    /// The fn back from an untyped IMP
+    ///
+    /// # Safety
+    ///
+    /// `imp` must be a fn of this signature.
+    unsafe fn from_imp(imp: unsafe extern "C" fn()) -> Self;
+}
+
+macro_rules! method_implementation {
+    ($($arg:ident),*) => {
+        method_implementation!(@impl unsafe extern "C" fn(Recv, SEL $(, $arg)*) -> R; $($arg),*);
+        method_implementation!(@impl extern "C" fn(Recv, SEL $(, $arg)*) -> R; $($arg),*);
+    };
+    (@impl $fn:ty; $($arg:ident),*) => {
+        unsafe impl<Recv: Encode, R: Encode $(, $arg: Encode)*> MethodImplementation for $fn {
+            type Receiver = Recv;
+
+            fn encoding() -> CString {
+                method_encoding(&R::ENCODING, &[$($arg::ENCODING),*])
+            }
+
+            #[inline]
+            fn imp(self) -> IMP {
+                Some(unsafe { mem::transmute::<$fn, unsafe extern "C" fn()>(self) })
+            }
+
+            #[inline]
+            unsafe fn from_imp(imp: unsafe extern "C" fn()) -> Self {
+                unsafe { mem::transmute::<unsafe extern "C" fn(), $fn>(imp) }
+            }
+        }
+    };
+}
+
+method_implementation!();
+method_implementation!(A);
+method_implementation!(A, B);
+method_implementation!(A, B, C);
+method_implementation!(A, B, C, D);
+method_implementation!(A, B, C, D, E);
+method_implementation!(A, B, C, D, E, F);
+method_implementation!(A, B, C, D, E, F, G);
+method_implementation!(A, B, C, D, E, F, G, H);
+method_implementation!(A, B, C, D, E, F, G, H, I);
+method_implementation!(A, B, C, D, E, F, G, H, I, J);
+
+/// A class being declared, registered by [`ClassBuilder::register`]
+///
+/// Dropping it without registering leaves the allocated class pair unused.
+pub struct ClassBuilder {
+    class: Class,
+}
+
+impl ClassBuilder {
+    /// Start declaring the class `name` as a subclass of `superclass`
+    ///
+    /// Returns `None` if a class of that name already exists.
+    ///
+    /// # Safety
+    ///
+    /// `superclass` must be a registered class.
+    pub unsafe fn new(name: &CStr, superclass: Class) -> Option<Self> {
+        let class = unsafe { objc_allocateClassPair(superclass, name.as_ptr(), 0) };
+        if class.is_null() { None } else { Some(Self { class }) }
+    }
+
+    /// The class being declared
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// Declare conformance to the protocol `name`
+    ///
+    /// # Panics
+    ///
+    /// If no protocol of that name is loaded.
+    pub fn add_protocol(&mut self, name: &CStr) {
+        let protocol = unsafe { objc_getProtocol(name.as_ptr()) };
+        assert!(!protocol.is_null(), "protocol {:?} not found", name);
+        unsafe { class_addProtocol(self.class, protocol) };
+    }
+
+    /// Add an instance variable of type `T`
+    ///
+    /// # Panics
+    ///
+    /// If the class already has an instance variable of that name.
+    pub fn add_ivar<T: Encode>(&mut self, name: &CStr) {
+        let encoding = T::ENCODING.to_c_string();
+        let alignment = mem::align_of::<T>().trailing_zeros() as u8;
+        let added = unsafe {
+            class_addIvar(self.class, name.as_ptr(), mem::size_of::<T>(), alignment, encoding.as_ptr())
+        };
+        assert!(added, "failed to add ivar {:?} to {}", name, self.name());
+    }
+
+    /// Add an instance method
+    ///
+    /// The type encoding comes from the signature of `func`.
+    ///
+    /// # Safety
+    ///
+    /// `func` must have the signature callers expect for `selector`,
+    /// e.g. the one declared by the protocol or superclass.
+    ///
+    /// # Panics
+    ///
+    /// If the class already implements `selector`.
+    pub unsafe fn add_method<F: MethodImplementation>(&mut self, selector: &CStr, func: F) {
+        unsafe { add_method_to(self.class, selector, func, '-') }
+    }
+
+    /// Add a class method
+    ///
+    /// # Safety
+    ///
+    /// As for [`add_method`](Self::add_method).
+    pub unsafe fn add_class_method<F: MethodImplementation>(&mut self, selector: &CStr, func: F) {
+        unsafe { add_method_to(object_getClass(self.class.cast()), selector, func, '+') }
+    }
+
+    /// Store a `S` in each instance, reachable through [`state`]
+    ///
+    /// Adds a pointer ivar named [`STATE_IVAR`] and a `dealloc` that drops
+    /// the state before calling the superclass's `dealloc`. Instances start
+    /// without state; set it with [`set_state`], typically in an `init`
+    /// method.
+    ///
+    /// Only one class in a hierarchy can have state.
+    pub fn add_state<S: 'static>(&mut self) {
+        self.add_ivar::<*mut core::ffi::c_void>(STATE_IVAR);
+        unsafe { self.add_method(c"dealloc", dealloc_state::<S> as unsafe extern "C" fn(id, SEL)) };
+    }
+
+    /// Register the class with the runtime, after which it can be instantiated
+    pub fn register(self) -> Class {
+        unsafe { objc_registerClassPair(self.class) };
+        self.class
+    }
+
+    fn name(&self) -> String {
+        unsafe { CStr::from_ptr(class_getName(self.class)) }.to_string_lossy().into_owned()
+    }
+}
+
+unsafe fn add_method_to<F: MethodImplementation>(class: Class, selector: &CStr, func: F, kind: char) {
+    debug_assert!(
+        matches!(F::Receiver::ENCODING, Encoding::Object | Encoding::Class),
+        "method receivers must be objects or classes, not {}",
+        F::Receiver::ENCODING
+    );
+
+    let encoding = F::encoding();
+    unsafe {
+        let sel = sel_registerName(selector.as_ptr());
+        let added = class_addMethod(class, sel, func.imp(), encoding.as_ptr());
+        assert!(
+            added,
+            "failed to add {}[{} {}]: the class already implements it",
+            kind,
+            CStr::from_ptr(class_getName(class)).to_string_lossy(),
+            selector.to_string_lossy()
+        );
+    }
+}
+
+/// A class declared from Rust, registered on first use
+///
+/// Keep it in a `static`; [`get`](Self::get) declares and registers the class
+/// once per process, no matter how many threads ask for it.
+pub struct DeclaredClass {
+    name: &'static CStr,
+    superclass: &'static CStr,
+    class: OnceLock<ClassPtr>,
+}
+
+/// Registered classes are immutable and can be shared between threads
+struct ClassPtr(Class);
+
+unsafe impl Send for ClassPtr {}
+unsafe impl Sync for ClassPtr {}
+
+impl DeclaredClass {
+    /// A class `name` subclassing the class `superclass`
+    pub const fn new(name: &'static CStr, superclass: &'static CStr) -> Self {
+        Self {
+            name,
+            superclass,
+            class: OnceLock::new(),
+        }
+    }
+
+    /// The class, declaring it with `declare` and registering it on the first call
+    ///
+    /// # Panics
+    ///
+    /// If the superclass isn't loaded, or another image already registered a
+    /// class of the same name.
+    pub fn get(&self, declare: impl FnOnce(&mut ClassBuilder)) -> Class {
+        self.class
+            .get_or_init(|| {
+                let superclass = unsafe { objc_getClass(self.superclass.as_ptr()) };
+                assert!(!superclass.is_null(), "superclass {:?} of {:?} not found", self.superclass, self.name);
+
+                let mut builder = unsafe { ClassBuilder::new(self.name, superclass) }
+                    .unwrap_or_else(|| panic!("class {:?} is already registered", self.name));
+                declare(&mut builder);
+                ClassPtr(builder.register())
+            })
+            .0
+    }
+
+    /// The class if it has been registered
+    pub fn try_get(&self) -> Option<Class> {
+        self.class.get().map(|class| class.0)
+    }
+}
+
+/// Pointer to the instance variable `name` of `obj`
+///
+/// # Safety
+///
+/// `obj` must be a valid object and the ivar must be of type `T`.
+///
+/// # Panics
+///
+/// If the object's class has no such instance variable.
+pub unsafe fn ivar_ptr<T>(obj: id, name: &CStr) -> *mut T {
+    unsafe {
+        let ivar = class_getInstanceVariable(object_getClass(obj), name.as_ptr());
+        assert!(!ivar.is_null(), "object has no ivar {:?}", name);
+        obj.cast::<u8>().offset(ivar_getOffset(ivar)).cast()
+    }
+}
+
+/// The state of an instance of a class declared with [`ClassBuilder::add_state`]
+///
+/// Objective-C objects are shared, so the state is only available by shared
+/// reference; use `Cell`/`RefCell` fields for mutable parts.
+///
+/// # Safety
+///
+/// `obj` must be an instance of such a class declared with state `S`, and
+/// must outlive the returned reference.
+///
+/// # Panics
+///
+/// If no state has been set.
+pub unsafe fn state<'a, S: 'static>(obj: id) -> &'a S {
+    let state = unsafe { *ivar_ptr::<*mut S>(obj, STATE_IVAR) };
+    assert!(!state.is_null(), "state of object {:p} used before set_state", obj);
+    unsafe { &*state }
+}
+
+/// Set the state of an instance, dropping the previous one
+///
+/// # Safety
+///
+/// As for [`state`]; no references to a previous state may be alive.
+pub unsafe fn set_state<S: 'static>(obj: id, state: S) {
+    unsafe {
+        let slot = ivar_ptr::<*mut S>(obj, STATE_IVAR);
+        let previous = mem::replace(&mut *slot, Box::into_raw(Box::new(state)));
+        if !previous.is_null() {
+            drop(Box::from_raw(previous));
+        }
+    }
+}
+
+/// `dealloc` of classes with state: drop it, then continue with the superclass
+unsafe extern "C" fn dealloc_state<S: 'static>(this: id, cmd: SEL) {
+    unsafe {
+        let slot = ivar_ptr::<*mut S>(this, STATE_IVAR);
+        let state = mem::replace(&mut *slot, ptr::null_mut());
+        if !state.is_null() {
+            drop(Box::from_raw(state));
+        }
+
+        // `this` may be an instance of a subclass: find the class this dealloc was added to,
+        // skipping subclasses that override dealloc and those that inherit it
+        let own_imp = dealloc_state::<S> as unsafe extern "C" fn(id, SEL) as usize;
+        let is_own = |class: Class| {
+            !class.is_null() && class_getMethodImplementation(class, cmd).map(|imp| imp as usize) == Some(own_imp)
+        };
+        let mut class = object_getClass(this);
+        while !class.is_null() && !is_own(class) {
+            class = class_getSuperclass(class);
+        }
+        while is_own(class_getSuperclass(class)) {
+            class = class_getSuperclass(class);
+        }
+
+        let superclass = class_getSuperclass(class);
+        send_super_dealloc(this, superclass, cmd);
+    }
+}
+
+/// `[super dealloc]` from a method of a subclass of `superclass`
+unsafe fn send_super_dealloc(this: id, superclass: Class, cmd: SEL) {
+    // Mirrors `struct objc_super`, whose field names differ between header configurations
+    #[repr(C)]
+    struct Super {
+        receiver: id,
+        super_class: Class,
+    }
+
+    let sup = Super {
+        receiver: this,
+        super_class: superclass,
+    };
+    type MsgSendSuper = unsafe extern "C" fn(*const Super, SEL);
+    unsafe {
+        let msg_send: MsgSendSuper = mem::transmute(crate::objc::objc_msgSendSuper as *const ());
+        msg_send(&sup, cmd);
+    }
+}
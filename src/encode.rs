@@ -0,0 +1,445 @@
+//! Objective-C type encodings of Rust types
+//!
+//! The runtime describes every method argument and return value with a type
+//! encoding string (`@` for objects, `:` for selectors, `^v` for `void *`,
+//! ...). [`Encode`] gives Rust types the encoding of the C type they are
+//! passed as, so calls can be checked against the runtime's method signatures
+//! and the encodings `class_addMethod` and `class_addIvar` take can be built
+//! from Rust types.
+//!
+//! `#[repr(C)]` structs get their encoding from `#[derive(Encode)]`:
+//!
+//! ```ignore
+//! #[repr(C)]
+//! #[derive(Encode)]
+//! #[encoding(name = "UIEdgeInsets")]
+//! struct EdgeInsets { top: f64, left: f64, bottom: f64, right: f64 }
+//!
+//! assert_eq!(EdgeInsets::ENCODING.to_string(), "{UIEdgeInsets=dddd}");
+//! ```
+
+use core::ffi::c_void;
+use core::fmt;
+use std::ffi::CString;
+
+use crate::coregraphics::{CGPoint, CGRect, CGSize};
+use crate::objc::{objc_class, objc_object, objc_selector};
+
+pub use ios_sys_macros::Encode;
+
+/// An Objective-C type encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// `c`
+    Char,
+    /// `s`
+    Short,
+    /// `i`
+    Int,
+    /// `l` (32-bit `long`)
+    Long,
+    /// `q`
+    LongLong,
+    /// `C`
+    UChar,
+    /// `S`
+    UShort,
+    /// `I`
+    UInt,
+    /// `L`
+    ULong,
+    /// `Q`
+    ULongLong,
+    /// `f`
+    Float,
+    /// `d`
+    Double,
+    /// `B`
+    Bool,
+    /// `v`
+    Void,
+    /// `*` (`char *`)
+    String,
+    /// `@`
+    Object,
+    /// `#`
+    Class,
+    /// `:`
+    Sel,
+    /// `?` (e.g. function pointers)
+    Unknown,
+    /// `@?`
+    Block,
+    /// `^type`
+    Pointer(&'static Encoding),
+    /// `[countType]`
+    Array(usize, &'static Encoding),
+    /// `{name=fields}`
+    Struct(&'static str, &'static [Encoding]),
+    /// `(name=fields)`
+    Union(&'static str, &'static [Encoding]),
+}
+
+impl Encoding {
+    /// The encoding as a C string, e.g. for `class_addIvar`
+    pub fn to_c_string(&self) -> CString {
+        CString::new(self.to_string()).expect("encodings never contain nul bytes")
+    }
+}
+
+/// The encoding of a method for `class_addMethod`, e.g. `v@:@` for
+/// `- (void)setTitle:(NSString *)title`
+///
+/// `self` and `_cmd` are added before `args`. Stack offsets are left out,
+/// which the runtime accepts.
+pub fn method_encoding(ret: &Encoding, args: &[Encoding]) -> CString {
+    let mut encoding = format!("{}@:", ret);
+    for arg in args {
+        encoding.push_str(&arg.to_string());
+    }
+    CString::new(encoding).expect("encodings never contain nul bytes")
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            Self::Char => "c",
+            Self::Short => "s",
+            Self::Int => "i",
+            Self::Long => "l",
+            Self::LongLong => "q",
+            Self::UChar => "C",
+            Self::UShort => "S",
+            Self::UInt => "I",
+            Self::ULong => "L",
+            Self::ULongLong => "Q",
+            Self::Float => "f",
+            Self::Double => "d",
+            Self::Bool => "B",
+            Self::Void => "v",
+            Self::String => "*",
+            Self::Object => "@",
+            Self::Class => "#",
+            Self::Sel => ":",
+            Self::Unknown => "?",
+            Self::Block => "@?",
+            Self::Pointer(pointee) => return write!(f, "^{}", pointee),
+            Self::Array(count, element) => return write!(f, "[{}{}]", count, element),
+            Self::Struct(name, fields) => return write_aggregate(f, '{', name, fields, '}'),
+            Self::Union(name, fields) => return write_aggregate(f, '(', name, fields, ')'),
+        };
+        f.write_str(code)
+    }
+}
+
+fn write_aggregate(f: &mut fmt::Formatter<'_>, open: char, name: &str, fields: &[Encoding], close: char) -> fmt::Result {
+    write!(f, "{}{}=", open, name)?;
+    for field in fields {
+        write!(f, "{}", field)?;
+    }
+    write!(f, "{}", close)
+}
+
+/// A type that can be passed to or returned from an Objective-C method
+///
+/// # Safety
+///
+/// `ENCODING` must describe a C type with the same size, alignment and
+/// calling convention as `Self`.
+pub unsafe trait Encode {
+    /// The encoding of `Self`
+    const ENCODING: Encoding;
+}
+
+/// A type that pointers can point to
+///
+/// Pointers and references to `T` implement [`Encode`] with
+/// `T::ENCODING_REF`, which is usually `^` followed by `T`'s own encoding.
+/// Runtime types are the exception: a pointer to `objc_object` is `@`.
+///
+/// # Safety
+///
+/// `ENCODING_REF` must describe a C pointer to the C type of `Self`.
+pub unsafe trait RefEncode {
+    /// The encoding of a pointer to `Self`
+    const ENCODING_REF: Encoding;
+}
+
+macro_rules! encode_primitives {
+    ($($ty:ty => $encoding:ident,)*) => {$(
+        unsafe impl Encode for $ty {
+            const ENCODING: Encoding = Encoding::$encoding;
+        }
+
+        unsafe impl RefEncode for $ty {
+            const ENCODING_REF: Encoding = Encoding::Pointer(&Encoding::$encoding);
+        }
+    )*};
+}
+
+encode_primitives! {
+    i16 => Short,
+    i32 => Int,
+    i64 => LongLong,
+    isize => LongLong,
+    u8 => UChar,
+    u16 => UShort,
+    u32 => UInt,
+    u64 => ULongLong,
+    usize => ULongLong,
+    f32 => Float,
+    f64 => Double,
+    bool => Bool,
+}
+
+unsafe impl Encode for i8 {
+    const ENCODING: Encoding = Encoding::Char;
+}
+
+// `char *` has its own encoding
+unsafe impl RefEncode for i8 {
+    const ENCODING_REF: Encoding = Encoding::String;
+}
+
+unsafe impl Encode for () {
+    const ENCODING: Encoding = Encoding::Void;
+}
+
+unsafe impl RefEncode for c_void {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&Encoding::Void);
+}
+
+unsafe impl RefEncode for objc_object {
+    const ENCODING_REF: Encoding = Encoding::Object;
+}
+
+unsafe impl RefEncode for objc_class {
+    const ENCODING_REF: Encoding = Encoding::Class;
+}
+
+unsafe impl RefEncode for objc_selector {
+    const ENCODING_REF: Encoding = Encoding::Sel;
+}
+
+unsafe impl<T: RefEncode + ?Sized> Encode for *const T {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+
+unsafe impl<T: RefEncode + ?Sized> Encode for *mut T {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+
+unsafe impl<T: RefEncode + ?Sized> Encode for &T {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+
+unsafe impl<T: RefEncode + ?Sized> Encode for &mut T {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+
+unsafe impl<T: RefEncode + ?Sized> Encode for Option<&T> {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+
+unsafe impl<T: RefEncode + ?Sized> Encode for Option<&mut T> {
+    const ENCODING: Encoding = T::ENCODING_REF;
+}
+
+unsafe impl<T: RefEncode + ?Sized> RefEncode for *const T {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&T::ENCODING_REF);
+}
+
+unsafe impl<T: RefEncode + ?Sized> RefEncode for *mut T {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&T::ENCODING_REF);
+}
+
+unsafe impl<T: Encode, const N: usize> Encode for [T; N] {
+    const ENCODING: Encoding = Encoding::Array(N, &T::ENCODING);
+}
+
+unsafe impl<T: Encode, const N: usize> RefEncode for [T; N] {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
+}
+
+// The CoreGraphics geometry types are generated by bindgen, so they can't derive Encode
+macro_rules! encode_structs {
+    ($($ty:ty => $name:literal [$($field:ty),*],)*) => {$(
+        unsafe impl Encode for $ty {
+            const ENCODING: Encoding = Encoding::Struct($name, &[$(<$field>::ENCODING),*]);
+        }
+
+        unsafe impl RefEncode for $ty {
+            const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
+        }
+    )*};
+}
+
+encode_structs! {
+    CGPoint => "CGPoint" [f64, f64],
+    CGSize => "CGSize" [f64, f64],
+    CGRect => "CGRect" [CGPoint, CGSize],
+}
+
+unsafe impl Encode for crate::rc::AnyObject {
+    const ENCODING: Encoding = Encoding::Object;
+}
+
+/// Split a method type encoding like `v24@0:8@16` into its types
+///
+/// Stack offsets, type qualifiers (`r`, `n`, `o`, ...) and quoted names are
+/// dropped, so `@"NSString"16` becomes `@`.
+pub(crate) fn split_method_encoding(encoding: &str) -> Vec<String> {
+    let bytes = encoding.as_bytes();
+    let mut types = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        pos = skip_qualifiers(bytes, pos);
+        if pos >= bytes.len() {
+            break;
+        }
+        let end = type_end(bytes, pos);
+        types.push(strip_quoted(&encoding[pos..end]));
+        pos = end;
+        while pos < bytes.len() && (bytes[pos].is_ascii_digit() || bytes[pos] == b'-') {
+            pos += 1;
+        }
+    }
+
+    types
+}
+
+fn skip_qualifiers(bytes: &[u8], mut pos: usize) -> usize {
+    while pos < bytes.len() && matches!(bytes[pos], b'r' | b'n' | b'N' | b'o' | b'O' | b'R' | b'V' | b'A' | b'j') {
+        pos += 1;
+    }
+    pos
+}
+
+/// End of the single type starting at `pos`
+fn type_end(bytes: &[u8], pos: usize) -> usize {
+    match bytes[pos] {
+        b'^' => {
+            let next = skip_qualifiers(bytes, pos + 1);
+            if next < bytes.len() { type_end(bytes, next) } else { next }
+        }
+        b'@' => match bytes.get(pos + 1) {
+            Some(b'?') => pos + 2,
+            Some(b'"') => closing_quote(bytes, pos + 1),
+            _ => pos + 1,
+        },
+        b'{' | b'(' | b'[' => {
+            let mut depth = 0;
+            let mut i = pos;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = closing_quote(bytes, i);
+                        continue;
+                    }
+                    b'{' | b'(' | b'[' => depth += 1,
+                    b'}' | b')' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            bytes.len()
+        }
+        b'b' => {
+            let mut i = pos + 1;
+            while i < bytes.len() && bytes[i].is_ascii_digit() {
+                i += 1;
+            }
+            i
+        }
+        _ => pos + 1,
+    }
+}
+
+/// Position after the quoted string starting at `pos`
+fn closing_quote(bytes: &[u8], pos: usize) -> usize {
+    bytes[pos + 1..]
+        .iter()
+        .position(|&b| b == b'"')
+        .map_or(bytes.len(), |i| pos + i + 2)
+}
+
+fn strip_quoted(ty: &str) -> String {
+    let mut out = String::with_capacity(ty.len());
+    let mut in_quotes = false;
+    for c in ty.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Whether a type from [`split_method_encoding`] can be passed as `expected`
+///
+/// Tolerates the differences between equivalent encodings: `BOOL` is `c` on
+/// some architectures and `B` on others, `char *` may be `*` or `^c`, and
+/// structs behind pointers may omit their fields.
+pub(crate) fn encodings_match(expected: &str, actual: &str) -> bool {
+    if expected == actual {
+        return true;
+    }
+
+    let (Some(e), Some(a)) = (expected.as_bytes().first(), actual.as_bytes().first()) else {
+        return false;
+    };
+    match (e, a) {
+        (b'c' | b'B', b'c' | b'B') => expected.len() == 1 && actual.len() == 1,
+        (b'*', b'^') => matches!(actual, "^c" | "^C"),
+        (b'^', b'*') => matches!(expected, "^c" | "^C"),
+        (b'^', b'^') => encodings_match(&expected[1..], &actual[1..]),
+        (b'{', b'{') | (b'(', b'(') => aggregates_match(expected, actual),
+        (b'[', b'[') => {
+            let (e_count, e_elem) = split_array(expected);
+            let (a_count, a_elem) = split_array(actual);
+            e_count == a_count && encodings_match(e_elem, a_elem)
+        }
+        _ => false,
+    }
+}
+
+fn aggregates_match(expected: &str, actual: &str) -> bool {
+    let (e_name, e_fields) = split_aggregate(expected);
+    let (a_name, a_fields) = split_aggregate(actual);
+    if e_name != a_name && e_name != "?" && a_name != "?" {
+        return false;
+    }
+    match (e_fields, a_fields) {
+        (Some(e_fields), Some(a_fields)) => {
+            let e_fields = split_method_encoding(e_fields);
+            let a_fields = split_method_encoding(a_fields);
+            e_fields.len() == a_fields.len()
+                && e_fields.iter().zip(&a_fields).all(|(e, a)| encodings_match(e, a))
+        }
+        // `^{CGRect}` refers to the same struct as `^{CGRect={CGPoint=dd}{CGSize=dd}}`
+        _ => true,
+    }
+}
+
+/// `{Name=fields}` into `("Name", Some("fields"))`
+fn split_aggregate(ty: &str) -> (&str, Option<&str>) {
+    let inner = &ty[1..ty.len().saturating_sub(1).max(1)];
+    match inner.split_once('=') {
+        Some((name, fields)) => (name, Some(fields)),
+        None => (inner, None),
+    }
+}
+
+/// `[12i]` into `("12", "i")`
+fn split_array(ty: &str) -> (&str, &str) {
+    let inner = &ty[1..ty.len().saturating_sub(1).max(1)];
+    let digits = inner.bytes().take_while(u8::is_ascii_digit).count();
+    inner.split_at(digits)
+}
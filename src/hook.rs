@@ -0,0 +1,420 @@
+//! Replacing method implementations
+//!
+//! [`Hook`] swaps the IMP of an existing instance or class method for a Rust
+//! fn and keeps the previous IMP, typed as the same fn, to call through to:
+//!
+//! ```ignore
+//! type SetAlpha = unsafe extern "C" fn(id, SEL, f64);
+//!
+//! static SET_ALPHA: OnceLock<Hook<SetAlpha>> = OnceLock::new();
+//!
+//! unsafe extern "C" fn set_alpha(this: id, cmd: SEL, alpha: f64) {
+//!     let original = SET_ALPHA.get().unwrap().original();
+//!     unsafe { original(this, cmd, alpha.max(0.5)) }
+//! }
+//!
+//! let class = unsafe { objc_getClass(c"UIView".as_ptr()) };
+//! let hook = unsafe { Hook::instance_method(class, c"setAlpha:", set_alpha as SetAlpha) }?;
+//! SET_ALPHA.set(hook).ok();
+//! ```
+//!
+//! A method the class inherits without overriding is added to the class
+//! rather than replaced, so the superclass and its other subclasses keep
+//! their implementation. The original is then the inherited IMP at the time
+//! of hooking.
+
+use core::ffi::{CStr, c_void};
+use core::fmt;
+use core::mem;
+use std::sync::{Mutex, OnceLock};
+
+use crate::declare::MethodImplementation;
+use crate::encode::{encodings_match, split_method_encoding};
+use crate::objc::{
+    Class, IMP, Method, SEL, class_addMethod, class_getInstanceMethod, class_getName, class_getSuperclass,
+    method_getImplementation, method_getTypeEncoding, method_setImplementation, objc_getClass, object_getClass,
+    sel_registerName,
+};
+
+pub use ios_sys_macros::hook;
+
+/// Why a method couldn't be hooked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookError {
+    /// The class is nil
+    NilClass,
+    /// No class with this name is loaded
+    ClassNotFound {
+        /// The class name
+        class: String,
+    },
+    /// Neither the class nor its superclasses implement the selector
+    MethodNotFound {
+        /// `-[Class selector]` or `+[Class selector]`
+        method: String,
+    },
+    /// The Rust fn's signature doesn't match the method's type encoding
+    SignatureMismatch {
+        /// `-[Class selector]` or `+[Class selector]`
+        method: String,
+        /// The encoding derived from the Rust fn
+        expected: String,
+        /// The method's encoding
+        actual: String,
+    },
+    /// `class_addMethod` failed to add the override
+    AddMethodFailed {
+        /// `-[Class selector]` or `+[Class selector]`
+        method: String,
+    },
+    /// A hooking library didn't install the hook
+    Failed {
+        /// `-[Class selector]` or `+[Class selector]`
+        method: String,
+    },
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HookError::NilClass => write!(f, "cannot hook a method of a nil class"),
+            HookError::ClassNotFound { class } => write!(f, "class {} not found", class),
+            HookError::MethodNotFound { method } => write!(f, "{} not found", method),
+            HookError::SignatureMismatch { method, expected, actual } => {
+                write!(f, "{} has type encoding {}, but the hook has {}", method, actual, expected)
+            }
+            HookError::AddMethodFailed { method } => write!(f, "failed to add an override of {}", method),
+            HookError::Failed { method } => write!(f, "failed to hook {}", method),
+        }
+    }
+}
+
+impl std::error::Error for HookError {}
+
+/// An installed method hook
+///
+/// Holds the implementation that was replaced, as the same fn type as the
+/// replacement. Dropping the handle leaves the hook installed; call
+/// [`remove`](Self::remove) to restore the original.
+pub struct Hook<F: MethodImplementation> {
+    class: Class,
+    sel: SEL,
+    original: F,
+    replacement: F,
+}
+
+// Classes, selectors and IMPs are process-wide and never freed
+unsafe impl<F: MethodImplementation> Send for Hook<F> {}
+unsafe impl<F: MethodImplementation> Sync for Hook<F> {}
+
+impl<F: MethodImplementation> Hook<F> {
+    /// Replace the instance method `selector` of `class` with `replacement`
+    ///
+    /// # Safety
+    ///
+    /// `class` must be nil or a valid class, and `replacement` must be safe
+    /// to call wherever the method is, including from other threads.
+    pub unsafe fn instance_method(class: Class, selector: &CStr, replacement: F) -> Result<Self, HookError> {
+        unsafe { Self::install(class, selector, replacement, '-') }
+    }
+
+    /// Replace the class method `selector` of `class` with `replacement`
+    ///
+    /// # Safety
+    ///
+    /// As for [`instance_method`](Self::instance_method).
+    pub unsafe fn class_method(class: Class, selector: &CStr, replacement: F) -> Result<Self, HookError> {
+        if class.is_null() {
+            return Err(HookError::NilClass);
+        }
+        // Class methods are the instance methods of the metaclass
+        let metaclass = unsafe { object_getClass(class.cast()) };
+        unsafe { Self::install(metaclass, selector, replacement, '+') }
+    }
+
+    unsafe fn install(class: Class, selector: &CStr, replacement: F, kind: char) -> Result<Self, HookError> {
+        unsafe {
+            let (sel, method) = checked_method::<F>(class, selector, kind)?;
+            let Some(original) = method_imp(method) else {
+                return Err(HookError::MethodNotFound { method: describe(class, selector, kind) });
+            };
+            let types = method_getTypeEncoding(method);
+
+            let original = if overrides(class, sel, method) {
+                match method_setImplementation(method, replacement.imp()) {
+                    // Another thread may have replaced it since method_imp
+                    Some(current) => current,
+                    None => original,
+                }
+            } else if class_addMethod(class, sel, replacement.imp(), types) {
+                original
+            } else {
+                return Err(HookError::AddMethodFailed { method: describe(class, selector, kind) });
+            };
+
+            Ok(Hook {
+                class,
+                sel,
+                original: F::from_imp(original),
+                replacement,
+            })
+        }
+    }
+
+    /// The implementation that was replaced
+    #[inline]
+    pub fn original(&self) -> F {
+        self.original
+    }
+
+    /// The class the hook was installed on; the metaclass for class methods
+    pub fn class(&self) -> Class {
+        self.class
+    }
+
+    /// The hooked selector
+    pub fn selector(&self) -> SEL {
+        self.sel
+    }
+
+    /// Restore the original implementation
+    ///
+    /// Returns `false` and leaves the method alone if it was replaced again
+    /// after this hook, since restoring would drop the later hook. A method
+    /// that was added to the class keeps its entry, pointing at the
+    /// inherited implementation captured when hooking.
+    ///
+    /// # Safety
+    ///
+    /// No code may call [`original`](Self::original) of this hook afterwards
+    /// expecting the hook to be active.
+    pub unsafe fn remove(self) -> bool {
+        unsafe {
+            let method = class_getInstanceMethod(self.class, self.sel);
+            let current = method_imp(method).map(|imp| imp as usize);
+            if current != self.replacement.imp().map(|imp| imp as usize) {
+                return false;
+            }
+            method_setImplementation(method, self.original.imp());
+            true
+        }
+    }
+}
+
+impl<F: MethodImplementation> fmt::Debug for Hook<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (class, sel) = unsafe {
+            (
+                CStr::from_ptr(class_getName(self.class)),
+                CStr::from_ptr(crate::objc::sel_getName(self.sel)),
+            )
+        };
+        f.debug_struct("Hook").field("class", &class).field("selector", &sel).finish()
+    }
+}
+
+/// A hook installed at most once, kept in a `static`
+///
+/// What [`#[hook]`](hook) stores the original implementation
+/// in. The class is looked up by name when the hook is installed, so it can
+/// be a class from a framework loaded after the tweak.
+pub struct HookCell<F: MethodImplementation> {
+    class: &'static CStr,
+    selector: &'static CStr,
+    class_method: bool,
+    hook: OnceLock<Hook<F>>,
+    installing: Mutex<()>,
+}
+
+impl<F: MethodImplementation> HookCell<F> {
+    /// A cell for the instance method, or with `class_method` the class
+    /// method, `selector` of the class named `class`
+    pub const fn new(class: &'static CStr, selector: &'static CStr, class_method: bool) -> Self {
+        HookCell {
+            class,
+            selector,
+            class_method,
+            hook: OnceLock::new(),
+            installing: Mutex::new(()),
+        }
+    }
+
+    /// Install `replacement`, unless the hook is already installed
+    ///
+    /// # Safety
+    ///
+    /// As for [`Hook::instance_method`].
+    pub unsafe fn install(&self, replacement: F) -> Result<(), HookError> {
+        let _installing = self.installing.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.hook.get().is_some() {
+            return Ok(());
+        }
+
+        let class = unsafe { objc_getClass(self.class.as_ptr()) };
+        if class.is_null() {
+            return Err(HookError::ClassNotFound {
+                class: self.class.to_string_lossy().into_owned(),
+            });
+        }
+        let hook = unsafe {
+            if self.class_method {
+                Hook::class_method(class, self.selector, replacement)?
+            } else {
+                Hook::instance_method(class, self.selector, replacement)?
+            }
+        };
+        let _ = self.hook.set(hook);
+        Ok(())
+    }
+
+    /// The installed hook
+    pub fn get(&self) -> Option<&Hook<F>> {
+        self.hook.get()
+    }
+
+    /// The implementation the hook replaced
+    ///
+    /// # Panics
+    ///
+    /// If the hook isn't installed; the replacement can only run once it is.
+    #[inline]
+    pub fn original(&self) -> F {
+        match self.hook.get() {
+            Some(hook) => hook.original(),
+            None => panic!(
+                "hook of {}[{} {}] called before it was installed",
+                if self.class_method { '+' } else { '-' },
+                self.class.to_string_lossy(),
+                self.selector.to_string_lossy()
+            ),
+        }
+    }
+}
+
+/// An `extern "C"` fn that C function hooks can be installed on
+///
+/// Implemented for `extern "C"` and `unsafe extern "C"` fns of up to 10
+/// arguments, so a hook and the original it returns share one fn type.
+///
+/// # Safety
+///
+/// `as_ptr` and `from_ptr` must round-trip the fn's address.
+pub unsafe trait FunctionPointer: Copy {
+    /// The fn's address
+    fn as_ptr(self) -> *mut c_void;
+
+    /// The fn at `ptr`
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and point to a fn of this signature.
+    unsafe fn from_ptr(ptr: *mut c_void) -> Self;
+}
+
+macro_rules! function_pointer {
+    ($($arg:ident),*) => {
+        function_pointer!(@impl unsafe extern "C" fn($($arg),*) -> R; $($arg),*);
+        function_pointer!(@impl extern "C" fn($($arg),*) -> R; $($arg),*);
+    };
+    (@impl $fn:ty; $($arg:ident),*) => {
+        unsafe impl<R $(, $arg)*> FunctionPointer for $fn {
+            #[inline]
+            fn as_ptr(self) -> *mut c_void {
+                self as *mut c_void
+            }
+
+            #[inline]
+            unsafe fn from_ptr(ptr: *mut c_void) -> Self {
+                unsafe { mem::transmute::<*mut c_void, $fn>(ptr) }
+            }
+        }
+    };
+}
+
+function_pointer!();
+function_pointer!(A);
+function_pointer!(A, B);
+function_pointer!(A, B, C);
+function_pointer!(A, B, C, D);
+function_pointer!(A, B, C, D, E);
+function_pointer!(A, B, C, D, E, F);
+function_pointer!(A, B, C, D, E, F, G);
+function_pointer!(A, B, C, D, E, F, G, H);
+function_pointer!(A, B, C, D, E, F, G, H, I);
+function_pointer!(A, B, C, D, E, F, G, H, I, J);
+
+/// Find `selector` on `class` and check `F` against its type encoding
+///
+/// `class` is the metaclass for class methods, with `kind` `'+'`.
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class.
+pub(crate) unsafe fn checked_method<F: MethodImplementation>(
+    class: Class,
+    selector: &CStr,
+    kind: char,
+) -> Result<(SEL, Method), HookError> {
+    if class.is_null() {
+        return Err(HookError::NilClass);
+    }
+
+    unsafe {
+        let sel = sel_registerName(selector.as_ptr());
+        let method = class_getInstanceMethod(class, sel);
+        if method.is_null() {
+            return Err(HookError::MethodNotFound { method: describe(class, selector, kind) });
+        }
+
+        let types = method_getTypeEncoding(method);
+        if !types.is_null() {
+            let types = CStr::from_ptr(types).to_string_lossy();
+            let expected = F::encoding();
+            if !signatures_match(&expected.to_string_lossy(), &types) {
+                return Err(HookError::SignatureMismatch {
+                    method: describe(class, selector, kind),
+                    expected: expected.to_string_lossy().into_owned(),
+                    actual: types.into_owned(),
+                });
+            }
+        }
+        Ok((sel, method))
+    }
+}
+
+/// `-[Class selector]` or `+[Class selector]`
+pub(crate) unsafe fn describe(class: Class, selector: &CStr, kind: char) -> String {
+    let class_name = unsafe { CStr::from_ptr(class_getName(class)) }.to_string_lossy();
+    format!("{}[{} {}]", kind, class_name, selector.to_string_lossy())
+}
+
+/// The IMP of `method`, or `None` for a null method
+unsafe fn method_imp(method: Method) -> IMP {
+    if method.is_null() {
+        return None;
+    }
+    unsafe { method_getImplementation(method) }
+}
+
+/// Whether `class` implements `method` itself rather than inheriting it
+unsafe fn overrides(class: Class, sel: SEL, method: Method) -> bool {
+    unsafe {
+        let superclass = class_getSuperclass(class);
+        superclass.is_null() || class_getInstanceMethod(superclass, sel) != method
+    }
+}
+
+/// Compare a Rust fn's method encoding against a method's type encoding
+///
+/// The receiver is skipped: a hook on a class method takes a `Class` but the
+/// runtime encodes every receiver as `@`.
+fn signatures_match(expected: &str, actual: &str) -> bool {
+    let expected = split_method_encoding(expected);
+    let actual = split_method_encoding(actual);
+    expected.len() == actual.len()
+        && expected
+            .iter()
+            .zip(&actual)
+            .enumerate()
+            .all(|(i, (e, a))| i == 1 || encodings_match(e, a))
+}
@@ -0,0 +1,342 @@
+//! Inspecting the classes, methods, ivars, properties and protocols loaded in the process
+//!
+//! The runtime's `*_copy*List` functions return `malloc`ed arrays that the
+//! caller frees. These wrappers return iterators that own the array, free it
+//! when dropped and yield owned Rust structs:
+//!
+//! ```ignore
+//! use ios_sys::introspect;
+//!
+//! for class in introspect::classes() {
+//!     let name = unsafe { introspect::class_name(class) };
+//!     for method in unsafe { introspect::methods(class) } {
+//!         println!("-[{} {}] {}", name, method.name, method.types.as_deref().unwrap_or("?"));
+//!     }
+//! }
+//! ```
+
+mod property;
+
+use core::ffi::{CStr, c_char, c_uint};
+use core::fmt;
+use core::ptr;
+
+pub use property::PropertyInfo;
+
+use crate::objc::{
+    BOOL, Class, IMP, Ivar, Method, Protocol, SEL, class_copyIvarList, class_copyMethodList, class_copyPropertyList,
+    class_copyProtocolList, class_getName, class_getSuperclass, ivar_getName, ivar_getOffset, ivar_getTypeEncoding,
+    method_getImplementation, method_getName, method_getTypeEncoding, objc_copyClassList, objc_copyClassNamesForImage,
+    objc_copyImageNames, objc_copyProtocolList, objc_method_description, objc_property_t, object_getClass,
+    property_getAttributes, property_getName, protocol_copyMethodDescriptionList, protocol_copyPropertyList,
+    protocol_copyProtocolList, protocol_getName, sel_getName,
+};
+
+/// An array returned by one of the runtime's `*_copy*List` functions
+///
+/// Yields the elements by value and frees the array when dropped.
+pub struct CopiedList<T: Copy> {
+    ptr: *mut T,
+    len: usize,
+    next: usize,
+}
+
+impl<T: Copy> CopiedList<T> {
+    /// Take ownership of an array of `len` elements, or of nothing if `ptr` is null
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a `malloc`ed array of `len` initialized elements.
+    pub unsafe fn from_raw(ptr: *mut T, len: usize) -> Self {
+        CopiedList {
+            ptr,
+            len: if ptr.is_null() { 0 } else { len },
+            next: 0,
+        }
+    }
+
+    /// Call a `*_copy*List` function and take ownership of the result
+    unsafe fn copy(f: impl FnOnce(*mut c_uint) -> *mut T) -> Self {
+        let mut count: c_uint = 0;
+        let ptr = f(&mut count);
+        unsafe { Self::from_raw(ptr, count as usize) }
+    }
+}
+
+impl<T: Copy> Iterator for CopiedList<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.next >= self.len {
+            return None;
+        }
+        let item = unsafe { *self.ptr.add(self.next) };
+        self.next += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: Copy> ExactSizeIterator for CopiedList<T> {}
+
+impl<T: Copy> Drop for CopiedList<T> {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { crate::free(self.ptr.cast()) };
+        }
+    }
+}
+
+impl<T: Copy> fmt::Debug for CopiedList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CopiedList")
+            .field("len", &self.len)
+            .field("next", &self.next)
+            .finish()
+    }
+}
+
+/// A copy of a C string owned by the runtime
+unsafe fn string(ptr: *const c_char) -> Option<String> {
+    (!ptr.is_null()).then(|| unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+}
+
+/// A method of a class
+#[derive(Debug, Clone)]
+pub struct MethodInfo {
+    /// The selector
+    pub selector: SEL,
+    /// The selector's name, e.g. `initWithFrame:`
+    pub name: String,
+    /// The type encoding, e.g. `@48@0:8{CGRect={CGPoint=dd}{CGSize=dd}}16`
+    pub types: Option<String>,
+    /// The implementation
+    pub imp: IMP,
+}
+
+impl MethodInfo {
+    /// # Safety
+    ///
+    /// `method` must be a valid method.
+    pub unsafe fn from_raw(method: Method) -> Self {
+        unsafe {
+            let selector = method_getName(method);
+            MethodInfo {
+                selector,
+                name: string(sel_getName(selector)).unwrap_or_default(),
+                types: string(method_getTypeEncoding(method)),
+                imp: method_getImplementation(method),
+            }
+        }
+    }
+}
+
+/// An instance variable of a class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IvarInfo {
+    /// The name, e.g. `_frame`
+    pub name: String,
+    /// The type encoding; may be missing or empty for Swift ivars
+    pub types: Option<String>,
+    /// Offset from the start of the object in bytes
+    pub offset: isize,
+}
+
+impl IvarInfo {
+    /// # Safety
+    ///
+    /// `ivar` must be a valid ivar.
+    pub unsafe fn from_raw(ivar: Ivar) -> Self {
+        unsafe {
+            IvarInfo {
+                name: string(ivar_getName(ivar)).unwrap_or_default(),
+                types: string(ivar_getTypeEncoding(ivar)),
+                offset: ivar_getOffset(ivar),
+            }
+        }
+    }
+}
+
+impl PropertyInfo {
+    /// # Safety
+    ///
+    /// `property` must be a valid property.
+    pub unsafe fn from_raw(property: objc_property_t) -> Self {
+        unsafe {
+            PropertyInfo {
+                name: string(property_getName(property)).unwrap_or_default(),
+                attributes: string(property_getAttributes(property)).unwrap_or_default(),
+            }
+        }
+    }
+}
+
+/// A protocol
+#[derive(Debug, Clone)]
+pub struct ProtocolInfo {
+    /// The protocol object
+    pub protocol: *mut Protocol,
+    /// The name, e.g. `UITableViewDelegate`
+    pub name: String,
+}
+
+impl ProtocolInfo {
+    /// # Safety
+    ///
+    /// `protocol` must be a valid protocol.
+    pub unsafe fn from_raw(protocol: *mut Protocol) -> Self {
+        ProtocolInfo {
+            protocol,
+            name: unsafe { string(protocol_getName(protocol)) }.unwrap_or_default(),
+        }
+    }
+
+    /// The methods the protocol declares, required or optional, instance or class
+    pub fn methods(&self, required: bool, instance: bool) -> impl ExactSizeIterator<Item = MethodDescription> + use<> {
+        let protocol = self.protocol;
+        unsafe {
+            CopiedList::copy(|count| {
+                protocol_copyMethodDescriptionList(protocol, required as BOOL, instance as BOOL, count)
+            })
+        }
+        .map(|description| unsafe { MethodDescription::from_raw(description) })
+    }
+
+    /// The properties the protocol declares
+    pub fn properties(&self) -> impl ExactSizeIterator<Item = PropertyInfo> + use<> {
+        let protocol = self.protocol;
+        unsafe { CopiedList::copy(|count| protocol_copyPropertyList(protocol, count)) }
+            .map(|property| unsafe { PropertyInfo::from_raw(property) })
+    }
+
+    /// The protocols the protocol adopts
+    pub fn protocols(&self) -> impl ExactSizeIterator<Item = ProtocolInfo> + use<> {
+        let protocol = self.protocol;
+        unsafe { CopiedList::copy(|count| protocol_copyProtocolList(protocol, count)) }
+            .map(|protocol| unsafe { ProtocolInfo::from_raw(protocol) })
+    }
+}
+
+/// A method declared by a protocol
+#[derive(Debug, Clone)]
+pub struct MethodDescription {
+    /// The selector
+    pub selector: SEL,
+    /// The selector's name
+    pub name: String,
+    /// The type encoding
+    pub types: Option<String>,
+}
+
+impl MethodDescription {
+    /// # Safety
+    ///
+    /// `description` must come from the runtime.
+    pub unsafe fn from_raw(description: objc_method_description) -> Self {
+        unsafe {
+            MethodDescription {
+                selector: description.name,
+                name: string(sel_getName(description.name)).unwrap_or_default(),
+                types: string(description.types),
+            }
+        }
+    }
+}
+
+/// Every registered class
+pub fn classes() -> CopiedList<Class> {
+    unsafe { CopiedList::copy(|count| objc_copyClassList(count)) }
+}
+
+/// The name of `class`
+///
+/// # Safety
+///
+/// `class` must be a valid class.
+pub unsafe fn class_name(class: Class) -> String {
+    unsafe { string(class_getName(class)) }.unwrap_or_default()
+}
+
+/// The superclass of `class`, `None` for a root class
+///
+/// # Safety
+///
+/// `class` must be a valid class.
+pub unsafe fn superclass(class: Class) -> Option<Class> {
+    let superclass = unsafe { class_getSuperclass(class) };
+    (!superclass.is_null()).then_some(superclass)
+}
+
+/// The instance methods `class` implements itself, not those it inherits
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class.
+pub unsafe fn methods(class: Class) -> impl ExactSizeIterator<Item = MethodInfo> {
+    unsafe { CopiedList::copy(|count| class_copyMethodList(class, count)) }
+        .map(|method| unsafe { MethodInfo::from_raw(method) })
+}
+
+/// The class methods `class` implements itself
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class.
+pub unsafe fn class_methods(class: Class) -> impl ExactSizeIterator<Item = MethodInfo> {
+    let metaclass = if class.is_null() {
+        ptr::null_mut()
+    } else {
+        unsafe { object_getClass(class.cast()) }
+    };
+    unsafe { methods(metaclass) }
+}
+
+/// The instance variables `class` declares, not those of its superclasses
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class.
+pub unsafe fn ivars(class: Class) -> impl ExactSizeIterator<Item = IvarInfo> {
+    unsafe { CopiedList::copy(|count| class_copyIvarList(class, count)) }.map(|ivar| unsafe { IvarInfo::from_raw(ivar) })
+}
+
+/// The properties `class` declares, not those of its superclasses
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class.
+pub unsafe fn properties(class: Class) -> impl ExactSizeIterator<Item = PropertyInfo> {
+    unsafe { CopiedList::copy(|count| class_copyPropertyList(class, count)) }
+        .map(|property| unsafe { PropertyInfo::from_raw(property) })
+}
+
+/// The protocols `class` adopts, not those of its superclasses
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class.
+pub unsafe fn protocols(class: Class) -> impl ExactSizeIterator<Item = ProtocolInfo> {
+    unsafe { CopiedList::copy(|count| class_copyProtocolList(class, count)) }
+        .map(|protocol| unsafe { ProtocolInfo::from_raw(protocol) })
+}
+
+/// Every registered protocol
+pub fn all_protocols() -> impl ExactSizeIterator<Item = ProtocolInfo> {
+    unsafe { CopiedList::copy(|count| objc_copyProtocolList(count)) }
+        .map(|protocol| unsafe { ProtocolInfo::from_raw(protocol) })
+}
+
+/// The paths of the loaded images containing Objective-C metadata
+pub fn image_names() -> impl ExactSizeIterator<Item = String> {
+    unsafe { CopiedList::copy(|count| objc_copyImageNames(count)) }.map(|name| unsafe { string(name) }.unwrap_or_default())
+}
+
+/// The names of the classes defined in the image at `image`, a path from [`image_names`]
+pub fn class_names_for_image(image: &CStr) -> impl ExactSizeIterator<Item = String> + use<> {
+    unsafe { CopiedList::copy(|count| objc_copyClassNamesForImage(image.as_ptr(), count)) }
+        .map(|name| unsafe { string(name) }.unwrap_or_default())
+}
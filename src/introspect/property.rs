@@ -0,0 +1,58 @@
+//! Property attribute strings
+//!
+//! Also compiled into the build script's tests, so it only depends on `std`.
+
+/// A declared property of a class or protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyInfo {
+    /// The name, e.g. `frame`
+    pub name: String,
+    /// The attribute string, e.g. `T{CGRect={CGPoint=dd}{CGSize=dd}},N,V_frame`
+    pub attributes: String,
+}
+
+impl PropertyInfo {
+    /// The attributes as `(code, value)` pairs, e.g. `('T', "@\"NSString\"")`, `('C', "")`
+    pub fn attribute_list(&self) -> Vec<(char, &str)> {
+        let mut attributes = Vec::new();
+        let mut start = 0;
+        let mut in_quotes = false;
+        for (i, c) in self.attributes.char_indices().chain([(self.attributes.len(), ',')]) {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                ',' if !in_quotes => {
+                    let attribute = &self.attributes[start..i];
+                    let mut chars = attribute.chars();
+                    if let Some(code) = chars.next() {
+                        attributes.push((code, chars.as_str()));
+                    }
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        attributes
+    }
+
+    /// The value of the attribute `code`, if present
+    pub fn attribute(&self, code: char) -> Option<&str> {
+        self.attribute_list()
+            .into_iter()
+            .find_map(|(c, value)| (c == code).then_some(value))
+    }
+
+    /// The type encoding of the property (`T`)
+    pub fn type_encoding(&self) -> Option<&str> {
+        self.attribute('T')
+    }
+
+    /// The backing ivar (`V`), if synthesized
+    pub fn ivar(&self) -> Option<&str> {
+        self.attribute('V').filter(|ivar| !ivar.is_empty())
+    }
+
+    /// Whether the property is `readonly` (`R`)
+    pub fn is_readonly(&self) -> bool {
+        self.attribute('R').is_some()
+    }
+}
@@ -31,6 +31,9 @@
 //! on any platform. Enable the `runtime` feature to link against actual
 //! iOS frameworks.
 
+// Lets the derive macros refer to `::ios_sys` from inside this crate too
+extern crate self as ios_sys;
+
 // Include bindings generated by frameworks.rs from SDK headers
 
 pub mod objc {
@@ -62,6 +65,14 @@ pub mod foundation {
     include!(concat!(env!("OUT_DIR"), "/foundation.rs"));
 }
 
+pub mod classes {
+    //! Objective-C classes of frameworks other than Foundation (auto-generated from class dump)
+    //!
+    //! One module per framework, each gated by that framework's feature
+    //! (e.g. `classes::uikitcore` with the `uikitcore` feature).
+    include!(concat!(env!("OUT_DIR"), "/objc_classes.rs"));
+}
+
 pub mod corefoundation {
     //! CoreFoundation framework bindings (auto-generated from CoreFoundation/CoreFoundation.h)
     #![allow(clippy::all)]
@@ -76,6 +87,32 @@ pub mod coregraphics {
     include!(concat!(env!("OUT_DIR"), "/coregraphics.rs"));
 }
 
+// ============================================================================
+// Runtime Helpers
+// ============================================================================
+
+pub mod autorelease;
+pub mod block;
+pub mod cache;
+pub mod class_dump;
+#[cfg(feature = "codesign")]
+pub mod codesign;
+pub mod declare;
+pub mod encode;
+pub mod hook;
+pub mod introspect;
+#[cfg(feature = "libhooker")]
+pub mod libhooker;
+pub mod macho;
+pub mod message;
+pub mod nserror;
+pub mod rc;
+pub mod rebind;
+#[cfg(feature = "substrate")]
+pub mod substrate;
+pub mod symbols;
+pub mod tweak;
+
 // Re-export commonly used types for convenience
 pub use objc::{Class, Ivar, Method, SEL};
 
@@ -0,0 +1,229 @@
+//! libhooker and libblackjack hooking API
+//!
+//! Typed wrappers over `LHHookFunctions`, `LBHookMessage` and the image
+//! lookup functions, with the raw declarations (generated from the Theos TBD
+//! stubs) in [`ffi`]. Enabled by the `libhooker` feature; with `runtime` the
+//! build links the stubs found under `$THEOS`. ElleKit provides the same
+//! symbols, so tweaks built against this run on either.
+//!
+//! Unlike Substrate, libhooker can install several function hooks at once,
+//! pausing other threads only once: see [`hook_functions`].
+
+use core::ffi::{CStr, c_int, c_void};
+use core::fmt;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use crate::declare::MethodImplementation;
+use crate::hook::{FunctionPointer, HookError, checked_method, describe};
+use crate::objc::{Class, object_getClass};
+
+pub mod ffi {
+    //! libhooker and libblackjack declarations (auto-generated from the Theos TBD stubs)
+    #![allow(clippy::all)]
+    #![allow(warnings)]
+    include!(concat!(env!("OUT_DIR"), "/libhooker.rs"));
+}
+
+/// Why libhooker couldn't install a hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The class doesn't implement the selector
+    SelectorNotFound,
+    /// The function is too short to patch
+    ShortFunction,
+    /// The function starts with an instruction that can't be relocated
+    BadInstructionAtStart,
+    /// Changing the protection of the code pages failed
+    Vm,
+    /// A symbol couldn't be found
+    NoSymbol,
+    /// A code libhooker added after these bindings
+    Unknown(c_int),
+    /// The method lookup or signature check failed before calling libhooker
+    Method(HookError),
+}
+
+impl Error {
+    /// The error for a `LIBHOOKER_ERR`, or `None` for `LIBHOOKER_OK`
+    pub fn from_code(code: ffi::LIBHOOKER_ERR) -> Option<Self> {
+        match code {
+            ffi::LIBHOOKER_OK => None,
+            ffi::LIBHOOKER_ERR_SELECTOR_NOT_FOUND => Some(Error::SelectorNotFound),
+            ffi::LIBHOOKER_ERR_SHORT_FUNC => Some(Error::ShortFunction),
+            ffi::LIBHOOKER_ERR_BAD_INSN_AT_START => Some(Error::BadInstructionAtStart),
+            ffi::LIBHOOKER_ERR_VM => Some(Error::Vm),
+            ffi::LIBHOOKER_ERR_NO_SYMBOL => Some(Error::NoSymbol),
+            code => Some(Error::Unknown(code)),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::SelectorNotFound => write!(f, "selector not found"),
+            Error::ShortFunction => write!(f, "function too short to hook"),
+            Error::BadInstructionAtStart => write!(f, "function starts with an instruction that can't be relocated"),
+            Error::Vm => write!(f, "failed to change the protection of the code"),
+            Error::NoSymbol => write!(f, "symbol not found"),
+            Error::Unknown(code) => write!(f, "libhooker error {}", code),
+            Error::Method(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<HookError> for Error {
+    fn from(err: HookError) -> Self {
+        Error::Method(err)
+    }
+}
+
+/// One function hook for [`hook_functions`]
+#[derive(Debug)]
+pub struct FunctionHook<F: FunctionPointer> {
+    target: F,
+    replacement: F,
+    original: Option<F>,
+}
+
+impl<F: FunctionPointer> FunctionHook<F> {
+    /// A hook redirecting calls to `target` to `replacement`
+    pub fn new(target: F, replacement: F) -> Self {
+        FunctionHook {
+            target,
+            replacement,
+            original: None,
+        }
+    }
+
+    /// A fn running the original code of the target, once installed
+    pub fn original(&self) -> Option<F> {
+        self.original
+    }
+}
+
+/// Install `hooks` together
+///
+/// Returns how many were installed; the installed ones have their
+/// [`original`](FunctionHook::original) set.
+///
+/// # Safety
+///
+/// Each target must be a fn that can be patched, and each replacement must
+/// be safe to call everywhere its target is, from any thread.
+pub unsafe fn hook_functions<F: FunctionPointer>(hooks: &mut [FunctionHook<F>]) -> usize {
+    let mut originals = vec![ptr::null_mut::<c_void>(); hooks.len()];
+    let raw: Vec<ffi::LHFunctionHook> = hooks
+        .iter()
+        .zip(&mut originals)
+        .map(|(hook, original)| ffi::LHFunctionHook {
+            function: hook.target.as_ptr(),
+            replacement: hook.replacement.as_ptr(),
+            oldptr: ptr::from_mut(original).cast(),
+            options: ptr::null_mut(),
+        })
+        .collect();
+
+    let installed = unsafe { ffi::LHHookFunctions(raw.as_ptr(), raw.len() as c_int) };
+    for (hook, original) in hooks.iter_mut().zip(originals) {
+        hook.original = (!original.is_null()).then(|| unsafe { F::from_ptr(original) });
+    }
+    installed.max(0) as usize
+}
+
+/// Redirect calls to `target` to `replacement`
+///
+/// Returns a fn that runs the original code of `target`, or `None` if
+/// libhooker couldn't hook it.
+///
+/// # Safety
+///
+/// As for [`hook_functions`].
+pub unsafe fn hook_function<F: FunctionPointer>(target: F, replacement: F) -> Option<F> {
+    let mut hooks = [FunctionHook::new(target, replacement)];
+    unsafe { hook_functions(&mut hooks) };
+    hooks[0].original
+}
+
+/// Replace the instance method `selector` of `class` with `replacement`
+///
+/// The signature of `replacement` is checked against the method's type
+/// encoding first. Returns the original implementation.
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class, and `replacement` must be safe to
+/// call wherever the method is, from any thread.
+pub unsafe fn hook_message<F: MethodImplementation>(class: Class, selector: &CStr, replacement: F) -> Result<F, Error> {
+    unsafe { hook_message_on(class, selector, replacement, '-') }
+}
+
+/// Replace the class method `selector` of `class` with `replacement`
+///
+/// # Safety
+///
+/// As for [`hook_message`].
+pub unsafe fn hook_class_message<F: MethodImplementation>(class: Class, selector: &CStr, replacement: F) -> Result<F, Error> {
+    if class.is_null() {
+        return Err(HookError::NilClass.into());
+    }
+    // Class methods are the instance methods of the metaclass
+    unsafe { hook_message_on(object_getClass(class.cast()), selector, replacement, '+') }
+}
+
+unsafe fn hook_message_on<F: MethodImplementation>(
+    class: Class,
+    selector: &CStr,
+    replacement: F,
+    kind: char,
+) -> Result<F, Error> {
+    unsafe {
+        let (sel, _) = checked_method::<F>(class, selector, kind)?;
+        let Some(imp) = replacement.imp() else {
+            unreachable!("fn pointers are non-null");
+        };
+        let mut original: *mut c_void = ptr::null_mut();
+        let code = ffi::LBHookMessage(class, sel, imp as *mut c_void, &mut original);
+        if let Some(err) = Error::from_code(code) {
+            return Err(err);
+        }
+        if original.is_null() {
+            return Err(HookError::Failed { method: describe(class, selector, kind) }.into());
+        }
+        Ok(F::from_imp(mem::transmute::<*mut c_void, unsafe extern "C" fn()>(original)))
+    }
+}
+
+/// A dyld image opened for symbol lookup
+#[derive(Debug)]
+pub struct Image(NonNull<c_void>);
+
+impl Image {
+    /// Open the image at `path`, which must already be loaded
+    pub fn open(path: &CStr) -> Option<Self> {
+        NonNull::new(unsafe { ffi::LHOpenImage(path.as_ptr()) }).map(Image)
+    }
+
+    /// Look up a symbol, including non-exported ones, as a `F`
+    ///
+    /// `name` is the mangled name, with the leading underscore of C symbols.
+    ///
+    /// # Safety
+    ///
+    /// The symbol must be a fn of type `F`.
+    pub unsafe fn find_symbol<F: FunctionPointer>(&self, name: &CStr) -> Option<F> {
+        let names = [name.as_ptr()];
+        let mut symbols = [ptr::null_mut()];
+        let found = unsafe { ffi::LHFindSymbols(self.0.as_ptr(), names.as_ptr(), symbols.as_mut_ptr(), 1) };
+        (found && !symbols[0].is_null()).then(|| unsafe { F::from_ptr(symbols[0]) })
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe { ffi::LHCloseImage(self.0.as_ptr()) }
+    }
+}
@@ -0,0 +1,1124 @@
+//! Reading 64-bit Mach-O images
+//!
+//! Everything works on byte slices, so the same code reads a file on the
+//! host and an image mapped in this process. Only the [`Layout`] differs: a
+//! file is addressed by file offsets, a loaded image by vm offsets from its
+//! `mach_header`.
+//!
+//! ```ignore
+//! use ios_sys::macho::{self, MachO};
+//!
+//! let data = std::fs::read("SpringBoard")?;
+//! let image = macho::arm64_slice(&data).and_then(MachO::parse).expect("not an arm64 Mach-O");
+//! for symbol in image.symbols().iter().filter(|symbol| symbol.is_exported()) {
+//!     println!("{:#x} {}", symbol.value, symbol.name);
+//! }
+//! ```
+//!
+//! Covers fat and thin files, load commands, segments and sections, the
+//! `LC_SYMTAB` and `LC_DYSYMTAB` tables, chained fixups and the exports trie.
+//! Nothing is validated beyond bounds checks: malformed input gives `None` or
+//! fewer entries, never a panic.
+
+use std::collections::{HashMap, HashSet};
+
+pub const MH_MAGIC_64: u32 = 0xfeed_facf;
+pub const FAT_MAGIC: u32 = 0xcafe_babe;
+pub const FAT_MAGIC_64: u32 = 0xcafe_babf;
+
+pub const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+pub const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+
+// mach_header_64 filetype
+pub const MH_EXECUTE: u32 = 0x2;
+pub const MH_DYLIB: u32 = 0x6;
+pub const MH_BUNDLE: u32 = 0x8;
+
+pub const LC_SYMTAB: u32 = 0x2;
+pub const LC_DYSYMTAB: u32 = 0xb;
+pub const LC_LOAD_DYLIB: u32 = 0xc;
+pub const LC_ID_DYLIB: u32 = 0xd;
+pub const LC_SEGMENT_64: u32 = 0x19;
+pub const LC_UUID: u32 = 0x1b;
+pub const LC_CODE_SIGNATURE: u32 = 0x1d;
+pub const LC_LAZY_LOAD_DYLIB: u32 = 0x20;
+pub const LC_DYLD_INFO: u32 = 0x22;
+pub const LC_LOAD_WEAK_DYLIB: u32 = 0x8000_0018;
+pub const LC_REEXPORT_DYLIB: u32 = 0x8000_001f;
+pub const LC_DYLD_INFO_ONLY: u32 = 0x8000_0022;
+pub const LC_LOAD_UPWARD_DYLIB: u32 = 0x8000_0023;
+pub const LC_MAIN: u32 = 0x8000_0028;
+pub const LC_DYLD_EXPORTS_TRIE: u32 = 0x8000_0033;
+pub const LC_DYLD_CHAINED_FIXUPS: u32 = 0x8000_0034;
+
+// nlist_64 n_type bits
+pub const N_STAB: u8 = 0xe0;
+pub const N_TYPE: u8 = 0x0e;
+pub const N_EXT: u8 = 0x01;
+pub const N_UNDF: u8 = 0x00;
+pub const N_SECT: u8 = 0x0e;
+
+// Section types, the low byte of section_64 flags
+pub const SECTION_TYPE: u32 = 0xff;
+pub const S_NON_LAZY_SYMBOL_POINTERS: u32 = 0x6;
+pub const S_LAZY_SYMBOL_POINTERS: u32 = 0x7;
+
+// Special indirect symbol table entries
+pub const INDIRECT_SYMBOL_LOCAL: u32 = 0x8000_0000;
+pub const INDIRECT_SYMBOL_ABS: u32 = 0x4000_0000;
+
+// Exports trie flags
+pub const EXPORT_SYMBOL_FLAGS_REEXPORT: u64 = 0x08;
+pub const EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER: u64 = 0x10;
+
+// Chained fixup pointer formats (`DYLD_CHAINED_PTR_*`)
+pub const DYLD_CHAINED_PTR_ARM64E: u16 = 1;
+pub const DYLD_CHAINED_PTR_64: u16 = 2;
+pub const DYLD_CHAINED_PTR_64_OFFSET: u16 = 6;
+pub const DYLD_CHAINED_PTR_ARM64E_USERLAND: u16 = 9;
+pub const DYLD_CHAINED_PTR_ARM64E_USERLAND24: u16 = 12;
+
+// Chained fixup import formats (`DYLD_CHAINED_IMPORT*`)
+pub const DYLD_CHAINED_IMPORT: u32 = 1;
+pub const DYLD_CHAINED_IMPORT_ADDEND: u32 = 2;
+pub const DYLD_CHAINED_IMPORT_ADDEND64: u32 = 3;
+
+/// Page start of a page without fixups
+const DYLD_CHAINED_PTR_START_NONE: u16 = 0xffff;
+
+/// `mach_header_64` size; load commands follow it
+const MACH_HEADER_64_SIZE: usize = 32;
+
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(offset..offset.checked_add(2)?)?.try_into().ok()?))
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset.checked_add(4)?)?.try_into().ok()?))
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(data.get(offset..offset.checked_add(8)?)?.try_into().ok()?))
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset.checked_add(4)?)?.try_into().ok()?))
+}
+
+fn read_u64_be(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(offset..offset.checked_add(8)?)?.try_into().ok()?))
+}
+
+/// Read a ULEB128 at `*offset`, advancing it
+pub(crate) fn read_uleb128(data: &[u8], offset: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*offset)?;
+        *offset += 1;
+        if shift < 64 {
+            value |= ((byte & 0x7f) as u64) << shift;
+        }
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+}
+
+/// The NUL-terminated string at the start of `data`
+pub(crate) fn c_str(data: &[u8]) -> Option<&str> {
+    let len = data.iter().position(|&b| b == 0)?;
+    std::str::from_utf8(&data[..len]).ok()
+}
+
+/// A fixed-size name field of a load command (`segname`, `sectname`), or a
+/// string table entry, whose NUL may be missing
+fn fixed_name(data: &[u8]) -> String {
+    let len = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..len]).into_owned()
+}
+
+/// One architecture of a fat binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FatArch {
+    pub cputype: u32,
+    pub cpusubtype: u32,
+    /// Offset of the slice in the file
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// The architectures of a fat binary, or `None` if `data` isn't one
+pub fn fat_arches(data: &[u8]) -> Option<Vec<FatArch>> {
+    let magic = read_u32_be(data, 0)?;
+    if magic != FAT_MAGIC && magic != FAT_MAGIC_64 {
+        return None;
+    }
+
+    let count = read_u32_be(data, 4)? as usize;
+    (0..count)
+        .map(|i| {
+            // fat_arch is 20 bytes, fat_arch_64 is 32
+            if magic == FAT_MAGIC {
+                let arch = 8 + i * 20;
+                Some(FatArch {
+                    cputype: read_u32_be(data, arch)?,
+                    cpusubtype: read_u32_be(data, arch + 4)?,
+                    offset: read_u32_be(data, arch + 8)? as u64,
+                    size: read_u32_be(data, arch + 12)? as u64,
+                })
+            } else {
+                let arch = 8 + i * 32;
+                Some(FatArch {
+                    cputype: read_u32_be(data, arch)?,
+                    cpusubtype: read_u32_be(data, arch + 4)?,
+                    offset: read_u64_be(data, arch + 8)?,
+                    size: read_u64_be(data, arch + 16)?,
+                })
+            }
+        })
+        .collect()
+}
+
+/// The `cputype` slice of a fat binary, or the binary itself if it is a thin one
+pub fn slice(data: &[u8], cputype: u32) -> Option<&[u8]> {
+    match fat_arches(data) {
+        Some(arches) => {
+            let arch = arches.into_iter().find(|arch| arch.cputype == cputype)?;
+            data.get(arch.offset as usize..arch.offset.checked_add(arch.size)? as usize)
+        }
+        None => (read_u32(data, 0)? == MH_MAGIC_64 && read_u32(data, 4)? == cputype).then_some(data),
+    }
+}
+
+/// The arm64 slice of a fat binary, or the binary itself if it is a thin one
+pub fn arm64_slice(data: &[u8]) -> Option<&[u8]> {
+    slice(data, CPU_TYPE_ARM64)
+}
+
+/// How an image's bytes are laid out in the slice it is read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// As in the file: `__LINKEDIT` data is at the file offsets the load
+    /// commands give
+    File,
+    /// As mapped by dyld: each segment is at its vm offset from the
+    /// `mach_header`, which starts the slice
+    Loaded,
+}
+
+/// The `mach_header_64` fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub cputype: u32,
+    pub cpusubtype: u32,
+    pub filetype: u32,
+    pub ncmds: u32,
+    pub sizeofcmds: u32,
+    pub flags: u32,
+}
+
+/// A load command, including its `cmd` and `cmdsize` fields
+#[derive(Debug, Clone, Copy)]
+pub struct LoadCommand<'a> {
+    pub cmd: u32,
+    /// Offset of the command from the start of the image
+    pub offset: usize,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub segment_name: String,
+    pub addr: u64,
+    pub size: u64,
+    /// File offset of the contents, 0 for zero-fill sections
+    pub offset: u32,
+    pub flags: u32,
+    /// For symbol pointer sections, the index of their first entry in the
+    /// indirect symbol table
+    pub reserved1: u32,
+    pub reserved2: u32,
+}
+
+impl Section {
+    /// The `S_*` type in the low byte of the flags
+    pub fn section_type(&self) -> u32 {
+        self.flags & SECTION_TYPE
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub name: String,
+    pub vmaddr: u64,
+    pub vmsize: u64,
+    pub fileoff: u64,
+    pub filesize: u64,
+    pub maxprot: u32,
+    pub initprot: u32,
+    pub sections: Vec<Section>,
+}
+
+/// `LC_SYMTAB`: where the nlist entries and their strings are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Symtab {
+    pub symoff: u32,
+    pub nsyms: u32,
+    pub stroff: u32,
+    pub strsize: u32,
+}
+
+/// `LC_DYSYMTAB`: how the symbol table is partitioned, and the indirect
+/// symbol table the symbol pointer sections index into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dysymtab {
+    pub ilocalsym: u32,
+    pub nlocalsym: u32,
+    pub iextdefsym: u32,
+    pub nextdefsym: u32,
+    pub iundefsym: u32,
+    pub nundefsym: u32,
+    pub indirectsymoff: u32,
+    pub nindirectsyms: u32,
+}
+
+/// A `__LINKEDIT` blob named by a load command: the exports trie, the chained
+/// fixups, the code signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkeditData {
+    /// File offset
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// An `nlist_64` entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub n_type: u8,
+    pub n_sect: u8,
+    pub n_desc: u16,
+    pub value: u64,
+}
+
+impl Symbol {
+    /// Whether the symbol is defined in a section and visible to other images
+    pub fn is_exported(&self) -> bool {
+        self.is_defined() && self.n_type & N_EXT != 0
+    }
+
+    /// Whether the symbol is defined in a section of this image
+    pub fn is_defined(&self) -> bool {
+        self.n_type & N_STAB == 0 && self.n_type & N_TYPE == N_SECT
+    }
+
+    /// Whether the symbol is imported from another image
+    pub fn is_undefined(&self) -> bool {
+        self.n_type & N_STAB == 0 && self.n_type & N_TYPE == N_UNDF && self.n_type & N_EXT != 0
+    }
+}
+
+/// What an exported name resolves to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// Offset of the symbol from the image base
+    Offset(u64),
+    /// The symbol of the dylib with `ordinal` named `name`, or the same
+    /// name if `None`
+    Reexport { ordinal: u64, name: Option<String> },
+    /// A stub that calls `resolver` the first time, both offsets from the base
+    StubAndResolver { stub: u64, resolver: u64 },
+}
+
+/// An entry of the exports trie
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    pub flags: u64,
+    pub target: ExportTarget,
+}
+
+impl Export {
+    /// Offset of the symbol from the image base, unless it is re-exported
+    pub fn offset(&self) -> Option<u64> {
+        match self.target {
+            ExportTarget::Offset(offset) | ExportTarget::StubAndResolver { stub: offset, .. } => Some(offset),
+            ExportTarget::Reexport { .. } => None,
+        }
+    }
+}
+
+/// A symbol bound by chained fixups
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub name: String,
+    /// 1-based index into [`MachO::dylibs`], or 0 (self), -1 (main
+    /// executable), -2 (flat lookup), -3 (weak lookup)
+    pub library_ordinal: i32,
+    pub weak: bool,
+    pub addend: i64,
+}
+
+/// `dyld_chained_starts_in_segment`: where the chains of one segment begin
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainedStarts {
+    /// Index of the segment in load command order
+    pub segment_index: usize,
+    pub page_size: u16,
+    pub pointer_format: u16,
+    /// Offset of the first fixup in each page, or `0xffff` for none
+    pub page_starts: Vec<u16>,
+}
+
+/// The contents of `LC_DYLD_CHAINED_FIXUPS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainedFixups {
+    /// By import ordinal, as bind fixups refer to them
+    pub imports: Vec<Import>,
+    pub starts: Vec<ChainedStarts>,
+}
+
+/// A pointer written as a chained fixup, decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainedPointer {
+    /// The unslid vm address the pointer is rebased to
+    Rebase(u64),
+    /// The import `ordinal`, plus `addend`
+    Bind { ordinal: u32, addend: i64 },
+}
+
+/// A fixup location and what it points to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fixup {
+    /// vm offset of the pointer from the image base
+    pub offset: u64,
+    pub pointer: ChainedPointer,
+}
+
+/// A pointer the image binds to an imported symbol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    /// The symbol, with its leading underscore
+    pub name: String,
+    /// vm offset of the pointer from the image base
+    pub offset: u64,
+}
+
+impl ChainedFixups {
+    /// Parse a `dyld_chained_fixups_header` and what it points to
+    pub fn parse(fixups: &[u8]) -> Option<Self> {
+        let starts_offset = read_u32(fixups, 4)? as usize;
+        let imports_offset = read_u32(fixups, 8)? as usize;
+        let symbols_offset = read_u32(fixups, 12)? as usize;
+        let imports_count = read_u32(fixups, 16)? as usize;
+        let imports_format = read_u32(fixups, 20)?;
+
+        // Only uncompressed symbol strings exist in practice
+        if read_u32(fixups, 24)? != 0 {
+            return None;
+        }
+
+        let mut names: HashMap<usize, String> = HashMap::new();
+        let mut name_at = |offset: usize| {
+            names
+                .entry(offset)
+                .or_insert_with(|| {
+                    let name = symbols_offset.checked_add(offset).and_then(|start| fixups.get(start..));
+                    name.and_then(c_str).unwrap_or("").to_string()
+                })
+                .clone()
+        };
+        let imports = (0..imports_count)
+            .map(|i| {
+                let import = match imports_format {
+                    DYLD_CHAINED_IMPORT | DYLD_CHAINED_IMPORT_ADDEND => {
+                        let size = if imports_format == DYLD_CHAINED_IMPORT { 4 } else { 8 };
+                        let entry = read_u32(fixups, imports_offset + i * size)?;
+                        let addend = match imports_format {
+                            DYLD_CHAINED_IMPORT => 0,
+                            _ => read_u32(fixups, imports_offset + i * size + 4)? as i32 as i64,
+                        };
+                        Import {
+                            name: name_at((entry >> 9) as usize),
+                            library_ordinal: (entry & 0xff) as u8 as i8 as i32,
+                            weak: entry & 0x100 != 0,
+                            addend,
+                        }
+                    }
+                    DYLD_CHAINED_IMPORT_ADDEND64 => {
+                        let entry = read_u64(fixups, imports_offset + i * 16)?;
+                        Import {
+                            name: name_at((entry >> 32) as usize),
+                            library_ordinal: (entry & 0xffff) as u16 as i16 as i32,
+                            weak: entry & 0x1_0000 != 0,
+                            addend: read_u64(fixups, imports_offset + i * 16 + 8)? as i64,
+                        }
+                    }
+                    _ => return None,
+                };
+                Some(import)
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        // dyld_chained_starts_in_image: seg_count, then one offset per segment
+        let seg_count = read_u32(fixups, starts_offset)? as usize;
+        let starts = (0..seg_count)
+            .map_while(|segment_index| read_u32(fixups, starts_offset + 4 + segment_index * 4))
+            .enumerate()
+            .filter(|&(_, info)| info != 0)
+            .filter_map(|(segment_index, info)| {
+                // dyld_chained_starts_in_segment: size, page_size, pointer_format,
+                // segment_offset, max_valid_pointer, page_count, page_start[]
+                let info = starts_offset + info as usize;
+                let page_count = read_u16(fixups, info + 20)? as usize;
+                Some(ChainedStarts {
+                    segment_index,
+                    page_size: read_u16(fixups, info + 4)?,
+                    pointer_format: read_u16(fixups, info + 6)?,
+                    page_starts: (0..page_count)
+                        .map(|page| read_u16(fixups, info + 22 + page * 2))
+                        .collect::<Option<_>>()?,
+                })
+            })
+            .collect();
+
+        Some(ChainedFixups { imports, starts })
+    }
+
+    /// The pointer format of the first segment with fixups
+    pub fn pointer_format(&self) -> Option<u16> {
+        self.starts.first().map(|starts| starts.pointer_format)
+    }
+}
+
+/// Sign-extend the low `bits` of `value`
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    ((value << (64 - bits)) as i64) >> (64 - bits)
+}
+
+/// Decode a pointer written as a chained fixup in `format`
+///
+/// `base` is the vm address of the image, which the `_OFFSET` and userland
+/// arm64e formats store their targets relative to.
+pub fn decode_chained_pointer(raw: u64, format: u16, base: u64) -> ChainedPointer {
+    let bits = |low: u32, count: u32| (raw >> low) & ((1 << count) - 1);
+
+    match format {
+        DYLD_CHAINED_PTR_ARM64E | DYLD_CHAINED_PTR_ARM64E_USERLAND | DYLD_CHAINED_PTR_ARM64E_USERLAND24 => {
+            let auth = bits(63, 1) != 0;
+            let bind = bits(62, 1) != 0;
+            let ordinal_bits = if format == DYLD_CHAINED_PTR_ARM64E_USERLAND24 { 24 } else { 16 };
+            match (auth, bind) {
+                // Authenticated binds keep the signing data where the addend would be
+                (true, true) => ChainedPointer::Bind {
+                    ordinal: bits(0, ordinal_bits) as u32,
+                    addend: 0,
+                },
+                (false, true) => ChainedPointer::Bind {
+                    ordinal: bits(0, ordinal_bits) as u32,
+                    addend: sign_extend(bits(32, 19), 19),
+                },
+                // Authenticated rebases always store an offset from the image base
+                (true, false) => ChainedPointer::Rebase(base.wrapping_add(bits(0, 32))),
+                (false, false) => {
+                    let target = bits(0, 43) | (bits(43, 8) << 56);
+                    if format == DYLD_CHAINED_PTR_ARM64E {
+                        ChainedPointer::Rebase(target)
+                    } else {
+                        ChainedPointer::Rebase(base.wrapping_add(target))
+                    }
+                }
+            }
+        }
+        DYLD_CHAINED_PTR_64 | DYLD_CHAINED_PTR_64_OFFSET => {
+            if bits(63, 1) != 0 {
+                ChainedPointer::Bind {
+                    ordinal: bits(0, 24) as u32,
+                    addend: bits(24, 8) as i64,
+                }
+            } else {
+                let target = bits(0, 36) | (bits(36, 8) << 56);
+                if format == DYLD_CHAINED_PTR_64_OFFSET {
+                    ChainedPointer::Rebase(base.wrapping_add(target))
+                } else {
+                    ChainedPointer::Rebase(target)
+                }
+            }
+        }
+        // Other formats are kernel or firmware only
+        _ => ChainedPointer::Rebase(raw),
+    }
+}
+
+/// Bytes between chained pointers, and the offset to the next one in `raw`
+fn chain_next(raw: u64, format: u16) -> Option<(usize, u64)> {
+    match format {
+        DYLD_CHAINED_PTR_ARM64E | DYLD_CHAINED_PTR_ARM64E_USERLAND | DYLD_CHAINED_PTR_ARM64E_USERLAND24 => {
+            Some((8, (raw >> 51) & 0x7ff))
+        }
+        DYLD_CHAINED_PTR_64 | DYLD_CHAINED_PTR_64_OFFSET => Some((4, (raw >> 51) & 0xfff)),
+        _ => None,
+    }
+}
+
+/// A parsed 64-bit Mach-O image
+#[derive(Debug, Clone)]
+pub struct MachO<'a> {
+    data: &'a [u8],
+    pub layout: Layout,
+    pub header: Header,
+    pub segments: Vec<Segment>,
+    /// The `LC_ID_DYLIB` install name, for dylibs and frameworks
+    pub install_name: Option<String>,
+    /// The dylibs this image links, in library ordinal order (ordinal 1 first)
+    pub dylibs: Vec<String>,
+    pub uuid: Option<[u8; 16]>,
+    pub symtab: Option<Symtab>,
+    pub dysymtab: Option<Dysymtab>,
+    pub exports_trie: Option<LinkeditData>,
+    pub chained_fixups: Option<LinkeditData>,
+    pub code_signature: Option<LinkeditData>,
+}
+
+impl<'a> MachO<'a> {
+    /// Parse a thin 64-bit image read from a file; use [`slice`] first for fat binaries
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        Self::parse_with_layout(data, Layout::File)
+    }
+
+    /// Parse an image mapped by dyld, `data` starting at its `mach_header`
+    pub fn parse_loaded(data: &'a [u8]) -> Option<Self> {
+        Self::parse_with_layout(data, Layout::Loaded)
+    }
+
+    fn parse_with_layout(data: &'a [u8], layout: Layout) -> Option<Self> {
+        if read_u32(data, 0)? != MH_MAGIC_64 {
+            return None;
+        }
+
+        let mut macho = MachO {
+            data,
+            layout,
+            header: Header {
+                cputype: read_u32(data, 4)?,
+                cpusubtype: read_u32(data, 8)?,
+                filetype: read_u32(data, 12)?,
+                ncmds: read_u32(data, 16)?,
+                sizeofcmds: read_u32(data, 20)?,
+                flags: read_u32(data, 24)?,
+            },
+            segments: Vec::new(),
+            install_name: None,
+            dylibs: Vec::new(),
+            uuid: None,
+            symtab: None,
+            dysymtab: None,
+            exports_trie: None,
+            chained_fixups: None,
+            code_signature: None,
+        };
+
+        let linkedit_data = |command: &[u8]| {
+            Some(LinkeditData {
+                offset: read_u32(command, 8)?,
+                size: read_u32(command, 12)?,
+            })
+        };
+        let dylib_name = |command: &[u8]| {
+            let name_offset = read_u32(command, 8)? as usize;
+            command.get(name_offset..).and_then(c_str).map(str::to_string)
+        };
+
+        for command in macho.load_commands() {
+            let data = command.data;
+            match command.cmd {
+                LC_SEGMENT_64 => macho.segments.push(parse_segment(data)?),
+                LC_SYMTAB => {
+                    macho.symtab = Some(Symtab {
+                        symoff: read_u32(data, 8)?,
+                        nsyms: read_u32(data, 12)?,
+                        stroff: read_u32(data, 16)?,
+                        strsize: read_u32(data, 20)?,
+                    });
+                }
+                LC_DYSYMTAB => {
+                    macho.dysymtab = Some(Dysymtab {
+                        ilocalsym: read_u32(data, 8)?,
+                        nlocalsym: read_u32(data, 12)?,
+                        iextdefsym: read_u32(data, 16)?,
+                        nextdefsym: read_u32(data, 20)?,
+                        iundefsym: read_u32(data, 24)?,
+                        nundefsym: read_u32(data, 28)?,
+                        indirectsymoff: read_u32(data, 56)?,
+                        nindirectsyms: read_u32(data, 60)?,
+                    });
+                }
+                LC_DYLD_INFO | LC_DYLD_INFO_ONLY if read_u32(data, 44)? != 0 => {
+                    macho.exports_trie = Some(LinkeditData {
+                        offset: read_u32(data, 40)?,
+                        size: read_u32(data, 44)?,
+                    });
+                }
+                LC_DYLD_EXPORTS_TRIE => macho.exports_trie = linkedit_data(data),
+                LC_DYLD_CHAINED_FIXUPS => macho.chained_fixups = linkedit_data(data),
+                LC_CODE_SIGNATURE => macho.code_signature = linkedit_data(data),
+                LC_ID_DYLIB => macho.install_name = dylib_name(data),
+                LC_LOAD_DYLIB | LC_LOAD_WEAK_DYLIB | LC_REEXPORT_DYLIB | LC_LAZY_LOAD_DYLIB | LC_LOAD_UPWARD_DYLIB => {
+                    macho.dylibs.push(dylib_name(data).unwrap_or_default());
+                }
+                LC_UUID => macho.uuid = data.get(8..24).and_then(|uuid| uuid.try_into().ok()),
+                _ => {}
+            }
+        }
+
+        Some(macho)
+    }
+
+    /// The bytes the image was parsed from
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The load commands, in order
+    ///
+    /// Stops at the first command that doesn't fit in the image.
+    pub fn load_commands(&self) -> impl Iterator<Item = LoadCommand<'a>> + use<'a> {
+        let data = self.data;
+        let mut offset = MACH_HEADER_64_SIZE;
+        (0..self.header.ncmds).map_while(move |_| {
+            let cmd = read_u32(data, offset)?;
+            let cmdsize = read_u32(data, offset + 4)? as usize;
+            if cmdsize < 8 {
+                return None;
+            }
+            let command = LoadCommand {
+                cmd,
+                offset,
+                data: data.get(offset..offset.checked_add(cmdsize)?)?,
+            };
+            offset += cmdsize;
+            Some(command)
+        })
+    }
+
+    /// The segment named `name`
+    pub fn segment(&self, name: &str) -> Option<&Segment> {
+        self.segments.iter().find(|segment| segment.name == name)
+    }
+
+    /// The first section named `name`, in whichever segment holds it
+    ///
+    /// ObjC sections move between `__DATA`, `__DATA_CONST` and `__DATA_DIRTY`
+    /// depending on the linker and the OS version.
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.segments
+            .iter()
+            .flat_map(|segment| &segment.sections)
+            .find(|section| section.name == name)
+    }
+
+    /// The address the image is based at: the vm address of `__TEXT`
+    pub fn base_address(&self) -> u64 {
+        self.segment("__TEXT").map_or(0, |segment| segment.vmaddr)
+    }
+
+    /// Where file offset `offset` is in the slice
+    fn data_offset(&self, offset: u64) -> Option<usize> {
+        match self.layout {
+            Layout::File => Some(offset as usize),
+            Layout::Loaded => {
+                let segment = self
+                    .segments
+                    .iter()
+                    .find(|segment| offset >= segment.fileoff && offset - segment.fileoff < segment.filesize)?;
+                let vm_offset = segment.vmaddr.checked_sub(self.base_address())?;
+                Some(vm_offset.checked_add(offset - segment.fileoff)? as usize)
+            }
+        }
+    }
+
+    /// `size` bytes at file offset `offset`, wherever the layout puts them
+    pub fn file_bytes(&self, offset: u64, size: u64) -> Option<&'a [u8]> {
+        let start = self.data_offset(offset)?;
+        self.data.get(start..start.checked_add(size as usize)?)
+    }
+
+    /// The contents of `blob`
+    pub fn linkedit(&self, blob: LinkeditData) -> Option<&'a [u8]> {
+        self.file_bytes(blob.offset as u64, blob.size as u64)
+    }
+
+    /// The bytes of `segment` present in the slice: its file contents, or
+    /// its whole vm range when loaded
+    pub fn segment_bytes(&self, segment: &Segment) -> Option<&'a [u8]> {
+        match self.layout {
+            Layout::File => self.file_bytes(segment.fileoff, segment.filesize),
+            Layout::Loaded => {
+                let start = segment.vmaddr.checked_sub(self.base_address())? as usize;
+                self.data.get(start..start.checked_add(segment.vmsize as usize)?)
+            }
+        }
+    }
+
+    /// The entries of the symbol table
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let Some(symtab) = self.symtab else {
+            return Vec::new();
+        };
+        let nlists = self.file_bytes(symtab.symoff as u64, symtab.nsyms as u64 * 16);
+        let strings = self.file_bytes(symtab.stroff as u64, symtab.strsize as u64);
+        match (nlists, strings) {
+            (Some(nlists), Some(strings)) => parse_nlists(nlists, strings),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The indirect symbol table: symbol table indices, or
+    /// [`INDIRECT_SYMBOL_LOCAL`] / [`INDIRECT_SYMBOL_ABS`]
+    pub fn indirect_symbols(&self) -> Vec<u32> {
+        let Some(dysymtab) = self.dysymtab else {
+            return Vec::new();
+        };
+        self.file_bytes(dysymtab.indirectsymoff as u64, dysymtab.nindirectsyms as u64 * 4)
+            .map(|table| table.chunks_exact(4).filter_map(|entry| read_u32(entry, 0)).collect())
+            .unwrap_or_default()
+    }
+
+    /// The entries of the exports trie, sorted by name
+    pub fn exports(&self) -> Vec<Export> {
+        self.exports_trie
+            .and_then(|trie| self.linkedit(trie))
+            .map(parse_export_trie)
+            .unwrap_or_default()
+    }
+
+    /// The imports and chain starts of `LC_DYLD_CHAINED_FIXUPS`
+    pub fn parse_chained_fixups(&self) -> Option<ChainedFixups> {
+        ChainedFixups::parse(self.linkedit(self.chained_fixups?)?)
+    }
+
+    /// Every chained fixup, found by walking the chains from each page start
+    ///
+    /// dyld overwrites the chains when it applies them, so this only finds
+    /// anything in a file, or an image that hasn't been fixed up.
+    pub fn fixups(&self) -> Vec<Fixup> {
+        let Some(chained_fixups) = self.parse_chained_fixups() else {
+            return Vec::new();
+        };
+        let base = self.base_address();
+
+        let mut fixups = Vec::new();
+        for starts in &chained_fixups.starts {
+            let Some(segment) = self.segments.get(starts.segment_index) else { continue };
+            let Some(bytes) = self.segment_bytes(segment) else { continue };
+            let segment_offset = segment.vmaddr.wrapping_sub(base);
+
+            let page_size = starts.page_size as usize;
+            for (page, &start) in starts.page_starts.iter().enumerate() {
+                if start == DYLD_CHAINED_PTR_START_NONE {
+                    continue;
+                }
+                // Chains never leave their page
+                let page_start = page * page_size;
+                let page_end = (page_start + page_size).min(bytes.len());
+                let mut offset = page_start + start as usize;
+                while offset < page_end
+                    && let Some(raw) = read_u64(bytes, offset)
+                {
+                    fixups.push(Fixup {
+                        offset: segment_offset.wrapping_add(offset as u64),
+                        pointer: decode_chained_pointer(raw, starts.pointer_format, base),
+                    });
+                    match chain_next(raw, starts.pointer_format) {
+                        Some((stride, next)) if next != 0 => offset += next as usize * stride,
+                        _ => break,
+                    }
+                }
+            }
+        }
+        fixups
+    }
+
+    /// The symbol pointer slots (`__got`, `__la_symbol_ptr`) and the symbols
+    /// the indirect symbol table binds them to
+    pub fn symbol_pointers(&self) -> Vec<Binding> {
+        let sections = self.segments.iter().flat_map(|segment| &segment.sections);
+        symbol_pointer_bindings(sections, self.base_address(), &self.indirect_symbols(), &self.symbols())
+    }
+
+    /// The pointers chained fixups bind to imports, anywhere in the image
+    ///
+    /// Like [`fixups`](Self::fixups), needs the chains dyld hasn't applied yet.
+    pub fn chained_binds(&self) -> Vec<Binding> {
+        let Some(imports) = self.parse_chained_fixups().map(|fixups| fixups.imports) else {
+            return Vec::new();
+        };
+        self.fixups()
+            .into_iter()
+            .filter_map(|fixup| match fixup.pointer {
+                ChainedPointer::Bind { ordinal, .. } => Some(Binding {
+                    name: imports.get(ordinal as usize)?.name.clone(),
+                    offset: fixup.offset,
+                }),
+                ChainedPointer::Rebase(_) => None,
+            })
+            .collect()
+    }
+
+    /// Every pointer bound to an imported symbol, sorted by offset
+    ///
+    /// Images with chained fixups usually list their `__got` slots both ways.
+    pub fn bindings(&self) -> Vec<Binding> {
+        let mut bindings = self.symbol_pointers();
+        bindings.extend(self.chained_binds());
+        bindings.sort_by_key(|binding| binding.offset);
+        bindings.dedup_by_key(|binding| binding.offset);
+        bindings
+    }
+}
+
+/// The symbols that symbol pointer `sections` are bound to
+///
+/// `indirect_symbols` and `symbols` are the image's tables, and `base` its
+/// `__TEXT` address. Slots the indirect symbol table marks local or
+/// absolute aren't bound to anything and are left out.
+pub fn symbol_pointer_bindings<'s>(
+    sections: impl IntoIterator<Item = &'s Section>,
+    base: u64,
+    indirect_symbols: &[u32],
+    symbols: &[Symbol],
+) -> Vec<Binding> {
+    let mut bindings = Vec::new();
+    for section in sections {
+        if !matches!(section.section_type(), S_NON_LAZY_SYMBOL_POINTERS | S_LAZY_SYMBOL_POINTERS) {
+            continue;
+        }
+        for slot in 0..section.size / 8 {
+            let Some(&index) = indirect_symbols.get(section.reserved1 as usize + slot as usize) else {
+                break;
+            };
+            if index & (INDIRECT_SYMBOL_LOCAL | INDIRECT_SYMBOL_ABS) != 0 {
+                continue;
+            }
+            let Some(symbol) = symbols.get(index as usize).filter(|symbol| !symbol.name.is_empty()) else {
+                continue;
+            };
+            bindings.push(Binding {
+                name: symbol.name.clone(),
+                offset: section.addr.wrapping_sub(base).wrapping_add(slot * 8),
+            });
+        }
+    }
+    bindings
+}
+
+fn parse_segment(command: &[u8]) -> Option<Segment> {
+    let nsects = read_u32(command, 64)? as usize;
+
+    // section_64 entries (80 bytes each) follow the 72-byte segment_command_64
+    let sections = (0..nsects)
+        .map(|i| {
+            let section = command.get(72 + i * 80..72 + (i + 1) * 80)?;
+            Some(Section {
+                name: fixed_name(&section[0..16]),
+                segment_name: fixed_name(&section[16..32]),
+                addr: read_u64(section, 32)?,
+                size: read_u64(section, 40)?,
+                offset: read_u32(section, 48)?,
+                flags: read_u32(section, 64)?,
+                reserved1: read_u32(section, 68)?,
+                reserved2: read_u32(section, 72)?,
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(Segment {
+        name: fixed_name(command.get(8..24)?),
+        vmaddr: read_u64(command, 24)?,
+        vmsize: read_u64(command, 32)?,
+        fileoff: read_u64(command, 40)?,
+        filesize: read_u64(command, 48)?,
+        maxprot: read_u32(command, 56)?,
+        initprot: read_u32(command, 60)?,
+        sections,
+    })
+}
+
+/// Parse `nlist_64` entries, whose names are offsets into `strings`
+///
+/// Returns one symbol per entry, since the indirect symbol table refers to
+/// them by index; a name that can't be read is empty.
+pub fn parse_nlists(nlists: &[u8], strings: &[u8]) -> Vec<Symbol> {
+    nlists
+        .chunks_exact(16)
+        .map(|nlist| {
+            let name = read_u32(nlist, 0)
+                .and_then(|strx| strings.get(strx as usize..))
+                .map(fixed_name)
+                .unwrap_or_default();
+            Symbol {
+                name,
+                n_type: nlist[4],
+                n_sect: nlist[5],
+                n_desc: read_u16(nlist, 6).unwrap_or(0),
+                value: read_u64(nlist, 8).unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+/// `dyld_cache_header` field offsets
+///
+/// The header grew over time; `mappingOffset` is its size in a given cache,
+/// so a field exists only if it fits before that. See [`cache_header_u32`].
+pub mod cache_header {
+    pub const MAPPING_OFFSET: usize = 16;
+    pub const MAPPING_COUNT: usize = 20;
+    pub const IMAGES_OFFSET_OLD: usize = 24;
+    pub const IMAGES_COUNT_OLD: usize = 28;
+    pub const SLIDE_INFO_OFFSET_OLD: usize = 56;
+    pub const SLIDE_INFO_SIZE_OLD: usize = 64;
+    pub const LOCAL_SYMBOLS_OFFSET: usize = 72;
+    pub const LOCAL_SYMBOLS_SIZE: usize = 80;
+    pub const MAPPING_WITH_SLIDE_OFFSET: usize = 312;
+    pub const MAPPING_WITH_SLIDE_COUNT: usize = 316;
+    pub const SUBCACHE_ARRAY_OFFSET: usize = 392;
+    pub const SUBCACHE_ARRAY_COUNT: usize = 396;
+    pub const SYMBOL_FILE_UUID: usize = 400;
+    pub const IMAGES_OFFSET: usize = 448;
+    pub const IMAGES_COUNT: usize = 452;
+    pub const CACHE_SUB_TYPE: usize = 456;
+    pub const OBJC_OPTS_OFFSET: usize = 464;
+}
+
+/// Whether a shared cache header is big enough to contain the field at `offset`
+pub fn cache_header_has_field(header: &[u8], offset: usize, size: usize) -> bool {
+    read_u32(header, cache_header::MAPPING_OFFSET).is_some_and(|header_size| header_size as usize >= offset + size)
+}
+
+/// The shared cache header field at `offset`, if this cache's header has it
+pub fn cache_header_u32(header: &[u8], offset: usize) -> Option<u32> {
+    cache_header_has_field(header, offset, 4).then(|| read_u32(header, offset)).flatten()
+}
+
+/// The shared cache header field at `offset`, if this cache's header has it
+pub fn cache_header_u64(header: &[u8], offset: usize) -> Option<u64> {
+    cache_header_has_field(header, offset, 8).then(|| read_u64(header, offset)).flatten()
+}
+
+/// The `dyld_cache_local_symbols_info` table of a cache file, and whether
+/// its entries are 64-bit (see [`cache_local_symbols`])
+///
+/// `file` is the `.symbols` file of a split cache, or the main cache file
+/// of older ones; its own header says where the table is.
+pub fn cache_local_symbols_info(file: &[u8]) -> Option<(&[u8], bool)> {
+    let offset = cache_header_u64(file, cache_header::LOCAL_SYMBOLS_OFFSET)? as usize;
+    let size = cache_header_u64(file, cache_header::LOCAL_SYMBOLS_SIZE)? as usize;
+    let info = file.get(offset..offset.checked_add(size)?)?;
+    Some((info, cache_header_has_field(file, cache_header::SYMBOL_FILE_UUID, 16)))
+}
+
+/// The local symbols of the image at `dylib_offset` in a dyld shared cache
+///
+/// The cache builder moves them out of the images into one
+/// `dyld_cache_local_symbols_info` table, `info`, which split caches keep in
+/// their `.symbols` file. `wide` is whether its entries have 64-bit dylib
+/// offsets, as in caches whose header has a `symbolFileUUID` field.
+pub fn cache_local_symbols(info: &[u8], dylib_offset: u64, wide: bool) -> Vec<Symbol> {
+    // nlistOffset, nlistCount, stringsOffset, stringsSize, entriesOffset, entriesCount
+    let field = |index: usize| read_u32(info, index * 4).unwrap_or(0) as usize;
+    let (nlist_offset, strings_offset, strings_size) = (field(0), field(2), field(3));
+    let (entries_offset, entries_count) = (field(4), field(5));
+
+    // Entries are (dylibOffset, nlistStartIndex, nlistCount)
+    let entry_size = if wide { 16 } else { 12 };
+    let entry = (0..entries_count)
+        .map(|i| entries_offset + i * entry_size)
+        .take_while(|&entry| entry + entry_size <= info.len())
+        .find(|&entry| {
+            let offset = if wide { read_u64(info, entry) } else { read_u32(info, entry).map(u64::from) };
+            offset == Some(dylib_offset)
+        });
+    let Some(entry) = entry else {
+        return Vec::new();
+    };
+    let counts = entry + entry_size - 8;
+    let (Some(start), Some(count)) = (read_u32(info, counts), read_u32(info, counts + 4)) else {
+        return Vec::new();
+    };
+
+    let nlists = nlist_offset + start as usize * 16;
+    let nlists = info.get(nlists..nlists.saturating_add(count as usize * 16));
+    let strings = info.get(strings_offset..strings_offset.saturating_add(strings_size));
+    match (nlists, strings) {
+        (Some(nlists), Some(strings)) => parse_nlists(nlists, strings),
+        _ => Vec::new(),
+    }
+}
+
+/// The entries of an exports trie, sorted by name
+///
+/// Each node has the terminal info of the symbol spelled by the path to it,
+/// if there is one, then its children as (edge label, node offset) pairs.
+pub fn parse_export_trie(trie: &[u8]) -> Vec<Export> {
+    let mut exports = Vec::new();
+    let mut stack = vec![(0usize, String::new())];
+    let mut visited = HashSet::new();
+
+    while let Some((node, prefix)) = stack.pop() {
+        // A malformed trie could loop
+        if !visited.insert(node) {
+            continue;
+        }
+        let mut offset = node;
+        let Some(terminal_size) = read_uleb128(trie, &mut offset) else { continue };
+        let children = offset.saturating_add(terminal_size as usize);
+
+        if terminal_size != 0
+            && let Some(export) = parse_export_info(trie, &mut offset, &prefix)
+        {
+            exports.push(export);
+        }
+
+        let mut offset = children;
+        let Some(&count) = trie.get(offset) else { continue };
+        offset += 1;
+        for _ in 0..count {
+            let Some(label) = trie.get(offset..).and_then(c_str) else { break };
+            offset += label.len() + 1;
+            let Some(child) = read_uleb128(trie, &mut offset) else { break };
+            stack.push((child as usize, format!("{}{}", prefix, label)));
+        }
+    }
+
+    exports.sort_by(|a, b| a.name.cmp(&b.name));
+    exports
+}
+
+/// The terminal info of the node at `*offset`: flags, then the target
+fn parse_export_info(trie: &[u8], offset: &mut usize, name: &str) -> Option<Export> {
+    let flags = read_uleb128(trie, offset)?;
+    let target = if flags & EXPORT_SYMBOL_FLAGS_REEXPORT != 0 {
+        let ordinal = read_uleb128(trie, offset)?;
+        let imported = trie.get(*offset..).and_then(c_str).filter(|imported| !imported.is_empty());
+        ExportTarget::Reexport {
+            ordinal,
+            name: imported.map(str::to_string),
+        }
+    } else if flags & EXPORT_SYMBOL_FLAGS_STUB_AND_RESOLVER != 0 {
+        ExportTarget::StubAndResolver {
+            stub: read_uleb128(trie, offset)?,
+            resolver: read_uleb128(trie, offset)?,
+        }
+    } else {
+        ExportTarget::Offset(read_uleb128(trie, offset)?)
+    };
+    Some(Export {
+        name: name.to_string(),
+        flags,
+        target,
+    })
+}
@@ -0,0 +1,254 @@
+//! Sending arbitrary messages
+//!
+//! [`msg_send!`](crate::msg_send) calls any selector without a generated
+//! wrapper, inferring the `objc_msgSend` signature from the argument and
+//! return types:
+//!
+//! ```ignore
+//! use ios_sys::msg_send;
+//!
+//! let length: usize = unsafe { msg_send![string, length] };
+//! let ch: u16 = unsafe { msg_send![string, characterAtIndex: 0usize] };
+//! let _: () = unsafe { msg_send![dict, setObject: value, forKey: key] };
+//! ```
+//!
+//! Debug builds look up the method the receiver resolves the selector to and
+//! panic if its type encoding disagrees with the Rust types, instead of
+//! silently passing arguments in the wrong registers.
+
+use core::ffi::CStr;
+use core::mem;
+
+use crate::encode::{Encode, Encoding};
+use crate::objc::{Class, SEL, id};
+use crate::rc::{Message, Retained};
+
+/// Something a message can be sent to
+///
+/// # Safety
+///
+/// `as_receiver` must return nil or a valid object or class.
+pub unsafe trait MessageReceiver {
+    /// The receiver as an object pointer
+    fn as_receiver(&self) -> id;
+}
+
+unsafe impl MessageReceiver for id {
+    #[inline]
+    fn as_receiver(&self) -> id {
+        *self
+    }
+}
+
+unsafe impl MessageReceiver for Class {
+    #[inline]
+    fn as_receiver(&self) -> id {
+        (*self).cast()
+    }
+}
+
+unsafe impl<T: Message> MessageReceiver for &T {
+    #[inline]
+    fn as_receiver(&self) -> id {
+        self.as_id()
+    }
+}
+
+unsafe impl<T: Message> MessageReceiver for &Retained<T> {
+    #[inline]
+    fn as_receiver(&self) -> id {
+        Retained::as_ptr(self)
+    }
+}
+
+/// A tuple of message arguments
+///
+/// # Safety
+///
+/// `ENCODINGS` must match the tuple's element types.
+pub unsafe trait MessageArguments: Sized {
+    /// The encodings of the arguments, in order
+    const ENCODINGS: &'static [Encoding];
+
+    /// Call `objc_msgSend` with the arguments
+    ///
+    /// # Safety
+    ///
+    /// The receiver must respond to `sel` with this signature.
+    unsafe fn send<R: Encode>(self, receiver: id, sel: SEL) -> R;
+}
+
+macro_rules! message_arguments {
+    ($($arg:ident),*) => {
+        unsafe impl<$($arg: Encode),*> MessageArguments for ($($arg,)*) {
+            const ENCODINGS: &'static [Encoding] = &[$($arg::ENCODING),*];
+
+            #[inline]
+            #[allow(non_snake_case)]
+            unsafe fn send<R: Encode>(self, receiver: id, sel: SEL) -> R {
+                type MsgSend<$($arg,)* R> = unsafe extern "C" fn(id, SEL $(, $arg)*) -> R;
+                let ($($arg,)*) = self;
+                unsafe {
+                    let msg_send: MsgSend<$($arg,)* R> = mem::transmute(msg_send_entry::<R>());
+                    msg_send(receiver, sel $(, $arg)*)
+                }
+            }
+        }
+    };
+}
+
+/// The `objc_msgSend` variant that returns `R`
+///
+/// arm64 returns every type through registers or `x8`, which plain
+/// `objc_msgSend` handles. x86_64 returns structs larger than 16 bytes in
+/// memory, which needs `objc_msgSend_stret`.
+#[inline]
+fn msg_send_entry<R: Encode>() -> *const () {
+    #[cfg(target_arch = "x86_64")]
+    if matches!(R::ENCODING, Encoding::Struct(..) | Encoding::Union(..)) && mem::size_of::<R>() > 16 {
+        return crate::objc::objc_msgSend_stret as *const ();
+    }
+    crate::objc::objc_msgSend as *const ()
+}
+
+message_arguments!();
+message_arguments!(A);
+message_arguments!(A, B);
+message_arguments!(A, B, C);
+message_arguments!(A, B, C, D);
+message_arguments!(A, B, C, D, E);
+message_arguments!(A, B, C, D, E, F);
+message_arguments!(A, B, C, D, E, F, G);
+message_arguments!(A, B, C, D, E, F, G, H);
+message_arguments!(A, B, C, D, E, F, G, H, I);
+message_arguments!(A, B, C, D, E, F, G, H, I, J);
+message_arguments!(A, B, C, D, E, F, G, H, I, J, K);
+message_arguments!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// Send `sel` with `args` to `receiver`
+///
+/// Used by [`msg_send!`](crate::msg_send). Debug builds verify the types
+/// against the method's type encoding first.
+///
+/// # Safety
+///
+/// The receiver must respond to `sel`, and the argument and return types
+/// must match the method's signature.
+#[inline]
+pub unsafe fn send_message<T: MessageReceiver, A: MessageArguments, R: Encode>(receiver: T, sel: SEL, args: A) -> R {
+    let receiver = receiver.as_receiver();
+    #[cfg(debug_assertions)]
+    unsafe {
+        verify_message::<A, R>(receiver, sel)
+    };
+    unsafe { args.send(receiver, sel) }
+}
+
+/// Panic if the method `receiver` resolves `sel` to has a different signature
+///
+/// Messages to nil and selectors without a method (forwarded or resolved
+/// dynamically) can't be checked and are let through.
+#[cfg(debug_assertions)]
+unsafe fn verify_message<A: MessageArguments, R: Encode>(receiver: id, sel: SEL) {
+    use crate::encode::{encodings_match, split_method_encoding};
+    use crate::objc::{class_getInstanceMethod, class_getName, class_isMetaClass, method_getTypeEncoding, object_getClass};
+
+    if receiver.is_null() {
+        return;
+    }
+
+    unsafe {
+        // The class of a class object is its metaclass, whose instance methods are the class methods
+        let class = object_getClass(receiver);
+        let method = class_getInstanceMethod(class, sel);
+        if method.is_null() {
+            return;
+        }
+        let types = method_getTypeEncoding(method);
+        if types.is_null() {
+            return;
+        }
+
+        let actual = split_method_encoding(&CStr::from_ptr(types).to_string_lossy());
+        let expected: Vec<String> = [R::ENCODING.to_string(), "@".to_string(), ":".to_string()]
+            .into_iter()
+            .chain(A::ENCODINGS.iter().map(ToString::to_string))
+            .collect();
+
+        let matches = expected.len() == actual.len()
+            && expected.iter().zip(&actual).all(|(e, a)| encodings_match(e, a));
+        if !matches {
+            let kind = if class_isMetaClass(class) { '+' } else { '-' };
+            let class_name = CStr::from_ptr(class_getName(class)).to_string_lossy();
+            let sel_name = CStr::from_ptr(crate::objc::sel_getName(sel)).to_string_lossy();
+            panic!(
+                "msg_send type mismatch for {}[{} {}]\n{}",
+                kind,
+                class_name,
+                sel_name,
+                signature_diff(&expected, &actual)
+            );
+        }
+    }
+}
+
+/// One line per return value and argument, marking the ones that differ
+#[cfg(debug_assertions)]
+fn signature_diff(expected: &[String], actual: &[String]) -> String {
+    use crate::encode::encodings_match;
+    use core::fmt::Write;
+
+    let mut diff = format!("  {:<12} {:<16} {}\n", "", "rust", "method");
+    for i in 0..expected.len().max(actual.len()) {
+        let label = match i {
+            0 => "return".to_string(),
+            1 => "self".to_string(),
+            2 => "_cmd".to_string(),
+            _ => format!("arg {}", i - 3),
+        };
+        let e = expected.get(i).map_or("(none)", String::as_str);
+        let a = actual.get(i).map_or("(none)", String::as_str);
+        let marker = if expected.get(i).zip(actual.get(i)).is_some_and(|(e, a)| encodings_match(e, a)) {
+            ' '
+        } else {
+            '!'
+        };
+        writeln!(diff, "{} {:<12} {:<16} {}", marker, label, e, a).unwrap();
+    }
+    diff
+}
+
+/// Build a selector name at compile time; used by [`msg_send!`](crate::msg_send)
+#[doc(hidden)]
+pub const fn __selector_name(bytes: &'static [u8]) -> &'static CStr {
+    match CStr::from_bytes_with_nul(bytes) {
+        Ok(name) => name,
+        Err(_) => panic!("selector name contains a nul byte"),
+    }
+}
+
+/// Send a message with types inferred from the arguments and return value
+///
+/// `msg_send![receiver, selector]` or
+/// `msg_send![receiver, keyword: arg, keyword: arg, ...]`. The receiver can be
+/// an `id`, a `Class`, a reference to a class wrapper or a `&Retained`.
+/// The selector is registered once per call site.
+///
+/// Must be used in an `unsafe` block: the receiver has to respond to the
+/// selector with matching types. Debug builds panic on a type mismatch with
+/// the method's type encoding.
+#[macro_export]
+macro_rules! msg_send {
+    [$receiver:expr, $sel:ident $(,)?] => {{
+        static SELECTOR: $crate::cache::CachedSel = $crate::cache::CachedSel::new(
+            $crate::message::__selector_name(concat!(stringify!($sel), "\0").as_bytes()),
+        );
+        $crate::message::send_message($receiver, SELECTOR.get(), ())
+    }};
+    [$receiver:expr, $($keyword:ident : $arg:expr),+ $(,)?] => {{
+        static SELECTOR: $crate::cache::CachedSel = $crate::cache::CachedSel::new(
+            $crate::message::__selector_name(concat!($(stringify!($keyword), ":",)+ "\0").as_bytes()),
+        );
+        $crate::message::send_message($receiver, SELECTOR.get(), ($($arg,)+))
+    }};
+}
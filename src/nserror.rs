@@ -0,0 +1,104 @@
+//! `NSError` values returned through `NSError **` out-parameters
+//!
+//! Generated `try_*` methods report failures as `Err(NSErrorWrapper)`.
+
+use core::ffi::{CStr, c_char};
+use core::fmt;
+use core::{mem, ptr};
+
+use crate::cache::CachedSel;
+use crate::objc::{SEL, id};
+use crate::rc::{AnyObject, Retained};
+
+/// An `NSError` reported by an Objective-C method
+///
+/// Holds no error if the method failed without setting one, which Cocoa
+/// allows but discourages.
+pub struct NSErrorWrapper(Option<Retained<AnyObject>>);
+
+impl NSErrorWrapper {
+    /// Wrap the `NSError` written to an out-parameter
+    ///
+    /// The error is autoreleased, so it is retained here to outlive the
+    /// enclosing autorelease pool.
+    ///
+    /// # Safety
+    ///
+    /// `error` must be null or point to a valid `NSError`.
+    pub unsafe fn from_raw(error: id) -> Self {
+        Self(unsafe { Retained::retain(error) })
+    }
+
+    /// The underlying `NSError`, null if the method didn't set one
+    pub fn as_raw(&self) -> id {
+        self.0.as_ref().map_or(ptr::null_mut(), Retained::as_ptr)
+    }
+
+    /// The error domain, e.g. `NSCocoaErrorDomain`
+    pub fn domain(&self) -> String {
+        static DOMAIN: CachedSel = CachedSel::new(c"domain");
+        unsafe { string_from_nsstring(self.send(&DOMAIN)) }
+    }
+
+    /// The error code within the domain
+    pub fn code(&self) -> isize {
+        static CODE: CachedSel = CachedSel::new(c"code");
+        unsafe { self.send(&CODE) }
+    }
+
+    /// The user-facing description of the error
+    pub fn localized_description(&self) -> String {
+        static LOCALIZED_DESCRIPTION: CachedSel = CachedSel::new(c"localizedDescription");
+        if self.0.is_none() {
+            return "The operation failed without reporting an error".to_string();
+        }
+        unsafe { string_from_nsstring(self.send(&LOCALIZED_DESCRIPTION)) }
+    }
+
+    /// Send a message without arguments, returning zero for a missing error
+    unsafe fn send<R: Default>(&self, sel: &CachedSel) -> R {
+        let Some(error) = &self.0 else {
+            return R::default();
+        };
+        type MsgSend<R> = unsafe extern "C" fn(id, SEL) -> R;
+        unsafe {
+            let msg_send: MsgSend<R> = mem::transmute(crate::objc::objc_msgSend as *const ());
+            msg_send(Retained::as_ptr(error), sel.get())
+        }
+    }
+}
+
+/// Copy an `NSString` into a Rust `String` (empty for nil)
+unsafe fn string_from_nsstring(string: id) -> String {
+    static UTF8_STRING: CachedSel = CachedSel::new(c"UTF8String");
+    if string.is_null() {
+        return String::new();
+    }
+    type MsgSend = unsafe extern "C" fn(id, SEL) -> *const c_char;
+    unsafe {
+        let msg_send: MsgSend = mem::transmute(crate::objc::objc_msgSend as *const ());
+        let utf8 = msg_send(string, UTF8_STRING.get());
+        if utf8.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr(utf8).to_string_lossy().into_owned()
+    }
+}
+
+impl fmt::Debug for NSErrorWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NSError")
+            .field("domain", &self.domain())
+            .field("code", &self.code())
+            .field("localized_description", &self.localized_description())
+            .finish()
+    }
+}
+
+impl fmt::Display for NSErrorWrapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.domain(), self.code(), self.localized_description())
+    }
+}
+
+impl std::error::Error for NSErrorWrapper {}
@@ -0,0 +1,137 @@
+//! Reference-counted ownership of Objective-C objects
+//!
+//! Generated class wrappers like `NSString(pub id)` are plain, non-owning
+//! views of an object. [`Retained`] owns one reference to it: cloning calls
+//! `objc_retain` and dropping calls `objc_release`.
+//!
+//! Generated methods return objects as `Retained` following the Cocoa naming
+//! conventions: methods in the `alloc`, `new`, `copy`, `mutableCopy` and
+//! `init` families return a +1 reference that is taken over as is, all other
+//! methods return a +0 reference that is retained.
+
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+
+use crate::objc::{id, objc_autorelease, objc_release, objc_retain};
+
+/// An Objective-C object wrapper that [`Retained`] can manage
+///
+/// # Safety
+///
+/// `from_id` must wrap the pointer as is and `as_id` must return it
+/// unchanged.
+pub unsafe trait Message {
+    /// The wrapped object pointer
+    fn as_id(&self) -> id;
+
+    /// Wrap an object pointer without changing its retain count
+    ///
+    /// # Safety
+    ///
+    /// `obj` must be null or point to an object of the wrapper's class.
+    unsafe fn from_id(obj: id) -> Self;
+}
+
+/// An object of unknown class
+#[repr(transparent)]
+pub struct AnyObject(pub id);
+
+unsafe impl Message for AnyObject {
+    #[inline]
+    fn as_id(&self) -> id {
+        self.0
+    }
+
+    #[inline]
+    unsafe fn from_id(obj: id) -> Self {
+        Self(obj)
+    }
+}
+
+/// An owned reference to an Objective-C object
+///
+/// Never null. Dereferences to the wrapper, so its methods can be called
+/// directly.
+pub struct Retained<T: Message> {
+    obj: T,
+}
+
+impl<T: Message> Retained<T> {
+    /// Take over a +1 reference, e.g. the result of `alloc`, `new` or `copy`
+    ///
+    /// Returns `None` for nil.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must be null or an object of `T`'s class that the caller owns a
+    /// reference to. That reference is released when the `Retained` drops.
+    #[inline]
+    pub unsafe fn from_raw(obj: id) -> Option<Self> {
+        if obj.is_null() {
+            return None;
+        }
+        Some(Self { obj: unsafe { T::from_id(obj) } })
+    }
+
+    /// Retain a +0 reference, e.g. the result of a getter
+    ///
+    /// Returns `None` for nil.
+    ///
+    /// # Safety
+    ///
+    /// `obj` must be null or a valid object of `T`'s class.
+    #[inline]
+    pub unsafe fn retain(obj: id) -> Option<Self> {
+        unsafe { Self::from_raw(objc_retain(obj)) }
+    }
+
+    /// The object pointer, still owned by `this`
+    #[inline]
+    pub fn as_ptr(this: &Self) -> id {
+        this.obj.as_id()
+    }
+
+    /// Give up ownership without releasing, returning the +1 reference
+    #[inline]
+    pub fn into_raw(this: Self) -> id {
+        ManuallyDrop::new(this).obj.as_id()
+    }
+
+    /// Hand the reference to the current autorelease pool, returning a +0 pointer
+    ///
+    /// The pointer stays valid until the pool is drained.
+    #[inline]
+    pub fn autorelease(this: Self) -> id {
+        unsafe { objc_autorelease(Self::into_raw(this)) }
+    }
+}
+
+impl<T: Message> Deref for Retained<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.obj
+    }
+}
+
+impl<T: Message> Clone for Retained<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        unsafe { Self { obj: T::from_id(objc_retain(self.obj.as_id())) } }
+    }
+}
+
+impl<T: Message> Drop for Retained<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { objc_release(self.obj.as_id()) }
+    }
+}
+
+impl<T: Message> fmt::Debug for Retained<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Retained").field(&self.obj.as_id()).finish()
+    }
+}
@@ -0,0 +1,273 @@
+//! Rebinding the imports of loaded images, like fishhook
+//!
+//! Instead of patching a function's code, this rewrites the pointers other
+//! images call it through: the `__got` and `__la_symbol_ptr` slots the
+//! indirect symbol table names, and the pointers chained fixups bind. Images
+//! loaded later have their symbol pointers rebound as dyld adds them; their
+//! chained binds have to be read from the image's file, which can't happen
+//! under dyld's loader lock, so those wait for the next `rebind_symbol`.
+//! Calls from inside the image that defines the function, and through
+//! `dlsym`, still reach the original.
+//!
+//! Slots holding arm64e signed pointers (`__auth_got`, authenticated binds)
+//! are left alone: an unsigned replacement would fail authentication.
+//!
+//! ```ignore
+//! type Open = unsafe extern "C" fn(*const c_char, c_int, c_int) -> c_int;
+//!
+//! static ORIGINAL: OnceLock<Open> = OnceLock::new();
+//!
+//! unsafe extern "C" fn logged_open(path: *const c_char, flags: c_int, mode: c_int) -> c_int {
+//!     eprintln!("open({:?})", unsafe { CStr::from_ptr(path) });
+//!     unsafe { ORIGINAL.get().unwrap()(path, flags, mode) }
+//! }
+//!
+//! let original = unsafe { rebind::rebind_symbol(c"_open", logged_open as Open) }.unwrap();
+//! ORIGINAL.set(original).ok();
+//! ```
+
+use core::ffi::{CStr, c_char, c_int, c_void};
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::{Arc, Mutex, Once, OnceLock};
+
+use crate::hook::FunctionPointer;
+use crate::mach::{
+    KERN_SUCCESS, VM_PROT_COPY, VM_PROT_READ, VM_PROT_WRITE, VM_REGION_BASIC_INFO_64, kern_return_t,
+    mach_msg_type_number_t, mach_port_t, mach_task_self_, vm_page_size, vm_prot_t, vm_protect, vm_region_64,
+    vm_region_basic_info_data_64_t, vm_region_flavor_t,
+};
+use crate::macho::{Binding, MachO, arm64_slice, parse_nlists, symbol_pointer_bindings};
+use crate::symbols::{Image, RTLD_DEFAULT, dlsym};
+
+#[cfg_attr(feature = "runtime", link(name = "System", kind = "dylib"))]
+unsafe extern "C" {
+    fn _dyld_register_func_for_add_image(func: unsafe extern "C" fn(header: *const c_void, slide: isize));
+
+    fn dladdr(address: *const c_void, info: *mut Dl_info) -> c_int;
+}
+
+#[repr(C)]
+struct Dl_info {
+    dli_fname: *const c_char,
+    dli_fbase: *mut c_void,
+    dli_sname: *const c_char,
+    dli_saddr: *mut c_void,
+}
+
+/// Bits above the largest user address, only set in signed pointers
+const PAC_MASK: usize = !((1 << 47) - 1);
+
+/// The rebindings made so far, applied in order to images dyld adds later
+static REBINDINGS: Mutex<Vec<(String, usize)>> = Mutex::new(Vec::new());
+
+/// Make the images that import `name` call `replacement` instead
+///
+/// `name` is the symbol table name, with its leading underscore. Returns the
+/// function the replacement can call on to: the previous replacement if
+/// `name` was rebound before, otherwise the function `dlsym` finds. Lazy
+/// pointers that haven't been bound yet hold a stub helper, so the rewritten
+/// pointers are only a fallback.
+///
+/// # Safety
+///
+/// `F` must be the symbol's actual signature, and nothing may be calling
+/// through the pointers while they change.
+pub unsafe fn rebind_symbol<F: FunctionPointer>(name: &CStr, replacement: F) -> Option<F> {
+    let name = name.to_str().ok()?;
+    let replacement = replacement.as_ptr() as usize;
+    let (previous, earlier) = {
+        let mut rebindings = REBINDINGS.lock().unwrap();
+        let previous = rebindings.iter().rev().find(|(rebound, _)| rebound == name).map(|&(_, function)| function);
+        let earlier = rebindings.clone();
+        rebindings.push((name.to_string(), replacement));
+        (previous, earlier)
+    };
+
+    // Images added since the last call only had their symbol pointers rebound
+    let pending = core::mem::take(&mut *PENDING.lock().unwrap());
+    let mut replaced = None;
+    for image in Image::all() {
+        let bindings = unsafe { bindings(&image) };
+        if pending.contains(&(image.header() as usize)) {
+            for (name, replacement) in &earlier {
+                unsafe { rebind_image(&image, &bindings, name, *replacement) };
+            }
+        }
+        replaced = replaced.or(unsafe { rebind_image(&image, &bindings, name, replacement) });
+    }
+
+    // dyld calls this for the images already loaded too, which are rebound by now
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| unsafe { _dyld_register_func_for_add_image(image_added) });
+
+    let original = previous
+        .or_else(|| {
+            let symbol = CString::new(name.strip_prefix('_')?).ok()?;
+            let address = unsafe { dlsym(RTLD_DEFAULT, symbol.as_ptr()) };
+            (!address.is_null()).then_some(address as usize)
+        })
+        .or(replaced)?;
+    Some(unsafe { F::from_ptr(original as *mut c_void) })
+}
+
+/// Headers of the images dyld added whose chained binds haven't been rebound
+static PENDING: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// Apply the rebindings made so far to an image dyld just added
+///
+/// This runs with dyld's loader lock held, so only the symbol pointers in
+/// memory are rebound here. Images with chained fixups are left for the next
+/// `rebind_symbol` to read their files.
+unsafe extern "C" fn image_added(header: *const c_void, slide: isize) {
+    let mut info = Dl_info {
+        dli_fname: core::ptr::null(),
+        dli_fbase: core::ptr::null_mut(),
+        dli_sname: core::ptr::null(),
+        dli_saddr: core::ptr::null_mut(),
+    };
+    if unsafe { dladdr(header, &mut info) } == 0 || info.dli_fname.is_null() {
+        return;
+    }
+    let path = unsafe { CStr::from_ptr(info.dli_fname) }.to_string_lossy().into_owned();
+    let image = Image::new(header, slide, path);
+
+    let (bindings, chained) = unsafe { read_bindings(&image, false) };
+    if chained {
+        PENDING.lock().unwrap().push(header as usize);
+    }
+    let rebindings = REBINDINGS.lock().unwrap().clone();
+    for (name, replacement) in rebindings {
+        unsafe { rebind_image(&image, &bindings, &name, replacement) };
+    }
+}
+
+/// Point the slots of `bindings` that bind `name` at `replacement`
+///
+/// Returns what the first slot it rewrote held before.
+///
+/// # Safety
+///
+/// The image must still be loaded.
+unsafe fn rebind_image(image: &Image, bindings: &[Binding], name: &str, replacement: usize) -> Option<usize> {
+    let mut original = None;
+    for binding in bindings.iter().filter(|binding| binding.name == name) {
+        let slot = (image.header() as usize).wrapping_add(binding.offset as usize) as *mut usize;
+        let current = unsafe { slot.read_volatile() };
+        if current & PAC_MASK != 0 || current == replacement {
+            continue;
+        }
+
+        // __DATA_CONST and __AUTH_CONST are made read-only once dyld has bound them
+        let task = unsafe { mach_task_self_ };
+        let page_size = unsafe { vm_page_size };
+        let page = slot as usize & !(page_size - 1);
+        let Some(protection) = (unsafe { page_protection(task, page) }) else {
+            continue;
+        };
+        let writable = protection & VM_PROT_WRITE as vm_prot_t != 0;
+        let copy = (VM_PROT_READ | VM_PROT_WRITE | VM_PROT_COPY) as vm_prot_t;
+        if !writable && unsafe { vm_protect(task, page, page_size, 0, copy) } != KERN_SUCCESS as kern_return_t {
+            continue;
+        }
+        unsafe { slot.write_volatile(replacement) };
+        original.get_or_insert(current);
+        if !writable {
+            unsafe { vm_protect(task, page, page_size, 0, protection) };
+        }
+    }
+    original
+}
+
+/// The current protection of the page at `page`, as `vm_region_64` reports it
+///
+/// # Safety
+///
+/// `task` must be this task's port.
+unsafe fn page_protection(task: mach_port_t, page: usize) -> Option<vm_prot_t> {
+    let mut address = page;
+    let mut size = 0;
+    let mut info = vm_region_basic_info_data_64_t::default();
+    let mut count = (size_of::<vm_region_basic_info_data_64_t>() / size_of::<c_int>()) as mach_msg_type_number_t;
+    let mut object_name = 0;
+    let result = unsafe {
+        vm_region_64(
+            task,
+            &mut address,
+            &mut size,
+            VM_REGION_BASIC_INFO_64 as vm_region_flavor_t,
+            (&raw mut info).cast(),
+            &mut count,
+            &mut object_name,
+        )
+    };
+    // With no region containing `page`, vm_region_64 describes the next one
+    (result == KERN_SUCCESS as kern_return_t && address <= page).then_some(info.protection)
+}
+
+/// An image's bound pointers, by offset from its header
+type Bindings = Arc<Vec<Binding>>;
+
+/// Every pointer `image` binds to an imported symbol, read on first use
+///
+/// Cached by header and path, like the image's symbol table.
+///
+/// # Safety
+///
+/// The image must still be loaded.
+unsafe fn bindings(image: &Image) -> Bindings {
+    static BINDINGS: OnceLock<Mutex<HashMap<(usize, String), Bindings>>> = OnceLock::new();
+    let cache = BINDINGS.get_or_init(Default::default);
+    let key = (image.header() as usize, image.path().to_string());
+    if let Some(bindings) = cache.lock().unwrap().get(&key) {
+        return bindings.clone();
+    }
+
+    let bindings = Arc::new(unsafe { read_bindings(image, true) }.0);
+    cache.lock().unwrap().entry(key).or_insert(bindings).clone()
+}
+
+/// Read the bindings of `image`, and whether it uses chained fixups
+///
+/// The symbol pointers come from the image in memory. dyld overwrites the
+/// chains of chained fixups as it applies them, so those binds come from the
+/// file the image was loaded from, and only when `read_file` is set.
+///
+/// # Safety
+///
+/// The image must still be loaded.
+unsafe fn read_bindings(image: &Image, read_file: bool) -> (Vec<Binding>, bool) {
+    let Some(commands) = (unsafe { image.commands() }) else {
+        return (Vec::new(), false);
+    };
+
+    let mut bindings = Vec::new();
+    if let (Some(symtab), Some(dysymtab)) = (commands.symtab, commands.dysymtab) {
+        let linkedit = |offset, size| unsafe { image.linkedit(&commands, offset, size) };
+        let nlists = linkedit(symtab.symoff, symtab.nsyms as u64 * 16);
+        let strings = linkedit(symtab.stroff, symtab.strsize as u64);
+        let indirect = linkedit(dysymtab.indirectsymoff, dysymtab.nindirectsyms as u64 * 4);
+        if let (Some(nlists), Some(strings), Some(indirect)) = (nlists, strings, indirect) {
+            let symbols = parse_nlists(nlists, strings);
+            let indirect: Vec<u32> = indirect
+                .chunks_exact(4)
+                .map(|entry| u32::from_le_bytes(entry.try_into().unwrap()))
+                .collect();
+            let sections = commands.segments.iter().flat_map(|segment| &segment.sections);
+            bindings = symbol_pointer_bindings(sections, commands.base_address(), &indirect, &symbols);
+        }
+    }
+
+    let chained = commands.chained_fixups.is_some();
+    if chained
+        && read_file
+        && let Ok(file) = std::fs::read(image.path())
+        && let Some(file) = arm64_slice(&file).and_then(MachO::parse)
+        && file.uuid == commands.uuid
+    {
+        bindings.extend(file.chained_binds());
+    }
+    bindings.sort_by_key(|binding| binding.offset);
+    bindings.dedup_by_key(|binding| binding.offset);
+    (bindings, chained)
+}
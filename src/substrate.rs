@@ -0,0 +1,145 @@
+//! CydiaSubstrate hooking API
+//!
+//! Typed wrappers over `MSHookFunction`, `MSHookMessageEx` and the symbol
+//! lookup functions, with the raw declarations (generated from the Theos TBD
+//! stub) in [`ffi`]. Enabled by the `substrate` feature; with `runtime` the
+//! build links the stub found under `$THEOS`. ElleKit provides the same
+//! symbols, so tweaks built against this run on either.
+//!
+//! ```ignore
+//! type Getpid = unsafe extern "C" fn() -> i32;
+//!
+//! static ORIGINAL: OnceLock<Getpid> = OnceLock::new();
+//!
+//! unsafe extern "C" fn fake_getpid() -> i32 {
+//!     unsafe { ORIGINAL.get().unwrap()() + 1 }
+//! }
+//!
+//! let getpid = unsafe { substrate::find_symbol::<Getpid>(None, c"_getpid") }.unwrap();
+//! let original = unsafe { substrate::hook_function(getpid, fake_getpid as Getpid) }.unwrap();
+//! ORIGINAL.set(original).ok();
+//! ```
+
+use core::ffi::{CStr, c_void};
+use core::ptr;
+
+use crate::declare::MethodImplementation;
+use crate::hook::{FunctionPointer, HookError, checked_method, describe};
+use crate::objc::{Class, object_getClass};
+
+pub mod ffi {
+    //! CydiaSubstrate declarations (auto-generated from the Theos TBD stub)
+    #![allow(clippy::all)]
+    #![allow(warnings)]
+    include!(concat!(env!("OUT_DIR"), "/substrate.rs"));
+}
+
+/// An image loaded in the process
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Image(ffi::MSImageRef);
+
+// Images stay loaded for the lifetime of the process
+unsafe impl Send for Image {}
+unsafe impl Sync for Image {}
+
+impl Image {
+    /// The loaded image at `path`, e.g. `/System/Library/Frameworks/UIKit.framework/UIKit`
+    ///
+    /// Doesn't load the image; returns `None` if it isn't loaded.
+    pub fn by_name(path: &CStr) -> Option<Self> {
+        let image = unsafe { ffi::MSGetImageByName(path.as_ptr()) };
+        (!image.is_null()).then_some(Image(image))
+    }
+
+    /// The raw `MSImageRef`
+    pub fn as_raw(self) -> ffi::MSImageRef {
+        self.0
+    }
+}
+
+/// Look up a symbol, including non-exported ones, as a `F`
+///
+/// `name` is the mangled name, with the leading underscore of C symbols
+/// (`_getpid`). Without an image every loaded image is searched.
+///
+/// # Safety
+///
+/// The symbol must be a fn of type `F`.
+pub unsafe fn find_symbol<F: FunctionPointer>(image: Option<Image>, name: &CStr) -> Option<F> {
+    let image = image.map_or(ptr::null(), Image::as_raw);
+    let symbol = unsafe { ffi::MSFindSymbol(image, name.as_ptr()) };
+    (!symbol.is_null()).then(|| unsafe { F::from_ptr(symbol) })
+}
+
+/// Redirect calls to `target` to `replacement`
+///
+/// Returns a fn that runs the original code of `target`, or `None` if
+/// Substrate couldn't hook it.
+///
+/// # Safety
+///
+/// `target` must be a fn that can be patched, and `replacement` must be
+/// safe to call everywhere `target` is, from any thread.
+pub unsafe fn hook_function<F: FunctionPointer>(target: F, replacement: F) -> Option<F> {
+    let mut original = ptr::null_mut();
+    unsafe { ffi::MSHookFunction(target.as_ptr(), replacement.as_ptr(), &mut original) };
+    (!original.is_null()).then(|| unsafe { F::from_ptr(original) })
+}
+
+/// Replace the instance method `selector` of `class` with `replacement`
+///
+/// The signature of `replacement` is checked against the method's type
+/// encoding first. Returns the original implementation; for a method
+/// `class` inherits, Substrate adds an override calling the superclass's.
+///
+/// # Safety
+///
+/// `class` must be nil or a valid class, and `replacement` must be safe to
+/// call wherever the method is, from any thread.
+pub unsafe fn hook_message<F: MethodImplementation>(class: Class, selector: &CStr, replacement: F) -> Result<F, HookError> {
+    unsafe { hook_message_on(class, selector, replacement, '-') }
+}
+
+/// Replace the class method `selector` of `class` with `replacement`
+///
+/// # Safety
+///
+/// As for [`hook_message`].
+pub unsafe fn hook_class_message<F: MethodImplementation>(
+    class: Class,
+    selector: &CStr,
+    replacement: F,
+) -> Result<F, HookError> {
+    if class.is_null() {
+        return Err(HookError::NilClass);
+    }
+    // Class methods are the instance methods of the metaclass
+    unsafe { hook_message_on(object_getClass(class.cast()), selector, replacement, '+') }
+}
+
+unsafe fn hook_message_on<F: MethodImplementation>(
+    class: Class,
+    selector: &CStr,
+    replacement: F,
+    kind: char,
+) -> Result<F, HookError> {
+    unsafe {
+        let (sel, _) = checked_method::<F>(class, selector, kind)?;
+        let mut original = None;
+        ffi::MSHookMessageEx(class, sel, replacement.imp(), &mut original);
+        match original {
+            Some(original) => Ok(F::from_imp(original)),
+            None => Err(HookError::Failed { method: describe(class, selector, kind) }),
+        }
+    }
+}
+
+/// Overwrite read-only memory, such as code, with `data`
+///
+/// # Safety
+///
+/// `target` must be valid for `data.len()` bytes, and nothing may be
+/// executing or reading the bytes while they change.
+pub unsafe fn hook_memory(target: *mut c_void, data: &[u8]) {
+    unsafe { ffi::MSHookMemory(target, data.as_ptr().cast(), data.len()) }
+}
@@ -0,0 +1,285 @@
+//! Looking up non-exported symbols in loaded images
+//!
+//! `dlsym` and the TBD stubs the bindings link against only see exported
+//! symbols, so static functions of private frameworks are out of reach of
+//! both. This walks the images dyld has loaded and searches their nlist
+//! symbol tables, and for images in the dyld shared cache the local symbols
+//! the cache builder moved into the cache's `.symbols` file, like Substrate's
+//! `MSFindSymbol` does.
+//!
+//! Each image's table is read once and cached for the life of the process.
+//!
+//! ```ignore
+//! type Layout = unsafe extern "C" fn(id, bool);
+//!
+//! let image = symbols::Image::by_name("UIKitCore").unwrap();
+//! let layout = unsafe { symbols::find_symbol::<Layout>(Some(&image), c"__UIViewLayoutIfNeeded") };
+//! ```
+
+use core::ffi::{CStr, c_char, c_int, c_void};
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::fd::AsRawFd;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::hook::FunctionPointer;
+use crate::macho::{MachO, cache_local_symbols, cache_local_symbols_info, parse_nlists};
+
+#[cfg_attr(feature = "runtime", link(name = "System", kind = "dylib"))]
+unsafe extern "C" {
+    fn _dyld_image_count() -> u32;
+    fn _dyld_get_image_header(image_index: u32) -> *const c_void;
+    fn _dyld_get_image_vmaddr_slide(image_index: u32) -> isize;
+    fn _dyld_get_image_name(image_index: u32) -> *const c_char;
+
+    pub(crate) fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+}
+
+pub(crate) const RTLD_DEFAULT: *mut c_void = -2isize as *mut c_void;
+const PROT_READ: c_int = 1;
+const MAP_PRIVATE: c_int = 2;
+const MAP_FAILED: *mut c_void = !0usize as *mut c_void;
+
+/// `sizeof(struct mach_header_64)`
+const MACH_HEADER_64_SIZE: usize = 32;
+
+/// Where the shared cache lives when `dyld_shared_cache_file_path` is missing
+const CACHE_PATHS: [&str; 3] = [
+    "/System/Library/Caches/com.apple.dyld/dyld_shared_cache_arm64e",
+    "/System/Library/Caches/com.apple.dyld/dyld_shared_cache_arm64",
+    "/private/preboot/Cryptexes/OS/System/Library/Caches/com.apple.dyld/dyld_shared_cache_arm64e",
+];
+
+/// An image's defined symbols, by name, at their unslid addresses
+type SymbolTable = Arc<HashMap<String, usize>>;
+
+/// An image dyld has loaded
+#[derive(Clone, Debug)]
+pub struct Image {
+    header: usize,
+    slide: isize,
+    path: String,
+}
+
+impl Image {
+    /// The images loaded right now, in dyld's order
+    pub fn all() -> Vec<Self> {
+        let count = unsafe { _dyld_image_count() };
+        (0..count).filter_map(Self::at).collect()
+    }
+
+    /// The loaded image whose path, or last path component, is `name`
+    ///
+    /// e.g. `UIKitCore` or `/System/Library/PrivateFrameworks/UIKitCore.framework/UIKitCore`.
+    /// Doesn't load the image; returns `None` if it isn't loaded.
+    pub fn by_name(name: &str) -> Option<Self> {
+        Self::all()
+            .into_iter()
+            .find(|image| image.path == name || image.path.rsplit('/').next() == Some(name))
+    }
+
+    fn at(index: u32) -> Option<Self> {
+        let header = unsafe { _dyld_get_image_header(index) };
+        let name = unsafe { _dyld_get_image_name(index) };
+        // Both are null if the image was unloaded since the count was read
+        if header.is_null() || name.is_null() {
+            return None;
+        }
+        Some(Image {
+            header: header as usize,
+            slide: unsafe { _dyld_get_image_vmaddr_slide(index) },
+            path: unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned(),
+        })
+    }
+
+    /// The image's `mach_header_64`
+    pub fn header(&self) -> *const c_void {
+        self.header as *const c_void
+    }
+
+    /// How far the image was slid from the addresses it was linked at
+    pub fn slide(&self) -> isize {
+        self.slide
+    }
+
+    /// The path dyld loaded the image from
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The address of the symbol `name` (with its leading underscore) in this image
+    pub fn symbol_address(&self, name: &str) -> Option<NonNull<c_void>> {
+        let address = *self.symbols().get(name)?;
+        NonNull::new(address.wrapping_add_signed(self.slide) as *mut c_void)
+    }
+
+    /// An image dyld reported loading at `header`, e.g. to an add-image callback
+    pub(crate) fn new(header: *const c_void, slide: isize, path: String) -> Self {
+        Image { header: header as usize, slide, path }
+    }
+
+    /// The image's header and load commands
+    ///
+    /// The slice stops after the commands: the segments needn't follow each
+    /// other in memory, and cache images share one `__LINKEDIT`.
+    ///
+    /// # Safety
+    ///
+    /// The image must still be loaded.
+    pub(crate) unsafe fn commands(&self) -> Option<MachO<'static>> {
+        let header = self.header as *const u8;
+        let sizeofcmds = unsafe { header.add(20).cast::<u32>().read_unaligned() } as usize;
+        MachO::parse_loaded(unsafe { std::slice::from_raw_parts(header, MACH_HEADER_64_SIZE + sizeofcmds) })
+    }
+
+    /// `size` bytes at file offset `offset` in `__LINKEDIT`, where the
+    /// symbol tables are, found through the commands of `image`
+    ///
+    /// # Safety
+    ///
+    /// The image must still be loaded, and `image` be its [`commands`](Self::commands).
+    pub(crate) unsafe fn linkedit(&self, image: &MachO, offset: u32, size: u64) -> Option<&'static [u8]> {
+        let linkedit = image.segment("__LINKEDIT")?;
+        let start = linkedit.vmaddr.wrapping_add_signed(self.slide as i64) as *const u8;
+        let data = unsafe { std::slice::from_raw_parts(start, linkedit.vmsize as usize) };
+        let offset = (offset as u64).checked_sub(linkedit.fileoff)? as usize;
+        data.get(offset..offset.checked_add(size as usize)?)
+    }
+
+    /// The image's symbol table, read on first use
+    ///
+    /// Keyed by path as well as header: an image loaded where a closed one
+    /// was mustn't get its table.
+    fn symbols(&self) -> SymbolTable {
+        static TABLES: OnceLock<Mutex<HashMap<(usize, String), SymbolTable>>> = OnceLock::new();
+        let tables = TABLES.get_or_init(Default::default);
+        let key = (self.header, self.path.clone());
+        if let Some(table) = tables.lock().unwrap().get(&key) {
+            return table.clone();
+        }
+
+        // Read outside the lock; another thread reading the same image just wastes work
+        let table = Arc::new(unsafe { self.read_symbols() });
+        tables.lock().unwrap().entry(key).or_insert(table).clone()
+    }
+
+    /// Read the image's symbol table, and its shared cache local symbols
+    ///
+    /// # Safety
+    ///
+    /// The image must still be loaded.
+    unsafe fn read_symbols(&self) -> HashMap<String, usize> {
+        let mut table = HashMap::new();
+        let Some(image) = (unsafe { self.commands() }) else {
+            return table;
+        };
+
+        if let Some(symtab) = image.symtab {
+            let nlists = unsafe { self.linkedit(&image, symtab.symoff, symtab.nsyms as u64 * 16) };
+            let strings = unsafe { self.linkedit(&image, symtab.stroff, symtab.strsize as u64) };
+            if let (Some(nlists), Some(strings)) = (nlists, strings) {
+                table.extend(
+                    parse_nlists(nlists, strings)
+                        .into_iter()
+                        .filter(|symbol| symbol.is_defined() && !symbol.name.is_empty())
+                        .map(|symbol| (symbol.name, symbol.value as usize)),
+                );
+            }
+        }
+
+        if let Some(cache) = SharedCache::get()
+            && let Some(dylib_offset) = self.header.checked_sub(cache.start)
+            && dylib_offset < cache.size
+        {
+            for symbol in cache_local_symbols(cache.local_symbols, dylib_offset as u64, cache.wide) {
+                if symbol.is_defined() {
+                    table.entry(symbol.name).or_insert(symbol.value as usize);
+                }
+            }
+        }
+        table
+    }
+}
+
+/// The mapped shared cache and its local symbols table
+struct SharedCache {
+    start: usize,
+    size: usize,
+    local_symbols: &'static [u8],
+    wide: bool,
+}
+
+impl SharedCache {
+    /// The process's shared cache, if it has one and its symbols file is readable
+    fn get() -> Option<&'static Self> {
+        static CACHE: OnceLock<Option<SharedCache>> = OnceLock::new();
+        CACHE.get_or_init(|| unsafe { Self::open() }).as_ref()
+    }
+
+    unsafe fn open() -> Option<Self> {
+        // Private, so looked up instead of linked
+        type GetRange = unsafe extern "C" fn(*mut usize) -> *const c_void;
+        type FilePath = unsafe extern "C" fn() -> *const c_char;
+
+        let get_range = NonNull::new(unsafe { dlsym(RTLD_DEFAULT, c"_dyld_get_shared_cache_range".as_ptr()) })?;
+        let mut size = 0;
+        let start = unsafe { GetRange::from_ptr(get_range.as_ptr())(&mut size) };
+        if start.is_null() {
+            return None;
+        }
+
+        let file_path = NonNull::new(unsafe { dlsym(RTLD_DEFAULT, c"dyld_shared_cache_file_path".as_ptr()) })
+            .map(|function| unsafe { FilePath::from_ptr(function.as_ptr())() })
+            .filter(|path| !path.is_null())
+            .map(|path| unsafe { CStr::from_ptr(path) }.to_string_lossy().into_owned());
+        let path = file_path.or_else(|| {
+            CACHE_PATHS
+                .iter()
+                .find(|path| std::path::Path::new(path).exists())
+                .map(|path| path.to_string())
+        })?;
+
+        // Split caches keep the local symbols in a separate file, with its
+        // own header saying where they are
+        let data = map_file(&format!("{path}.symbols")).or_else(|| map_file(&path))?;
+        let (local_symbols, wide) = cache_local_symbols_info(data)?;
+        Some(SharedCache { start: start as usize, size, local_symbols, wide })
+    }
+}
+
+/// Map the file at `path` read-only for the rest of the process
+fn map_file(path: &str) -> Option<&'static [u8]> {
+    let file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len() as usize;
+    if len == 0 {
+        return None;
+    }
+    let data = unsafe { mmap(core::ptr::null_mut(), len, PROT_READ, MAP_PRIVATE, file.as_raw_fd(), 0) };
+    (data != MAP_FAILED).then(|| unsafe { std::slice::from_raw_parts(data.cast::<u8>(), len) })
+}
+
+/// The address of the symbol `name` in `image`, or in any loaded image
+///
+/// Unlike `dlsym`, finds non-exported symbols; `name` includes the leading
+/// underscore. Searching every image reads all their symbol tables the
+/// first time, so pass the image when it's known.
+pub fn symbol_address(image: Option<&Image>, name: &str) -> Option<NonNull<c_void>> {
+    match image {
+        Some(image) => image.symbol_address(name),
+        None => Image::all().iter().find_map(|image| image.symbol_address(name)),
+    }
+}
+
+/// The function `name` in `image`, or in any loaded image
+///
+/// Looks up a function the way `MSFindSymbol` does, without needing Substrate.
+///
+/// # Safety
+///
+/// `F` must be the symbol's actual signature.
+pub unsafe fn find_symbol<F: FunctionPointer>(image: Option<&Image>, name: &CStr) -> Option<F> {
+    let symbol = symbol_address(image, name.to_str().ok()?)?;
+    Some(unsafe { F::from_ptr(symbol.as_ptr()) })
+}
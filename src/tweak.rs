@@ -0,0 +1,165 @@
+//! Running code when a tweak dylib is loaded and unloaded
+//!
+//! [`ctor!`](crate::ctor) is the equivalent of Logos' `%ctor`: dyld calls the
+//! fn while loading the dylib, before the host's `main`. It can be limited to
+//! some processes the way a Theos filter plist limits injection:
+//!
+//! ```ignore
+//! ios_sys::ctor!(
+//!     bundles = ["com.apple.springboard"],
+//!     executables = ["backboardd"],
+//!     fn init() {
+//!         install_hooks();
+//!     }
+//! );
+//!
+//! ios_sys::dtor!(fn fini() {
+//!     flush_logs();
+//! });
+//! ```
+//!
+//! The fn pointers go in the Mach-O `__DATA,__mod_init_func` and
+//! `__DATA,__mod_term_func` sections; on other targets they are never called.
+
+use core::ffi::{CStr, c_char};
+use std::ffi::CString;
+use std::panic::{self, UnwindSafe};
+
+use crate::autorelease::autoreleasepool;
+use crate::cache::CachedClass;
+use crate::msg_send;
+use crate::objc::id;
+
+// libSystem
+#[cfg_attr(feature = "runtime", link(name = "System", kind = "dylib"))]
+unsafe extern "C" {
+    fn getprogname() -> *const c_char;
+}
+
+/// Which processes a constructor runs in
+///
+/// Like the `Filter` of a Theos plist with `Mode = Any`: the constructor
+/// runs if any listed bundle is loaded or the executable has any listed
+/// name. (Without `Mode = Any`, Substrate and ElleKit require a match for
+/// every kind of filter listed.) An empty filter matches every process.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Filter {
+    /// Bundle identifiers, any of which must be loaded
+    pub bundles: &'static [&'static str],
+    /// Executable names, one of which must be the process's
+    pub executables: &'static [&'static str],
+}
+
+impl Filter {
+    /// The filter matching every process
+    pub const EMPTY: Filter = Filter {
+        bundles: &[],
+        executables: &[],
+    };
+
+    /// Whether the current process matches
+    pub fn matches(&self) -> bool {
+        if self.bundles.is_empty() && self.executables.is_empty() {
+            return true;
+        }
+        self.executables.iter().any(|name| executable_name().is_some_and(|exe| exe == *name))
+            || self.bundles.iter().any(|bundle| bundle_loaded(bundle))
+    }
+}
+
+/// The name of the running executable, e.g. `SpringBoard`
+pub fn executable_name() -> Option<String> {
+    let name = unsafe { getprogname() };
+    (!name.is_null()).then(|| unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned())
+}
+
+/// Whether a bundle with identifier `identifier` is loaded
+pub fn bundle_loaded(identifier: &str) -> bool {
+    static NS_BUNDLE: CachedClass = CachedClass::new(c"NSBundle");
+    static NS_STRING: CachedClass = CachedClass::new(c"NSString");
+
+    let Ok(identifier) = CString::new(identifier) else {
+        return false;
+    };
+    autoreleasepool(|_| unsafe {
+        let identifier: id = msg_send![NS_STRING.get(), stringWithUTF8String: identifier.as_ptr()];
+        let bundle: id = msg_send![NS_BUNDLE.get(), bundleWithIdentifier: identifier];
+        !bundle.is_null()
+    })
+}
+
+/// Run a constructor or destructor body; used by [`ctor!`](crate::ctor) and [`dtor!`](crate::dtor)
+///
+/// Unwinding into dyld would abort the host process, so a panic is caught
+/// after the panic hook has reported it.
+#[doc(hidden)]
+pub fn __run(filter: &Filter, f: impl FnOnce() + UnwindSafe) {
+    if !filter.matches() {
+        return;
+    }
+    autoreleasepool(|_| {
+        let _ = panic::catch_unwind(f);
+    });
+}
+
+/// Run a fn when the dylib is loaded
+///
+/// `ctor!(fn name() { ... })` defines `name` and registers it in
+/// `__DATA,__mod_init_func`. Optional `bundles = [...]` and
+/// `executables = [...]` lists restrict it to matching processes, see
+/// [`Filter`]. A panic in the fn is caught instead of aborting the host.
+#[macro_export]
+macro_rules! ctor {
+    (bundles = [$($bundle:literal),* $(,)?], executables = [$($executable:literal),* $(,)?], $($item:tt)*) => {
+        $crate::ctor!(@filter [$($bundle),*] [$($executable),*] $($item)*);
+    };
+    (bundles = [$($bundle:literal),* $(,)?], $($item:tt)*) => {
+        $crate::ctor!(@filter [$($bundle),*] [] $($item)*);
+    };
+    (executables = [$($executable:literal),* $(,)?], $($item:tt)*) => {
+        $crate::ctor!(@filter [] [$($executable),*] $($item)*);
+    };
+    (@filter [$($bundle:literal),*] [$($executable:literal),*] $(#[$meta:meta])* $vis:vis fn $name:ident() $body:block $(;)?) => {
+        $(#[$meta])*
+        $vis fn $name() $body
+
+        const _: () = {
+            extern "C" fn constructor() {
+                static FILTER: $crate::tweak::Filter = $crate::tweak::Filter {
+                    bundles: &[$($bundle),*],
+                    executables: &[$($executable),*],
+                };
+                $crate::tweak::__run(&FILTER, $name);
+            }
+
+            #[used]
+            #[cfg_attr(target_vendor = "apple", unsafe(link_section = "__DATA,__mod_init_func"))]
+            static CONSTRUCTOR: extern "C" fn() = constructor;
+        };
+    };
+    ($($item:tt)*) => {
+        $crate::ctor!(@filter [] [] $($item)*);
+    };
+}
+
+/// Run a fn when the dylib is unloaded or the process exits
+///
+/// `dtor!(fn name() { ... })` defines `name` and registers it in
+/// `__DATA,__mod_term_func`. A panic in the fn is caught.
+#[macro_export]
+macro_rules! dtor {
+    ($(#[$meta:meta])* $vis:vis fn $name:ident() $body:block $(;)?) => {
+        $(#[$meta])*
+        $vis fn $name() $body
+
+        const _: () = {
+            extern "C" fn destructor() {
+                $crate::tweak::__run(&$crate::tweak::Filter::EMPTY, $name);
+            }
+
+            #[used]
+            #[cfg_attr(target_vendor = "apple", unsafe(link_section = "__DATA,__mod_term_func"))]
+            static DESTRUCTOR: extern "C" fn() = destructor;
+        };
+    };
+}